@@ -1,13 +1,30 @@
-use super::variable::{Value, Type};
+use crate::prelude::Diagnostic;
+use super::variable::{NoClosure, Value, Type, TypeBody};
 use crate::constructs::token::symbol::Symbol;
+use crate::constructs::token::span::Span;
 
+#[derive(Debug, Clone)]
 pub struct AST(pub Block);
 
 #[derive(Debug, Clone)]
 pub struct Identifier {
     pub name: String,
+    /// Where the identifier is written
+    pub span: Span,
+    /// The binding the identifier refers to, filled in when the program is verified
+    pub binding: Option<BindingId>,
 }
 
+impl Identifier {
+    pub fn new(name: impl Into<String>, span: Span) -> Self {
+        Identifier { name: name.into(), span, binding: None }
+    }
+}
+
+/// Tells apart each binding of a name, like the `n`s bound by two different matches
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct BindingId(pub u32);
+
 #[derive(Debug, Clone, PartialEq)]
 pub enum BinaryOperator {
     Range,
@@ -32,19 +49,21 @@ pub enum BinaryOperator {
 }
 
 impl BinaryOperator {
-    pub fn get_precedence_map() -> [Vec<BinaryOperator>; 10] {
-        [
-            vec![BinaryOperator::Range],
-            vec![BinaryOperator::ForEach, BinaryOperator::Map, BinaryOperator::Filter],
-            vec![BinaryOperator::All, BinaryOperator::Any],
-            vec![BinaryOperator::Mod],
-            vec![BinaryOperator::Mul, BinaryOperator::Div],
-            vec![BinaryOperator::Plus, BinaryOperator::Minus],
-            vec![BinaryOperator::NotEqual, BinaryOperator::Equal],
-            vec![BinaryOperator::Greater, BinaryOperator::GreaterEqual, BinaryOperator::Less, BinaryOperator::LessEqual],
-            vec![BinaryOperator::And],
-            vec![BinaryOperator::Or],
-        ]
+    /// How tightly the operator binds its operands. Operators with a higher
+    /// precedence are grouped first, and operators of equal precedence group left to right.
+    pub fn precedence(&self) -> u8 {
+        match self {
+            BinaryOperator::Range => 10,
+            BinaryOperator::ForEach | BinaryOperator::Map | BinaryOperator::Filter => 9,
+            BinaryOperator::All | BinaryOperator::Any => 8,
+            BinaryOperator::Mod => 7,
+            BinaryOperator::Mul | BinaryOperator::Div => 6,
+            BinaryOperator::Plus | BinaryOperator::Minus => 5,
+            BinaryOperator::NotEqual | BinaryOperator::Equal => 4,
+            BinaryOperator::Greater | BinaryOperator::GreaterEqual | BinaryOperator::Less | BinaryOperator::LessEqual => 3,
+            BinaryOperator::And => 2,
+            BinaryOperator::Or => 1,
+        }
     }
 }
 
@@ -71,24 +90,94 @@ pub enum Statement {
     Expression(Expression),
     Let(Identifier, Expression),
     Set(Identifier, Expression),
+    /// Assigns to part of a value, e.g. `grid[y][x] = 0`, `person.age = 31` or `Person::count = 0`
+    SetPlace(Identifier, Vec<Accessor>, Expression),
+    /// Declares a type with named fields
+    Type(TypeDeclaration),
+    /// Gives a type static members, e.g. `impl Person: count = 0`
+    Impl(Identifier, Vec<(Identifier, Expression)>),
     Return(Expression),
     Break(Option<Expression>),
     Continue,
+    /// A statement which failed to parse.
+    Error(Diagnostic),
+}
+
+/// One step into the value being assigned to, like `[0]`, `.age` or `::count`
+#[derive(Debug, Clone)]
+pub enum Accessor {
+    Index(Index),
+    Field(Identifier),
+    Static(Identifier),
+}
+
+/// A type declared with `type`, e.g.
+/// ```text
+/// type Person:
+///   name: string
+///   age: int
+/// type Shape: Circle(float) | Rect(float, float)
+/// ```
+#[derive(Debug, Clone)]
+pub struct TypeDeclaration {
+    pub name: Identifier,
+    pub body: TypeBody,
 }
 
 #[derive(Debug, Clone)]
 pub struct Pattern {
-    pub identifier: Option<Identifier>,
-    pub typ: Option<Type>,
+    pub kind: PatternKind,
     pub guard: Option<Expression>,
+    /// Where the pattern is written, from its bar to its colon
+    pub span: Span,
 }
 
 #[derive(Debug, Clone)]
-pub enum Function {
+pub enum PatternKind {
+    /// Matches anything without binding it, e.g. the pattern of `|~ num > 0 :`
+    Empty,
+    /// Binds the value to an identifier if it has the right type, e.g. `int num` or `num`
+    Binding {
+        typ: Option<Type>,
+        identifier: Identifier,
+    },
+    /// Matches a tuple with one element per pattern, e.g. `(int a, (b, c))`
+    Tuple(Vec<PatternKind>),
+    /// Matches anything without binding it, e.g. `_`
+    Wildcard,
+    /// Matches a value equal to a literal, e.g. `0`, `"quit"` or `true`
+    Literal(Value),
+    /// Matches a value from `start` up to `end`, e.g. `1..10`, or `1..=10` to include the end
+    Range {
+        start: Value,
+        end: Value,
+        inclusive: bool,
+    },
+    /// Matches if any of the patterns match, e.g. `1 | 2 | 3`. Each of them binds the same identifiers.
+    Or(Vec<PatternKind>),
+    /// Matches a variant of an enum type, with one pattern per value in its payload, e.g. `Shape::Rect(w, h)`
+    Variant {
+        typ: Identifier,
+        variant: Identifier,
+        payload: Vec<PatternKind>,
+    },
+}
+
+/// Something that can be called. `C` is a match made while running the program, which is up to
+/// whatever runs it, so see [`Value`] for what it is.
+#[derive(Debug, Clone)]
+pub enum Function<C = NoClosure> {
     Match {
         arms: Vec<MatchArm>,
     },
-    Builtin(Symbol)
+    Builtin(Symbol),
+    /// Makes a variant of an enum type from its payload, like `Shape::Circle`
+    Constructor {
+        typ: String,
+        variant: String,
+        payload: Vec<Type>,
+    },
+    Closure(C),
 }
 
 #[derive(Debug, Clone)]
@@ -98,15 +187,58 @@ pub struct MatchArm {
 }
 
 #[derive(Debug, Clone)]
-pub enum Expression {
+pub struct Expression {
+    pub kind: ExpressionKind,
+    /// Where the expression is written, so errors about it can point to it
+    pub span: Span,
+}
+
+impl Expression {
+    pub fn new(kind: ExpressionKind, span: Span) -> Self {
+        Expression { kind, span }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum ExpressionKind {
     Literal(Value),
     Identifier(Identifier),
     Binary(Box<Expression>, BinaryOperator, Box<Expression>),
     Unary(UnaryOperator, Box<Expression>),
     Call(Box<Expression>, Box<Expression>),
+    /// A string with expressions in it, e.g. `"{num} squared is {num * num}"`
+    Interpolated(Vec<InterpolatedPart>),
+    /// A tuple of values, e.g. `(1, "two", 3.0)`
+    Tuple(Vec<Expression>),
+    /// A list of values, e.g. `[1, 2, 3]`
+    List(Vec<Expression>),
+    /// A dictionary of keys and values, e.g. `{"one": 1, "two": 2}`
+    Dict(Vec<(Expression, Expression)>),
+    /// An element or slice of a value, e.g. `xs[0]` or `xs[1..3]`
+    Index(Box<Expression>, Index),
+    /// A value of a declared type, e.g. `Person: name: "Bob", age: 31`
+    Construct(Identifier, Vec<(Identifier, Expression)>),
+    /// A field of a value, e.g. `person.name`
+    Field(Box<Expression>, Identifier),
+    /// A static member of a type, e.g. `Person::new`
+    Static(Identifier, Identifier),
     // Grouping(Box<Expression>),
     // Call(Box<Expression>, Vec<Expression>),
     // If(Box<Expression>, Box<Block>, Option<Box<Block>>),
     // While(Box<Expression>, Box<Block>),
     // For(Identifier, Box<Expression>, Box<Expression>, Box<Block>),
-}
\ No newline at end of file
+}
+
+/// The inside of the square brackets after a value, e.g. `i + 1` in `xs[i + 1]`
+#[derive(Debug, Clone)]
+pub struct Index {
+    pub expression: Box<Expression>,
+    /// Where the index is written, so out of bounds errors can point to it
+    pub span: Span,
+}
+
+#[derive(Debug, Clone)]
+pub enum InterpolatedPart {
+    Text(String),
+    Expression(Expression),
+}
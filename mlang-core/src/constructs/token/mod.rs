@@ -1,3 +1,5 @@
+use std::fmt::Display;
+
 use crate::prelude::*;
 use crate::constructs::ast::{BinaryOperator, UnaryOperator};
 
@@ -5,6 +7,7 @@ pub mod span;
 use span::Span;
 pub mod symbol;
 use symbol::Symbol;
+pub mod trivia;
 mod tokens;
 pub use tokens::*;
 
@@ -17,6 +20,7 @@ pub enum TokenKind {
     // Single-character tokens.
     LeftParen, RightParen, 
     LeftSqrBrace, RightSqrBrace,
+    LeftBrace, RightBrace,
     Comma, Dot, Minus, Plus, Semicolon, Slash, Star,
     Percent,
 
@@ -40,8 +44,11 @@ pub enum TokenKind {
     // Literals.
     Identifier(Symbol), 
     Literal(Literal),
+    /// A string with expressions in it, e.g. `"{num} squared is {num * num}"`
+    InterpolatedString(Vec<StringPart>),
 
     // Whitespace.
+    /// A line break, holding the indentation of the next line in columns
     Newline(usize),
 
     // Keywords.
@@ -68,7 +75,23 @@ pub struct Literal {
     pub symbol: Symbol
 }
 
+#[derive(Debug, Clone, PartialEq)]
+pub enum StringPart {
+    Text(String),
+    /// The tokens of an interpolated expression, with spans into the file the string is in
+    Expression(Vec<Token>),
+}
+
 impl TokenKind {
+    /// How the token changes the depth of nested brackets: 1 for an opening bracket, -1 for a closing bracket and 0 otherwise
+    pub fn nesting(&self) -> isize {
+        match self {
+            TokenKind::LeftParen | TokenKind::LeftSqrBrace | TokenKind::LeftBrace => 1,
+            TokenKind::RightParen | TokenKind::RightSqrBrace | TokenKind::RightBrace => -1,
+            _ => 0,
+        }
+    }
+
     pub fn as_binary_operator(&self, span: Option<Span>) -> Result<BinaryOperator> {
         match self {
             TokenKind::DotDot => Ok(BinaryOperator::Range),
@@ -90,7 +113,7 @@ impl TokenKind {
             TokenKind::Slash => Ok(BinaryOperator::Div),
             TokenKind::Plus => Ok(BinaryOperator::Plus),
             TokenKind::Minus => Ok(BinaryOperator::Minus),
-            _ => semantic_err!(span, "\"{self:?}\" is not a binary operator"),
+            _ => syntax_err!(codes::NOT_AN_OPERATOR, span, "`{self}` is not a binary operator"),
         }
     }
 
@@ -98,7 +121,70 @@ impl TokenKind {
         match self {
             TokenKind::Minus => Ok(UnaryOperator::Minus),
             TokenKind::Exclamation => Ok(UnaryOperator::Not),
-            _ => semantic_err!(span, "\"{self:?}\" is not a unary operator"),
+            _ => syntax_err!(codes::NOT_AN_OPERATOR, span, "`{self}` is not a unary operator"),
         }
     }
-}
\ No newline at end of file
+}
+
+/// Writes the token the way it's spelled in the source, e.g. `::`, `let` or `"text"`
+impl Display for TokenKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let spelling = match self {
+            TokenKind::LeftParen => "(",
+            TokenKind::RightParen => ")",
+            TokenKind::LeftSqrBrace => "[",
+            TokenKind::RightSqrBrace => "]",
+            TokenKind::LeftBrace => "{",
+            TokenKind::RightBrace => "}",
+            TokenKind::Comma => ",",
+            TokenKind::Dot => ".",
+            TokenKind::Minus => "-",
+            TokenKind::Plus => "+",
+            TokenKind::Semicolon => ";",
+            TokenKind::Slash => "/",
+            TokenKind::Star => "*",
+            TokenKind::Percent => "%",
+            TokenKind::Bar(_) => "|",
+            TokenKind::Colon => ":",
+            TokenKind::Tilde => "~",
+            TokenKind::Dollar => "$",
+            TokenKind::At => "@",
+            TokenKind::Hash => "#",
+            TokenKind::TripleAmp => "&&&",
+            TokenKind::TripleBar => "|||",
+            TokenKind::DotDot => "..",
+            TokenKind::Amp => "&",
+            TokenKind::Caret => "^",
+            TokenKind::Question => "?",
+            TokenKind::Exclamation => "!",
+            TokenKind::ExclamationEqual => "!=",
+            TokenKind::Equal => "=",
+            TokenKind::EqualEqual => "==",
+            TokenKind::Greater => ">",
+            TokenKind::GreaterEqual => ">=",
+            TokenKind::Less => "<",
+            TokenKind::LessEqual => "<=",
+            TokenKind::DoubleAmp => "&&",
+            TokenKind::DoubleBar => "||",
+            TokenKind::ColonColon => "::",
+            TokenKind::Newline(_) => "line break",
+            TokenKind::Identifier(symbol) | TokenKind::Keyword(symbol) => return write!(f, "{}", symbol),
+            TokenKind::Literal(Literal { kind: LiteralKind::String, symbol }) => return write!(f, "\"{}\"", symbol.get_str().escape_debug()),
+            TokenKind::Literal(Literal { symbol, .. }) => return write!(f, "{}", symbol),
+            TokenKind::InterpolatedString(parts) => {
+                f.write_str("\"")?;
+                for part in parts {
+                    match part {
+                        StringPart::Text(text) => write!(f, "{}", text.escape_debug())?,
+                        StringPart::Expression(tokens) => {
+                            let tokens: Vec<String> = tokens.iter().map(|Token(kind, _)| kind.to_string()).collect();
+                            write!(f, "{{{}}}", tokens.join(" "))?;
+                        }
+                    }
+                }
+                return f.write_str("\"");
+            }
+        };
+        f.write_str(spelling)
+    }
+}
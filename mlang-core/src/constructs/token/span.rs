@@ -5,7 +5,7 @@ use std::ops::Add;
 pub struct Span {
     /// The beginning index of the span.
     pub index: u32,
-    pub len: u16,
+    pub len: u32,
 }
 
 impl Add for Span {
@@ -19,7 +19,7 @@ impl Add for Span {
         };
         Span {
             index: first.index,
-            len: (second.index - first.index + second.len as u32) as u16
+            len: second.index - first.index + second.len
         }
     }
 }
@@ -34,7 +34,7 @@ impl Span {
 
     pub fn after(&self) -> Self {
         Span {
-            index: (self.index + self.len as u32),
+            index: self.index + self.len,
             len: 1
         }
     }
@@ -44,10 +44,257 @@ impl Span {
     }
 
     pub fn ending(&self) -> usize {
-        self.index.saturating_add(self.len as u32).saturating_sub(1) as usize
+        self.index.saturating_add(self.len).saturating_sub(1) as usize
     }
 
     pub fn length(&self) -> usize {
         self.len as usize
     }
-}
\ No newline at end of file
+}
+
+/// Identifies a file in a [`SourceMap`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct FileId(pub u32);
+
+/// What the column of a [`LineColumn`] counts
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ColumnUnit {
+    /// Bytes, as used by spans
+    Utf8,
+    /// UTF-16 code units, as used by most editors and the language server protocol
+    Utf16,
+    /// Characters, as shown to people
+    Char,
+}
+
+/// A line and column in a source file, both starting at 0
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct LineColumn {
+    pub line: usize,
+    pub column: usize,
+}
+
+/// The source of a single file, with the start of each line precomputed
+/// so that byte offsets can be converted to lines and columns and back.
+#[derive(Debug, Clone)]
+pub struct SourceFile {
+    id: FileId,
+    name: String,
+    source: String,
+    /// Byte offset of the start of each line
+    line_starts: Vec<usize>,
+}
+
+impl SourceFile {
+    pub fn new(id: FileId, name: impl Into<String>, source: impl Into<String>) -> Self {
+        let source = source.into();
+        let line_starts = std::iter::once(0)
+            .chain(source.match_indices('\n').map(|(idx, _)| idx + 1))
+            .collect();
+
+        SourceFile { id, name: name.into(), source, line_starts }
+    }
+
+    pub fn with_name(mut self, name: impl Into<String>) -> Self {
+        self.set_name(name);
+        self
+    }
+
+    pub fn set_name(&mut self, name: impl Into<String>) {
+        self.name = name.into();
+    }
+
+    pub fn id(&self) -> FileId {
+        self.id
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    pub fn line_count(&self) -> usize {
+        self.line_starts.len()
+    }
+
+    /// The text of a line, without its line ending
+    pub fn line(&self, line: usize) -> Option<&str> {
+        let start = *self.line_starts.get(line)?;
+        let end = self.line_starts.get(line + 1).copied().unwrap_or(self.source.len());
+
+        Some(self.source[start..end].trim_end_matches(['\n', '\r']))
+    }
+
+    /// Converts a byte offset to a line and column. Offsets past the end of the file
+    /// are clamped to it, and offsets inside a character are moved to its start.
+    pub fn line_column(&self, offset: usize, unit: ColumnUnit) -> LineColumn {
+        let mut offset = offset.min(self.source.len());
+        while !self.source.is_char_boundary(offset) {
+            offset -= 1;
+        }
+
+        let line = self.line_starts.partition_point(|&start| start <= offset) - 1;
+        let before = &self.source[self.line_starts[line]..offset];
+        let column = match unit {
+            ColumnUnit::Utf8 => before.len(),
+            ColumnUnit::Utf16 => before.encode_utf16().count(),
+            ColumnUnit::Char => before.chars().count(),
+        };
+
+        LineColumn { line, column }
+    }
+
+    /// Converts a line and column back to a byte offset. Returns `None` if the line does not exist,
+    /// or the column is past the end of the line or inside a character.
+    pub fn offset(&self, position: LineColumn, unit: ColumnUnit) -> Option<usize> {
+        let start = *self.line_starts.get(position.line)?;
+        let text = self.line(position.line)?;
+
+        let column_offset = match unit {
+            ColumnUnit::Utf8 => text.is_char_boundary(position.column).then_some(position.column),
+            ColumnUnit::Utf16 => {
+                let mut units = 0;
+                text.char_indices()
+                    .map(|(idx, chr)| {
+                        let units_before = units;
+                        units += chr.len_utf16();
+                        (idx, units_before)
+                    })
+                    .chain(std::iter::once((text.len(), text.encode_utf16().count())))
+                    .find(|&(_, units_before)| units_before == position.column)
+                    .map(|(idx, _)| idx)
+            }
+            ColumnUnit::Char => text.char_indices()
+                .map(|(idx, _)| idx)
+                .chain(std::iter::once(text.len()))
+                .nth(position.column),
+        }?;
+
+        Some(start + column_offset)
+    }
+}
+
+/// Every source file taking part in a run, so that spans from different files can coexist.
+/// Spans are offsets into their own file, looked up through the file's [`FileId`].
+#[derive(Debug, Clone, Default)]
+pub struct SourceMap {
+    files: Vec<SourceFile>,
+}
+
+impl SourceMap {
+    pub fn add_file(&mut self, name: impl Into<String>, source: impl Into<String>) -> FileId {
+        let id = FileId(self.files.len() as u32);
+        self.files.push(SourceFile::new(id, name, source));
+        id
+    }
+
+    pub fn get(&self, id: FileId) -> Option<&SourceFile> {
+        self.files.get(id.0 as usize)
+    }
+
+    pub fn get_mut(&mut self, id: FileId) -> Option<&mut SourceFile> {
+        self.files.get_mut(id.0 as usize)
+    }
+
+    pub fn files(&self) -> impl Iterator<Item = &SourceFile> {
+        self.files.iter()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn file(source: &str) -> SourceFile {
+        let mut map = SourceMap::default();
+        let id = map.add_file("test.mlg", source);
+        map.get(id).unwrap().clone()
+    }
+
+    /// Checks that every offset at the start of a character, and the end of the file, converts to a line
+    /// and column and back. The `\n` of a `\r\n` is skipped, since it shares a column with the `\r`.
+    fn assert_round_trips(source: &str, unit: ColumnUnit) {
+        let file = file(source);
+        for offset in (0..=source.len()).filter(|&offset| source.is_char_boundary(offset)) {
+            if source[..offset].ends_with('\r') {
+                continue;
+            }
+            let position = file.line_column(offset, unit);
+            assert_eq!(file.offset(position, unit), Some(offset), "{:?} at offset {} is {:?}", source, offset, position);
+        }
+    }
+
+    #[test]
+    fn ascii() {
+        let file = file("let x = 1\nx println");
+        assert_eq!(file.line_column(14, ColumnUnit::Utf8), LineColumn { line: 1, column: 4 });
+        assert_eq!(file.line(1), Some("x println"));
+        assert_round_trips(file.source(), ColumnUnit::Utf8);
+        assert_round_trips(file.source(), ColumnUnit::Char);
+    }
+
+    #[test]
+    fn multi_byte_characters() {
+        let source = "let é = \"ü\"\né println";
+        let file = file(source);
+        // `=` is after the two bytes of `é`
+        assert_eq!(file.line_column(7, ColumnUnit::Utf8), LineColumn { line: 0, column: 7 });
+        assert_eq!(file.line_column(7, ColumnUnit::Char), LineColumn { line: 0, column: 6 });
+        assert_eq!(file.line_column(7, ColumnUnit::Utf16), LineColumn { line: 0, column: 6 });
+        // Offsets inside a character move to its start, and columns inside one don't exist
+        assert_eq!(file.line_column(5, ColumnUnit::Utf8), LineColumn { line: 0, column: 4 });
+        assert_eq!(file.offset(LineColumn { line: 0, column: 5 }, ColumnUnit::Utf8), None);
+        for unit in [ColumnUnit::Utf8, ColumnUnit::Utf16, ColumnUnit::Char] {
+            assert_round_trips(source, unit);
+        }
+    }
+
+    #[test]
+    fn astral_plane_characters() {
+        let source = "\"😀\" println";
+        let file = file(source);
+        // The emoji is 4 bytes, 2 UTF-16 code units, and 1 character
+        assert_eq!(file.line_column(5, ColumnUnit::Utf8).column, 5);
+        assert_eq!(file.line_column(5, ColumnUnit::Utf16).column, 3);
+        assert_eq!(file.line_column(5, ColumnUnit::Char).column, 2);
+        // Between the two halves of a surrogate pair isn't a position in the file
+        assert_eq!(file.offset(LineColumn { line: 0, column: 2 }, ColumnUnit::Utf16), None);
+        for unit in [ColumnUnit::Utf8, ColumnUnit::Utf16, ColumnUnit::Char] {
+            assert_round_trips(source, unit);
+        }
+    }
+
+    #[test]
+    fn crlf() {
+        let source = "a\r\nbc\r\nd";
+        let file = file(source);
+        assert_eq!(file.line_count(), 3);
+        assert_eq!(file.line(1), Some("bc"));
+        assert_eq!(file.line_column(3, ColumnUnit::Utf8), LineColumn { line: 1, column: 0 });
+        // A column past the `\r` is past the end of the line
+        assert_eq!(file.offset(LineColumn { line: 0, column: 2 }, ColumnUnit::Utf8), None);
+        assert_round_trips(source, ColumnUnit::Utf8);
+        assert_round_trips(source, ColumnUnit::Char);
+    }
+
+    #[test]
+    fn last_line() {
+        let file = file("a\nbc");
+        assert_eq!(file.line_column(4, ColumnUnit::Utf8), LineColumn { line: 1, column: 2 });
+        // Offsets past the end are clamped to it
+        assert_eq!(file.line_column(100, ColumnUnit::Utf8), LineColumn { line: 1, column: 2 });
+        assert_eq!(file.offset(LineColumn { line: 1, column: 2 }, ColumnUnit::Utf8), Some(4));
+        assert_eq!(file.offset(LineColumn { line: 1, column: 3 }, ColumnUnit::Utf8), None);
+        assert_eq!(file.offset(LineColumn { line: 2, column: 0 }, ColumnUnit::Utf8), None);
+
+        // A trailing line break starts an empty last line
+        let file = SourceFile::new(FileId(0), "test.mlg", "a\n");
+        assert_eq!(file.line_count(), 2);
+        assert_eq!(file.line(1), Some(""));
+        assert_eq!(file.line_column(2, ColumnUnit::Utf8), LineColumn { line: 1, column: 0 });
+        assert_round_trips("a\n", ColumnUnit::Utf8);
+    }
+}
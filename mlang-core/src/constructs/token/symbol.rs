@@ -1,29 +1,26 @@
+use std::collections::HashMap;
 use std::fmt::Display;
+use std::sync::RwLock;
 
 use lazy_static::lazy_static;
 
-type SymbolIndex = u8;
+type SymbolIndex = u32;
 
+/// An interned string. Symbols with the same string are equal, so comparing them is cheap.
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub struct Symbol(SymbolIndex);
 
-struct SymbolPool([*const str; 256]);
-
-static mut SYMBOL_POOL: SymbolPool = SymbolPool([std::ptr::slice_from_raw_parts::<u8>(core::ptr::NonNull::dangling().as_ptr(), 0) as *const str; 256]);
-static mut POOL_SIZE: SymbolIndex = 0;
-
-impl Drop for SymbolPool {
-    fn drop(&mut self) {
-        for ptr in self.0 {
-            unsafe {
-                let layout = std::alloc::Layout::for_value(ptr.as_ref().unwrap());
-                std::alloc::dealloc(ptr.cast::<u8>() as *mut u8, layout);
-            }
-        }
-    }
+/// Every interned string, shared between threads. Interned strings are never freed,
+/// so they can be handed out as `&'static str`.
+#[derive(Default)]
+struct SymbolPool {
+    strings: Vec<&'static str>,
+    indices: HashMap<&'static str, SymbolIndex>,
 }
 
-unsafe impl Sync for SymbolPool {}
+lazy_static! {
+    static ref SYMBOL_POOL: RwLock<SymbolPool> = RwLock::new(SymbolPool::default());
+}
 
 impl Display for Symbol {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
@@ -33,39 +30,31 @@ impl Display for Symbol {
 
 impl Symbol {
     pub fn from(str: &str) -> Self {
-        if !unsafe { INITIALIZED_BUILTINS } {
-            let _ = BUILTIN_SYMBOLS; // touch, to initialize builtins.
-            unsafe { INITIALIZED_BUILTINS = true };
+        if let Some(&index) = SYMBOL_POOL.read().unwrap().indices.get(str) {
+            return Self(index);
         }
 
-        match (0..unsafe { POOL_SIZE }).find(|&idx| {str == Symbol(idx).get_str()}) {
-            Some(idx) => Self(idx),
-            None => Self::intern(str)
-        }
+        Self::intern(str)
     }
 
     fn intern(str: &str) -> Self {
-        unsafe { 
-            // Copy str into a new memory address
-            let ptr = {
-                let layout = std::alloc::Layout::for_value(str);
-                let ptr = std::alloc::alloc(layout);
-
-                ptr.copy_from_nonoverlapping((str as *const str).cast::<u8>(), str.len());
-                std::ptr::slice_from_raw_parts(ptr, str.len()) as *const str
-            };
-            
-            let index = POOL_SIZE;
-            SYMBOL_POOL.0[POOL_SIZE as usize] = ptr;
-            POOL_SIZE += 1;
+        let mut pool = SYMBOL_POOL.write().unwrap();
 
-            Self(index)
+        // Another thread may have interned the string between dropping the read lock and taking the write lock
+        if let Some(&index) = pool.indices.get(str) {
+            return Self(index);
         }
+
+        let index = SymbolIndex::try_from(pool.strings.len()).expect("Too many symbols");
+        let str: &'static str = Box::leak(str.into());
+        pool.strings.push(str);
+        pool.indices.insert(str, index);
+
+        Self(index)
     }
 
-    pub fn get_str(&self) -> &str {
-        // Guaranteed to be safe because self.0 is guaranteed to be less than POOL_SIZE
-        unsafe { &*(SYMBOL_POOL.0[self.0 as usize]) }
+    pub fn get_str(&self) -> &'static str {
+        SYMBOL_POOL.read().unwrap().strings[self.0 as usize]
     }
 }
 
@@ -73,24 +62,11 @@ macro_rules! pre_intern {
     (
         $($name:ident: $expr:expr),*
     ) => {
-        #[allow(non_snake_case)]
-        struct Builtins {
-            $(pub $name: Symbol),*
-        }
-
-        static mut INITIALIZED_BUILTINS: bool = false;
-
-        lazy_static! {
-            static ref BUILTIN_SYMBOLS: Builtins = Builtins {
-                $($name: Symbol::intern($expr)),*
-            };
-        }
-
         pub mod builtin_symbols {
             use lazy_static::lazy_static;
             
             lazy_static! {
-                $(pub static ref $name: super::Symbol = super::BUILTIN_SYMBOLS.$name;)*
+                $(pub static ref $name: super::Symbol = super::Symbol::from($expr);)*
             }
         }
     }
@@ -101,6 +77,7 @@ pre_intern! {
     LET: "let",
     STRUCT: "struct",
     IMPL: "impl",
+    TYPE: "type",
     RETURN: "return",
     YIELD: "yield",
     // Boolean
@@ -108,6 +85,10 @@ pre_intern! {
     FALSE: "false",
     // Intrinsics
     PRINT: "print",
-    PRINTLN: "print",
-    ASSERT: "assert"
+    PRINTLN: "println",
+    ASSERT: "assert",
+    GET: "get",
+    SET: "set",
+    REMOVE: "remove",
+    CONTAINS: "contains"
 }
\ No newline at end of file
@@ -1,6 +1,7 @@
 use std::fmt::Debug;
 
 use crate::constructs::token::Token;
+use crate::constructs::token::span::Span;
 
 #[derive(Clone)]
 pub struct Tokens<'a> {
@@ -34,7 +35,7 @@ impl<'a> Iterator for Tokens<'a> {
 }
 
 impl<'a> Tokens<'a> {
-    pub fn new(tokens: &Vec<Token>) -> Tokens {
+    pub fn new(tokens: &Vec<Token>) -> Tokens<'_> {
         Tokens {
             tokens,
             index: 0,
@@ -42,22 +43,30 @@ impl<'a> Tokens<'a> {
         }
     }
 
-    pub fn peek(&self) -> Option<&Token> {
+    pub fn peek(&self) -> Option<&'a Token> {
         if self.index >= self.end {
             return None;
         }
         self.tokens.get(self.index)
     }
 
-    pub fn peek_n(&self, n: isize) -> Option<&Token> {
+    pub fn peek_n(&self, n: isize) -> Option<&'a Token> {
         self.tokens.get((self.index as isize + n) as usize)
     }
 
+    /// The span from `start` to the end of the last token taken
+    pub fn span_from(&self, start: Span) -> Span {
+        match self.peek_n(-1) {
+            Some(Token(_, end)) => start + *end,
+            None => start,
+        }
+    }
+
     pub fn skip(&mut self, n: usize) {
         self.index += n;
     }
 
-    pub fn take(&mut self, n: usize) -> Tokens {
+    pub fn take(&mut self, n: usize) -> Tokens<'a> {
         let queue = Tokens {
             tokens: self.tokens,
             index: self.index,
@@ -0,0 +1,69 @@
+use super::Token;
+use super::span::Span;
+
+/// Source text which doesn't affect the meaning of a program
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TriviaKind {
+    /// Spaces, tabs and carriage returns
+    Whitespace,
+    /// A line break which isn't part of a [`TokenKind::Newline`](super::TokenKind::Newline) token
+    Newline,
+    /// `// ...`, up to but not including the line break
+    LineComment,
+    /// `/* ... */`
+    BlockComment,
+    /// `/// ...`, up to but not including the line break
+    DocComment,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Trivia {
+    pub kind: TriviaKind,
+    pub span: Span,
+}
+
+impl Trivia {
+    pub fn text<'a>(&self, source: &'a str) -> &'a str {
+        &source[self.span.beginning()..self.span.beginning() + self.span.length()]
+    }
+}
+
+/// A token with the trivia that comes before it
+#[derive(Debug, Clone, PartialEq)]
+pub struct LosslessToken {
+    pub leading_trivia: Vec<Trivia>,
+    pub token: Token,
+}
+
+/// Every token in a file, with all of the trivia between them. Each byte of the source is in exactly
+/// one token or trivia span, in order, so writing them all out reproduces the source byte for byte.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LosslessTokens {
+    pub tokens: Vec<LosslessToken>,
+    /// Trivia after the last token, up to the end of the file
+    pub trailing_trivia: Vec<Trivia>,
+}
+
+impl LosslessTokens {
+    /// Writes out every token and trivia span, which gives back `source`
+    pub fn to_source(&self, source: &str) -> String {
+        let mut output = String::with_capacity(source.len());
+
+        for LosslessToken { leading_trivia, token: Token(_, span) } in &self.tokens {
+            for trivia in leading_trivia {
+                output.push_str(trivia.text(source));
+            }
+            output.push_str(&source[span.beginning()..span.beginning() + span.length()]);
+        }
+        for trivia in &self.trailing_trivia {
+            output.push_str(trivia.text(source));
+        }
+
+        output
+    }
+
+    /// The tokens without their trivia, as [`parse_tokens`](crate::tokenize::parse_tokens) returns them
+    pub fn into_tokens(self) -> Vec<Token> {
+        self.tokens.into_iter().map(|token| token.token).collect()
+    }
+}
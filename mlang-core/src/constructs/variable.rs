@@ -1,3 +1,5 @@
+use std::collections::BTreeMap;
+
 use crate::prelude::*;
 use super::ast::Function;
 use super::token::span::Span;
@@ -13,6 +15,12 @@ pub enum Type {
     Match,
     Tuple(Vec<Type>),
     List(Box<Type>),
+    /// A dictionary, written like `{string: int}`
+    Dict(Box<Type>, Box<Type>),
+    /// A type declared with `type`, e.g. `Person`
+    Named(String),
+    /// The type of a declared type itself, like the value of `Person`
+    Type,
     Iter,
     Builtin,
     None,
@@ -34,43 +42,155 @@ impl Type {
                 if let Some(('[', ']')) = Option::zip(chars.next(), chars.last()) {
                     return Some(Type::List(Box::new(Type::from_id(&s[1..s.len() - 1])?)));
                 }
+                // Declared types start with a capital letter, which keeps typos of the builtin types an error
+                if s.starts_with(char::is_uppercase) {
+                    return Some(Type::Named(s.to_string()));
+                }
                 None
             },
         }
     }
 }
 
+/// Writes the type the way it's written in mlang, e.g. `[int]` or `{string: (int, float)}`
+impl std::fmt::Display for Type {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Type::Int => write!(f, "int"),
+            Type::Float => write!(f, "float"),
+            Type::String => write!(f, "string"),
+            Type::Boolean => write!(f, "bool"),
+            Type::Match => write!(f, "match"),
+            Type::Tuple(types) if types.is_empty() => write!(f, "tuple"),
+            Type::Tuple(types) => {
+                let types: Vec<String> = types.iter().map(Type::to_string).collect();
+                write!(f, "({})", types.join(", "))
+            }
+            Type::List(element) => write!(f, "[{}]", element),
+            Type::Dict(key, value) => write!(f, "{{{}: {}}}", key, value),
+            Type::Named(name) => write!(f, "{}", name),
+            Type::Type => write!(f, "type"),
+            Type::Iter => write!(f, "iter"),
+            Type::Builtin => write!(f, "builtin"),
+            Type::None => write!(f, "none"),
+        }
+    }
+}
+
+/// A value, either written in the program or made while running it. `C` is what a match made while
+/// running the program turns into, e.g. one that keeps the identifiers around it, and belongs to
+/// whatever runs the program. The syntax tree has none of those, so it uses [`NoClosure`].
 #[derive(Debug, Clone)]
-pub enum Value {
+pub enum Value<C = NoClosure> {
     Int(isize),
     Float(f64),
     String(String),
     Boolean(bool),
     IntRange(isize, isize),
-    Function(Function),
-    Tuple(Box<[Value]>),
-    List(Vec<Value>),
-    Filter(Box<Value>, Function),
-    Map(Box<Value>, Function),
+    Function(Function<C>),
+    Tuple(Box<[Value<C>]>),
+    List(Vec<Value<C>>),
+    /// Iterates in order of the keys, so that it's the same every run
+    Dict(BTreeMap<Key, Value<C>>),
+    Filter(Box<Value<C>>, Function<C>),
+    Map(Box<Value<C>>, Function<C>),
+    /// A value of a declared type, holding its type's name and its fields in the order they were declared
+    Struct(String, Vec<(String, Value<C>)>),
+    /// A variant of a declared enum type, holding the type's name, the variant's name and its payload
+    Variant(String, String, Box<[Value<C>]>),
+    Type(Box<TypeDefinition<C>>),
     None,
 }
 
+/// The [`Function::Closure`] of values which can't hold one, like those in the syntax tree
+#[derive(Debug, Clone, Copy)]
+pub enum NoClosure {}
+
+impl std::fmt::Display for NoClosure {
+    fn fmt(&self, _: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match *self {}
+    }
+}
+
+/// A type declared with `type`, along with the static members given to it by `impl` blocks
+#[derive(Debug, Clone)]
+pub struct TypeDefinition<C = NoClosure> {
+    pub name: String,
+    pub body: TypeBody,
+    pub statics: BTreeMap<String, Value<C>>,
+}
+
+/// What a value of a declared type holds
+#[derive(Debug, Clone)]
+pub enum TypeBody {
+    /// Named fields, e.g. `x: int, y: int`
+    Struct(Vec<(String, Type)>),
+    /// Variants, each with the types of its payload, e.g. `Circle(float) | Rect(float, float) | Empty`
+    Enum(Vec<(String, Vec<Type>)>),
+}
+
+/// A value which can be used as a key of a [`Value::Dict`]. Floats and functions can't be keys,
+/// because they can't be put in order.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Key {
+    Int(isize),
+    String(String),
+    Boolean(bool),
+    Tuple(Vec<Key>),
+}
+
+impl Key {
+    pub fn from_value<C>(value: &Value<C>) -> Option<Key> {
+        match value {
+            Value::Int(i) => Some(Key::Int(*i)),
+            Value::String(s) => Some(Key::String(s.clone())),
+            Value::Boolean(b) => Some(Key::Boolean(*b)),
+            Value::Tuple(values) => values.iter().map(Key::from_value).collect::<Option<_>>().map(Key::Tuple),
+            _ => None,
+        }
+    }
+}
+
+impl<C> From<Key> for Value<C> {
+    fn from(key: Key) -> Value<C> {
+        match key {
+            Key::Int(i) => Value::Int(i),
+            Key::String(s) => Value::String(s),
+            Key::Boolean(b) => Value::Boolean(b),
+            Key::Tuple(keys) => Value::Tuple(keys.into_iter().map(Value::from).collect()),
+        }
+    }
+}
+
 impl TryFrom<(Literal, Span)> for Value {
-    type Error = MLGError;
+    type Error = Diagnostic;
 
     fn try_from((lit, span): (Literal, Span)) -> std::result::Result<Self, Self::Error> {
         let symbol = lit.symbol.get_str();
 
         Ok(match lit.kind {
             LiteralKind::String => Value::String(symbol.to_string()),
-            LiteralKind::Int => Value::Int(symbol.parse().or(syntax_err!(Some(span), "Failed to parse int {}", symbol))?),
-            LiteralKind::Float => Value::Float(symbol.parse().or(syntax_err!(Some(span), "Failed to parse float {}", symbol))?),
-            LiteralKind::Bool => Value::Boolean(symbol.parse().or(syntax_err!(Some(span), "Failed to parse bool {}", symbol))?),
+            LiteralKind::Int => Value::Int(parse_int_literal(symbol).or(syntax_err!(codes::INVALID_LITERAL, Some(span), "Int literal {} is too large", symbol))?),
+            LiteralKind::Float => Value::Float(symbol.replace('_', "").parse().or(syntax_err!(codes::INVALID_LITERAL, Some(span), "Failed to parse float {}", symbol))?),
+            LiteralKind::Bool => Value::Boolean(symbol.parse().or(syntax_err!(codes::INVALID_LITERAL, Some(span), "Failed to parse bool {}", symbol))?),
         })
     }
 }
 
-impl std::fmt::Display for Value {
+/// Parses an int literal which the tokenizer has already checked, e.g. `1_000` or `0xFF`
+fn parse_int_literal(literal: &str) -> std::result::Result<isize, std::num::ParseIntError> {
+    let digits = literal.replace('_', "");
+    let (radix, digits) = match digits.get(..2) {
+        Some("0x" | "0X") => (16, &digits[2..]),
+        Some("0o" | "0O") => (8, &digits[2..]),
+        Some("0b" | "0B") => (2, &digits[2..]),
+        _ => (10, digits.as_str()),
+    };
+
+    isize::from_str_radix(digits, radix)
+}
+
+impl<C: std::fmt::Display> std::fmt::Display for Value<C> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             Value::Int(i) => write!(f, "{}", i),
@@ -78,8 +198,10 @@ impl std::fmt::Display for Value {
             Value::String(s) => write!(f, "{}", s),
             Value::Boolean(b) => write!(f, "{}", b),
             Value::IntRange(i1, i2) => write!(f, "{}..{}", i1, i2),
-            Value::Function(Function::Match { arms: _ }) => write!(f, "<Match Statement>"),
+            Value::Function(Function::Match { .. }) => write!(f, "<Match Statement>"),
             Value::Function(Function::Builtin(_)) => write!(f, "<Builtin Function>"),
+            Value::Function(Function::Constructor { typ, variant, .. }) => write!(f, "<Constructor {}::{}>", typ, variant),
+            Value::Function(Function::Closure(closure)) => write!(f, "{}", closure),
             Value::Tuple(t) => {
                 let mut s = String::new();
                 for v in t.iter() {
@@ -87,7 +209,11 @@ impl std::fmt::Display for Value {
                 }
                 s.pop();
                 s.pop();
-                write!(f, "({:?})", s)
+                if t.len() == 1 {
+                    // Same as the literal, so a 1-tuple doesn't look like a bracketed value
+                    s.push(',');
+                }
+                write!(f, "({})", s)
             },
             Value::List(l) => {
                 let mut s = String::new();
@@ -98,6 +224,38 @@ impl std::fmt::Display for Value {
                 s.pop();
                 write!(f, "[{}]", s)
             },
+            Value::Dict(entries) => {
+                let mut s = String::new();
+                for (key, value) in entries.iter() {
+                    s.push_str(&format!("{}: {}, ", Value::<C>::from(key.clone()), value));
+                }
+                s.pop();
+                s.pop();
+                write!(f, "{{{}}}", s)
+            },
+            Value::Struct(name, fields) => {
+                let mut s = String::new();
+                for (field, value) in fields.iter() {
+                    s.push_str(&format!("{}: {}, ", field, value));
+                }
+                s.pop();
+                s.pop();
+                write!(f, "{}({})", name, s)
+            },
+            Value::Variant(typ, variant, payload) => {
+                if payload.is_empty() {
+                    return write!(f, "{}::{}", typ, variant);
+                }
+
+                let mut s = String::new();
+                for v in payload.iter() {
+                    s.push_str(&format!("{}, ", v));
+                }
+                s.pop();
+                s.pop();
+                write!(f, "{}::{}({})", typ, variant, s)
+            },
+            Value::Type(definition) => write!(f, "<Type {}>", definition.name),
             Value::None => write!(f, "None"),
             Value::Filter(_, _) => write!(f, "<Filter>"),
             Value::Map(_, _) => write!(f, "<Map>"),
@@ -105,7 +263,7 @@ impl std::fmt::Display for Value {
     }
 }
 
-impl Value {
+impl<C> Value<C> {
     pub fn get_type(&self) -> Type {
         match self {
             Value::Int(_) => Type::Int,
@@ -115,10 +273,56 @@ impl Value {
             Value::IntRange(_, _) => Type::Iter,
             Value::Function(_) => Type::Match,
             Value::Tuple(vals) => Type::Tuple(vals.iter().map(Value::get_type).collect()),
-            Value::List(vals) => Type::List(Box::new(vals.get(0).unwrap_or(&Value::None).get_type())),
+            Value::List(vals) => Type::List(Box::new(vals.first().unwrap_or(&Value::None).get_type())),
+            Value::Dict(entries) => match entries.first_key_value() {
+                Some((key, value)) => Type::Dict(Box::new(Value::<C>::from(key.clone()).get_type()), Box::new(value.get_type())),
+                None => Type::Dict(Box::new(Type::None), Box::new(Type::None)),
+            },
+            Value::Struct(name, _) | Value::Variant(name, ..) => Type::Named(name.clone()),
+            Value::Type(_) => Type::Type,
             Value::None => Type::None,
             Value::Filter(_, _) => Type::Iter,
             Value::Map(_, _) => Type::Iter,
         }
     }
-}
\ No newline at end of file
+}
+
+impl Value {
+    /// The same value, as one that can hold closures of type `C`. No value written in the program holds a closure,
+    /// so it carries over as it is.
+    pub fn with_closures<C>(&self) -> Value<C> {
+        match self {
+            Value::Int(i) => Value::Int(*i),
+            Value::Float(f) => Value::Float(*f),
+            Value::String(s) => Value::String(s.clone()),
+            Value::Boolean(b) => Value::Boolean(*b),
+            Value::IntRange(start, end) => Value::IntRange(*start, *end),
+            Value::Function(function) => Value::Function(function.with_closures()),
+            Value::Tuple(elements) => Value::Tuple(elements.iter().map(Value::with_closures).collect()),
+            Value::List(elements) => Value::List(elements.iter().map(Value::with_closures).collect()),
+            Value::Dict(entries) => Value::Dict(entries.iter().map(|(key, value)| (key.clone(), value.with_closures())).collect()),
+            Value::Filter(value, function) => Value::Filter(Box::new(value.with_closures()), function.with_closures()),
+            Value::Map(value, function) => Value::Map(Box::new(value.with_closures()), function.with_closures()),
+            Value::Struct(name, fields) => Value::Struct(name.clone(), fields.iter().map(|(field, value)| (field.clone(), value.with_closures())).collect()),
+            Value::Variant(typ, variant, payload) => Value::Variant(typ.clone(), variant.clone(), payload.iter().map(Value::with_closures).collect()),
+            Value::Type(definition) => Value::Type(Box::new(TypeDefinition {
+                name: definition.name.clone(),
+                body: definition.body.clone(),
+                statics: definition.statics.iter().map(|(name, value)| (name.clone(), value.with_closures())).collect(),
+            })),
+            Value::None => Value::None,
+        }
+    }
+}
+
+impl Function {
+    /// The same function, as one that can be a closure of type `C`
+    pub fn with_closures<C>(&self) -> Function<C> {
+        match self {
+            Function::Match { arms } => Function::Match { arms: arms.clone() },
+            Function::Builtin(symbol) => Function::Builtin(*symbol),
+            Function::Constructor { typ, variant, payload } => Function::Constructor { typ: typ.clone(), variant: variant.clone(), payload: payload.clone() },
+            Function::Closure(never) => match *never {},
+        }
+    }
+}
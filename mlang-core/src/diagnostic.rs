@@ -0,0 +1,199 @@
+use std::fmt::Display;
+
+use crate::constructs::token::span::{FileId, Span};
+
+/// Which stage of running a program raised a diagnostic
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DiagnosticKind {
+    Syntax,
+    Semantic,
+    Runtime,
+    Compiler,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
+pub enum Severity {
+    Note,
+    Warning,
+    Error,
+}
+
+impl Display for Severity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Severity::Note => write!(f, "note"),
+            Severity::Warning => write!(f, "warning"),
+            Severity::Error => write!(f, "error"),
+        }
+    }
+}
+
+/// A stable identifier for a kind of diagnostic, e.g. `E0201`.
+/// Every code is listed in [`codes`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ErrorCode(pub &'static str);
+
+impl Display for ErrorCode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+/// A span of source code, with an optional message explaining its part in the diagnostic
+#[derive(Debug, Clone, PartialEq)]
+pub struct Label {
+    pub span: Span,
+    /// The file the span is in, looked up in a [`SourceMap`](crate::constructs::token::span::SourceMap).
+    /// Diagnostics are raised while working through one file, so this is filled in afterwards by [`Diagnostic::in_file`].
+    pub file: Option<FileId>,
+    pub message: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    pub kind: DiagnosticKind,
+    pub severity: Severity,
+    pub code: ErrorCode,
+    pub message: String,
+    /// The span the diagnostic is about, if it could be pinned down
+    pub primary: Option<Label>,
+    /// Other spans relevant to the diagnostic, e.g. a previous declaration
+    pub secondary: Vec<Label>,
+    pub notes: Vec<String>,
+    pub help: Option<String>,
+}
+
+impl Diagnostic {
+    pub fn new(kind: DiagnosticKind, code: ErrorCode, message: String) -> Self {
+        Diagnostic {
+            kind,
+            severity: Severity::Error,
+            code,
+            message,
+            primary: None,
+            secondary: Vec::new(),
+            notes: Vec::new(),
+            help: None,
+        }
+    }
+
+    pub fn with_severity(mut self, severity: Severity) -> Self {
+        self.severity = severity;
+        self
+    }
+
+    pub fn with_span(mut self, span: Option<Span>) -> Self {
+        self.primary = span.map(|span| Label { span, file: None, message: None });
+        self
+    }
+
+    /// Points the diagnostic at `span`, unless it already points somewhere
+    pub fn or_span(mut self, span: Span) -> Self {
+        self.primary.get_or_insert(Label { span, file: None, message: None });
+        self
+    }
+
+    pub fn with_label(mut self, span: Span, message: impl Into<String>) -> Self {
+        self.primary = Some(Label { span, file: None, message: Some(message.into()) });
+        self
+    }
+
+    pub fn with_secondary_label(mut self, span: Span, message: impl Into<String>) -> Self {
+        self.secondary.push(Label { span, file: None, message: Some(message.into()) });
+        self
+    }
+
+    pub fn with_note(mut self, note: impl Into<String>) -> Self {
+        self.notes.push(note.into());
+        self
+    }
+
+    pub fn with_help(mut self, help: impl Into<String>) -> Self {
+        self.help = Some(help.into());
+        self
+    }
+
+    /// Puts every label which doesn't say which file it's in into `file`
+    pub fn in_file(mut self, file: FileId) -> Self {
+        for label in self.primary.iter_mut().chain(&mut self.secondary) {
+            label.file.get_or_insert(file);
+        }
+        self
+    }
+
+    pub fn span(&self) -> Option<Span> {
+        self.primary.as_ref().map(|label| label.span)
+    }
+
+    pub fn is_error(&self) -> bool {
+        self.severity == Severity::Error
+    }
+}
+
+impl Display for Diagnostic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}[{}]: {}", self.severity, self.code, self.message)
+    }
+}
+
+/// Every diagnostic code raised by mlang. Codes are grouped by the stage that raises them:
+/// `E01xx` for the tokenizer, `E02xx` for the parser, `E03xx` for the verifier,
+/// `E04xx` for the interpreter and `E09xx` for bugs in mlang itself.
+pub mod codes {
+    use super::ErrorCode;
+
+    // Tokenizer
+    pub const UNEXPECTED_CHARACTER: ErrorCode = ErrorCode("E0101");
+    pub const TAB_INDENTATION: ErrorCode = ErrorCode("E0102");
+    pub const UNTERMINATED_STRING: ErrorCode = ErrorCode("E0103");
+    pub const UNSUPPORTED_TOKEN: ErrorCode = ErrorCode("E0104");
+    pub const INVALID_LITERAL: ErrorCode = ErrorCode("E0105");
+    pub const SOURCE_TOO_LARGE: ErrorCode = ErrorCode("E0106");
+    pub const INVALID_ESCAPE: ErrorCode = ErrorCode("E0107");
+    pub const INVALID_MULTILINE_STRING: ErrorCode = ErrorCode("E0108");
+    pub const INVALID_INTERPOLATION: ErrorCode = ErrorCode("E0109");
+    pub const MIXED_INDENTATION: ErrorCode = ErrorCode("E0110");
+
+    // Parser
+    pub const UNEXPECTED_TOKEN: ErrorCode = ErrorCode("E0201");
+    pub const EXPECTED_EXPRESSION: ErrorCode = ErrorCode("E0202");
+    pub const UNCLOSED_DELIMITER: ErrorCode = ErrorCode("E0203");
+    pub const EXPECTED_IDENTIFIER: ErrorCode = ErrorCode("E0204");
+    pub const EXPECTED_STATEMENT: ErrorCode = ErrorCode("E0205");
+    pub const EXPECTED_BLOCK: ErrorCode = ErrorCode("E0206");
+    pub const MISALIGNED_MATCH_ARM: ErrorCode = ErrorCode("E0207");
+    pub const EXPECTED_MATCH_ARM: ErrorCode = ErrorCode("E0208");
+    pub const EXPECTED_COLON: ErrorCode = ErrorCode("E0209");
+    pub const UNKNOWN_TYPE: ErrorCode = ErrorCode("E0210");
+    pub const NOT_AN_OPERATOR: ErrorCode = ErrorCode("E0211");
+    pub const INVALID_PATTERN: ErrorCode = ErrorCode("E0212");
+    pub const DUPLICATE_FIELD: ErrorCode = ErrorCode("E0213");
+    pub const EXPECTED_EQUALS: ErrorCode = ErrorCode("E0214");
+
+    // Verifier
+    pub const NON_EXHAUSTIVE_MATCH: ErrorCode = ErrorCode("E0301");
+    pub const UNREACHABLE_ARM: ErrorCode = ErrorCode("E0302");
+    pub const MISMATCHED_TYPES: ErrorCode = ErrorCode("E0303");
+    pub const UNKNOWN_MEMBER: ErrorCode = ErrorCode("E0304");
+    pub const MISSING_FIELDS: ErrorCode = ErrorCode("E0305");
+    pub const UNRESOLVED_IDENTIFIER: ErrorCode = ErrorCode("E0306");
+    pub const REDEFINED_IDENTIFIER: ErrorCode = ErrorCode("E0307");
+    pub const UNDEFINED_ASSIGNMENT: ErrorCode = ErrorCode("E0308");
+    pub const DUPLICATE_BINDING: ErrorCode = ErrorCode("E0309");
+
+    // Interpreter
+    pub const INVALID_OPERANDS: ErrorCode = ErrorCode("E0401");
+    pub const UNDEFINED_IDENTIFIER: ErrorCode = ErrorCode("E0402");
+    pub const DUPLICATE_IDENTIFIER: ErrorCode = ErrorCode("E0403");
+    pub const NOT_CALLABLE: ErrorCode = ErrorCode("E0404");
+    pub const EXPECTED_BOOLEAN: ErrorCode = ErrorCode("E0405");
+    pub const ASSERTION_FAILED: ErrorCode = ErrorCode("E0406");
+    pub const INDEX_OUT_OF_BOUNDS: ErrorCode = ErrorCode("E0407");
+    pub const KEY_NOT_FOUND: ErrorCode = ErrorCode("E0408");
+    pub const UNKNOWN_FIELD: ErrorCode = ErrorCode("E0409");
+    pub const MISSING_FIELD: ErrorCode = ErrorCode("E0410");
+    pub const OUTPUT_FAILED: ErrorCode = ErrorCode("E0411");
+
+    // Internal
+    pub const INTERNAL: ErrorCode = ErrorCode("E0901");
+}
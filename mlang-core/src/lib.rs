@@ -1,42 +1,24 @@
+// Diagnostics carry labels, notes and help, so they are large. They are only built on the error path.
+#![allow(clippy::result_large_err)]
+
 pub mod parse;
 pub mod constructs;
 pub mod tokenize;
 pub mod verify;
+pub mod diagnostic;
 
 pub mod prelude {
-    use std::fmt::Display;
-    use crate::constructs::token::span::Span;
-
-    pub type Result<T> = std::result::Result<T, MLGError>;
+    pub use crate::diagnostic::{Diagnostic, DiagnosticKind, Severity, Label, ErrorCode, codes};
 
-    #[derive(Debug, Clone)]
-    pub enum MLGError {
-        SyntaxErr(Option<Span>, String),
-        SemanticErr(Option<Span>, String),
-        CompilerErr(String)
-    }
-
-    impl Display for MLGError {
-        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-            match self {
-                Self::SyntaxErr(_, s) => write!(f, "Invalid syntax: {}", s),
-                Self::SemanticErr(_, s) => write!(f, "Error: {}", s),
-                Self::CompilerErr(s) => write!(f, "Failed to compile: {}", s),
-            }
-        }
-    }
+    pub type Result<T> = std::result::Result<T, Diagnostic>;
 
     macro_rules! syntax_err {
-        ($span:expr, $($args:tt)*) => (Err(MLGError::SyntaxErr($span, format!($($args)*))))
-    }
-
-    macro_rules! semantic_err {
-        ($span:expr, $($args:tt)*) => (Err(MLGError::SyntaxErr($span, format!($($args)*))))
+        ($code:expr, $span:expr, $($args:tt)*) => (Err(Diagnostic::new(DiagnosticKind::Syntax, $code, format!($($args)*)).with_span($span)))
     }
 
     macro_rules! compiler_err {
-        ($($arg:tt)*) => (Err(MLGError::CompilerErr(format!($($arg)*))))
+        ($($arg:tt)*) => (Err(Diagnostic::new(DiagnosticKind::Compiler, codes::INTERNAL, format!($($arg)*))))
     }
 
-    pub(crate) use {syntax_err, semantic_err, compiler_err};
+    pub(crate) use {syntax_err, compiler_err};
 }
\ No newline at end of file
@@ -1,11 +1,162 @@
 mod parse_ast;
 mod parse_expr;
 mod parse_match_expr;
+mod parse_type;
 
-use crate::constructs::ast::AST;
+use crate::constructs::ast::{AST, Accessor, Block, Statement, Expression, ExpressionKind, Function, InterpolatedPart};
 use crate::constructs::token::Tokens;
+use crate::constructs::variable::Value;
 use crate::prelude::*;
 
-pub fn parse(tokens: &mut Tokens) -> Result<AST> {
-    Ok(AST(parse_ast::parse_block(tokens, 0)?))
-}
\ No newline at end of file
+/// Parses as much of the program as possible. Parts of the program which fail to parse 
+/// are left as [`Statement::Error`] nodes in the returned AST, and every error 
+/// encountered is returned alongside it, in the order it appears in the source.
+pub fn parse(tokens: &mut Tokens) -> (AST, Vec<Diagnostic>) {
+    let block = parse_ast::parse_block(tokens, 0);
+
+    let mut errors = Vec::new();
+    collect_block_errors(&block, &mut errors);
+
+    (AST(block), errors)
+}
+
+fn collect_block_errors(block: &Block, errors: &mut Vec<Diagnostic>) {
+    for statement in &block.statements {
+        match statement {
+            Statement::Error(err) => errors.push(err.clone()),
+            Statement::Expression(expr) |
+            Statement::Let(_, expr) |
+            Statement::Set(_, expr) |
+            Statement::Return(expr) |
+            Statement::Break(Some(expr)) => collect_expression_errors(expr, errors),
+            Statement::SetPlace(_, accessors, expr) => {
+                for accessor in accessors {
+                    if let Accessor::Index(index) = accessor {
+                        collect_expression_errors(&index.expression, errors);
+                    }
+                }
+                collect_expression_errors(expr, errors);
+            }
+            Statement::Impl(_, members) => {
+                for (_, expr) in members {
+                    collect_expression_errors(expr, errors);
+                }
+            }
+            Statement::Type(_) | Statement::Break(None) | Statement::Continue => {}
+        }
+    }
+}
+
+fn collect_expression_errors(expr: &Expression, errors: &mut Vec<Diagnostic>) {
+    match &expr.kind {
+        ExpressionKind::Literal(Value::Function(Function::Match { arms })) => {
+            for arm in arms {
+                if let Some(guard) = &arm.pattern.guard {
+                    collect_expression_errors(guard, errors);
+                }
+                collect_block_errors(&arm.block, errors);
+            }
+        }
+        ExpressionKind::Literal(_) | ExpressionKind::Identifier(_) | ExpressionKind::Static(..) => {}
+        ExpressionKind::Binary(left, _, right) | ExpressionKind::Call(left, right) => {
+            collect_expression_errors(left, errors);
+            collect_expression_errors(right, errors);
+        }
+        ExpressionKind::Unary(_, expr) | ExpressionKind::Field(expr, _) => collect_expression_errors(expr, errors),
+        ExpressionKind::Index(expr, index) => {
+            collect_expression_errors(expr, errors);
+            collect_expression_errors(&index.expression, errors);
+        }
+        ExpressionKind::Tuple(elements) | ExpressionKind::List(elements) => {
+            for element in elements {
+                collect_expression_errors(element, errors);
+            }
+        }
+        ExpressionKind::Construct(_, fields) => {
+            for (_, value) in fields {
+                collect_expression_errors(value, errors);
+            }
+        }
+        ExpressionKind::Dict(entries) => {
+            for (key, value) in entries {
+                collect_expression_errors(key, errors);
+                collect_expression_errors(value, errors);
+            }
+        }
+        ExpressionKind::Interpolated(parts) => {
+            for part in parts {
+                if let InterpolatedPart::Expression(expr) = part {
+                    collect_expression_errors(expr, errors);
+                }
+            }
+        }
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tokenize::parse_tokens;
+
+    fn parse_source(source: &str) -> (AST, Vec<Diagnostic>) {
+        let tokens = parse_tokens(source).expect("The test's source should tokenize");
+        parse(&mut Tokens::new(&tokens))
+    }
+
+    /// The code of each error, and the text its primary span covers
+    fn errors(source: &str) -> Vec<(ErrorCode, String)> {
+        let (_, errors) = parse_source(source);
+        errors.iter()
+            .map(|error| {
+                let text = error.span().and_then(|span| source.get(span.beginning()..span.beginning() + span.length())).unwrap_or_default();
+                (error.code, text.to_string())
+            })
+            .collect()
+    }
+
+    #[test]
+    fn empty_programs() {
+        for source in ["", "\n\n", "// Nothing to see here\n", "/* Or here */"] {
+            let (AST(block), errors) = parse_source(source);
+            assert!(errors.is_empty(), "{:?} gave {:?}", source, errors);
+            assert!(block.statements.is_empty());
+        }
+    }
+
+    #[test]
+    fn let_needs_equals() {
+        assert_eq!(errors("let x 5"), vec![(codes::EXPECTED_EQUALS, "5".to_string())]);
+        assert_eq!(errors("let x\nprint 1"), vec![(codes::EXPECTED_EQUALS, "\n".to_string())]);
+        assert_eq!(errors("let x"), vec![(codes::EXPECTED_EQUALS, String::new())]);
+        assert_eq!(errors("let 5 = x"), vec![(codes::EXPECTED_IDENTIFIER, "5".to_string())]);
+    }
+
+    #[test]
+    fn several_errors_in_one_file() {
+        let source = "let x 5\nlet y = 1 +\nlet z = (1, 2\nlet w = 3 )\nprint w";
+        let (AST(block), errors) = parse_source(source);
+
+        let codes: Vec<_> = errors.iter().map(|error| error.code).collect();
+        assert_eq!(codes, vec![codes::EXPECTED_EQUALS, codes::EXPECTED_EXPRESSION, codes::UNCLOSED_DELIMITER, codes::UNEXPECTED_TOKEN]);
+        assert!(matches!(block.statements.last(), Some(Statement::Expression(_))), "The last statement should still parse");
+    }
+
+    #[test]
+    fn unclosed_bracket_stops_at_the_next_statement() {
+        let source = "let a = (1, 2\n    , 3\nlet b = [1\nlet c = 2";
+        let (AST(block), errors) = parse_source(source);
+
+        assert_eq!(errors.len(), 2, "{:?}", errors);
+        assert_eq!(block.statements.len(), 3);
+        assert!(matches!(&block.statements[2], Statement::Let(ident, _) if ident.name == "c"));
+    }
+
+    #[test]
+    fn errors_inside_match_arms() {
+        let source = "let f =\n    | 0 : let a 1\n    | n :\n        let b = )\n        n\nprint (f 1)";
+        let (AST(block), errors) = parse_source(source);
+
+        let codes: Vec<_> = errors.iter().map(|error| error.code).collect();
+        assert_eq!(codes, vec![codes::EXPECTED_EQUALS, codes::EXPECTED_EXPRESSION]);
+        assert_eq!(block.statements.len(), 2);
+    }
+}
@@ -1,31 +1,34 @@
-use crate::constructs::token::span::Span;
 use crate::constructs::token::symbol::builtin_symbols;
 use crate::prelude::*;
 use crate::constructs::ast::*;
 use crate::constructs::token::{TokenKind, Token, Tokens};
-use super::parse_expr::parse_next_expression;
+use super::parse_expr::{parse_next_expression, parse_accessor};
+use super::parse_match_expr::find_end_of_match;
+use super::parse_type::{parse_type_declaration, parse_impl};
 
 pub fn find_end_of_block(tokens: &mut Tokens, block_indent: usize) -> usize {
     let mut length = 0;
-    // An block ends when the next line has a lower indentation than the current line
+    let mut depth = 0;
+    // An block ends when the next line has a lower indentation than the current line.
+    // Lines inside brackets are part of whatever the brackets are in.
     loop {
         match tokens.next() {
-            Some(Token(TokenKind::Newline(indent), ..)) => {
-                if *indent < block_indent {
-                    return length;
-                }
+            Some(Token(TokenKind::Newline(indent), ..)) if *indent < block_indent && depth <= 0 => {
+                return length;
             },
+            Some(Token(kind, ..)) => depth += kind.nesting(),
             None => return length,
-            _ => {}
         }
         length += 1;
     }
 }
 
-pub fn parse_block(tokens: &mut Tokens, block_indent: usize) -> Result<Block> {
+/// Parses every statement in the block. A statement which fails to parse is replaced
+/// with a [`Statement::Error`], and parsing picks back up at the next statement.
+pub fn parse_block(tokens: &mut Tokens, block_indent: usize) -> Block {
     let mut statements = Vec::new();
     let end = find_end_of_block(&mut tokens.clone(), block_indent);
-    let mut tokens = tokens.take(end);
+    let tokens = &mut tokens.take(end);
 
     while let Some(token) = tokens.peek() {
         if let Token(TokenKind::Newline(_), ..) = token {
@@ -33,47 +36,123 @@ pub fn parse_block(tokens: &mut Tokens, block_indent: usize) -> Result<Block> {
             continue;
         }
 
-        statements.push(parse_next_statement(&mut tokens, block_indent)?);
+        let statement_start = tokens.clone();
+        match parse_next_statement(tokens, block_indent) {
+            Ok(statement) => statements.push(statement),
+            Err(err) => {
+                statements.push(Statement::Error(err));
+                *tokens = statement_start;
+                let length = find_end_of_statement(&mut tokens.clone(), block_indent);
+                tokens.skip(length.max(1));
+            }
+        }
     }
 
-    if statements.is_empty() {
-        if let Some(Token(.., Span { index, len })) = tokens.last() {
-            return syntax_err!(Some(Span { 
-                index: index + *len as u32, 
-                len: 1
-            }), "Expected block");
-        } else {
-            return syntax_err!(None, "Expected block");
+    Block { statements }
+}
+
+/// Whether the statement starts like `xs[i] = ` or `person.age = `, with one or more accessors
+fn is_place_assignment(tokens: &Tokens) -> bool {
+    let lookahead = &mut tokens.clone();
+    lookahead.next(); // Skip the IDENTIFIER
+
+    let mut has_accessor = false;
+    loop {
+        match lookahead.peek() {
+            Some(Token(TokenKind::LeftSqrBrace, ..)) => {
+                // Skip to the matching closing bracket
+                let mut depth = 0;
+                loop {
+                    match lookahead.next() {
+                        Some(Token(kind, ..)) => depth += kind.nesting(),
+                        None => return false,
+                    }
+                    if depth == 0 {
+                        break;
+                    }
+                }
+            }
+            Some(Token(TokenKind::Dot | TokenKind::ColonColon, ..)) => lookahead.skip(2),
+            _ => break,
         }
+        has_accessor = true;
+    }
+
+    has_accessor && matches!(lookahead.peek(), Some(Token(TokenKind::Equal, ..)))
+}
+
+/// Finds where to pick back up after a statement which failed to parse. That's the next line which isn't indented
+/// past the statement, even inside brackets, so that a bracket which is never closed doesn't hide the statements after it.
+/// A line starting with a closing bracket still belongs to the statement, since it most likely closes one the statement opened.
+fn find_end_of_statement(tokens: &mut Tokens, block_indent: usize) -> usize {
+    if let Some(Token(TokenKind::Bar(match_indent), ..)) = tokens.peek() {
+        return find_end_of_match(tokens, block_indent, *match_indent);
     }
 
-    Ok(Block { statements })
+    let mut length = 0;
+    loop {
+        match tokens.next() {
+            Some(Token(TokenKind::Newline(indent), ..)) if *indent <= block_indent => {
+                if !matches!(tokens.peek(), Some(Token(kind, ..)) if kind.nesting() < 0) {
+                    return length;
+                }
+            }
+            Some(_) => {}
+            None => return length,
+        }
+        length += 1;
+    }
 }
 
 pub fn parse_next_statement(tokens: &mut Tokens, current_indent: usize) -> Result<Statement> {
     match tokens.peek() {
-        Some(Token(TokenKind::Keyword(symbol), ..)) if *symbol == *builtin_symbols::LET => {
-            let ident = match tokens.peek_n(1) {
-                Some(Token(TokenKind::Identifier(ident), ..)) => ident.get_str().to_string(),
-                Some(Token(.., span)) => return syntax_err!(Some(*span), "Expected identifier after let"),
-                None => return syntax_err!(None, "Unexpected end of assignment expression")
+        Some(Token(TokenKind::Keyword(symbol), let_span)) if *symbol == *builtin_symbols::LET => {
+            tokens.next(); // Skip the LET
+            let ident = match tokens.next() {
+                Some(Token(TokenKind::Identifier(ident), span)) => Identifier::new(ident.get_str(), *span),
+                Some(Token(.., span)) => return syntax_err!(codes::EXPECTED_IDENTIFIER, Some(*span), "Expected identifier after let"),
+                None => return syntax_err!(codes::EXPECTED_IDENTIFIER, Some(let_span.after()), "Expected identifier after let"),
             };
 
-            tokens.skip(3);
+            match tokens.next() {
+                Some(Token(TokenKind::Equal, ..)) => {}
+                None | Some(Token(TokenKind::Newline(_), ..)) => {
+                    return syntax_err!(codes::EXPECTED_EQUALS, Some(ident.span.after()), "Expected `=` after `let {}`", ident.name);
+                }
+                Some(Token(kind, span)) => {
+                    return syntax_err!(codes::EXPECTED_EQUALS, Some(*span), "Expected `=` after `let {}`, found `{}`", ident.name, kind);
+                }
+            }
             let expression = parse_next_expression(tokens, current_indent)?;
-            Ok(Statement::Let(Identifier { name: ident }, expression))
+            Ok(Statement::Let(ident, expression))
+        },
+        Some(Token(TokenKind::Keyword(symbol), ..)) if *symbol == *builtin_symbols::TYPE => {
+            parse_type_declaration(tokens, current_indent)
+        },
+        Some(Token(TokenKind::Keyword(symbol), ..)) if *symbol == *builtin_symbols::IMPL => {
+            parse_impl(tokens, current_indent)
         },
         Some(Token(TokenKind::Keyword(symbol), ..)) if *symbol == *builtin_symbols::RETURN => {
             tokens.skip(1);
             let expression = parse_next_expression(tokens, current_indent)?;
             Ok(Statement::Return(expression))
         },
-        Some(Token(TokenKind::Identifier(ident), ..)) => {
-            let ident = ident.get_str().to_string();
+        Some(Token(TokenKind::Identifier(ident), span)) => {
+            let ident = Identifier::new(ident.get_str(), *span);
             if let Some(Token(TokenKind::Equal, ..)) = tokens.peek_n(1) {
                 tokens.skip(2);
                 let expression = parse_next_expression(tokens, current_indent)?;
-                Ok(Statement::Set(Identifier { name: ident }, expression))
+                Ok(Statement::Set(ident, expression))
+            } else if is_place_assignment(tokens) {
+                tokens.next(); // Skip the IDENTIFIER
+                let mut accessors = Vec::new();
+                while !matches!(tokens.peek(), Some(Token(TokenKind::Equal, ..))) {
+                    accessors.push(parse_accessor(tokens, current_indent)?);
+                }
+
+                tokens.next(); // Skip the EQUAL
+                let expression = parse_next_expression(tokens, current_indent)?;
+                Ok(Statement::SetPlace(ident, accessors, expression))
             } else {
                 let expression = parse_next_expression(tokens, current_indent)?;
                 Ok(Statement::Expression(expression))
@@ -88,13 +167,10 @@ pub fn parse_next_statement(tokens: &mut Tokens, current_indent: usize) -> Resul
             Ok(Statement::Expression(expression))
         }
         None => {
-            if let Some(Token(.., Span { index, len })) = tokens.last() {
-                syntax_err!(Some(Span { 
-                    index: index + *len as u32, 
-                    len: 1
-                }), "Expected statement")
+            if let Some(Token(.., span)) = tokens.last() {
+                syntax_err!(codes::EXPECTED_STATEMENT, Some(span.after()), "Expected statement")
             } else {
-                syntax_err!(None, "Expected statement")
+                syntax_err!(codes::EXPECTED_STATEMENT, None, "Expected statement")
             }
         }
     }
@@ -1,288 +1,538 @@
-use crate::constructs::token::span::Span;
 use crate::prelude::*;
-use super::parse_ast::*;
 use super::parse_match_expr::parse_match_expression;
-use crate::constructs::token::{TokenKind, Token, Tokens};
-use crate::constructs::ast::{Expression, BinaryOperator, Identifier};
+use crate::constructs::token::{TokenKind, Token, Tokens, StringPart};
+use crate::constructs::ast::{Accessor, Expression, ExpressionKind, Identifier, Index, InterpolatedPart};
+use crate::constructs::token::span::Span;
 use crate::constructs::variable::Value;
 
-#[derive(Debug)]
-enum ExpressionFragment {
-    Parsed(Expression, Span),
-    Unparsed(Token),
+/// Parses the expression at the start of `tokens`, which ends at the first line that isn't indented past
+/// `current_indent`. The tokens are left at the line break which ends the expression.
+pub fn parse_next_expression(tokens: &mut Tokens, current_indent: usize) -> Result<Expression> {
+    // An expression which starts on the next line goes on for as long as the lines after it are indented past that one
+    let mut current_indent = current_indent;
+    while let Some(Token(TokenKind::Newline(indent), ..)) = tokens.peek() {
+        if *indent <= current_indent {
+            break;
+        }
+        current_indent = *indent;
+        tokens.next();
+    }
+
+    if !continues(tokens, current_indent, 0) {
+        // An empty expression is none
+        let span = tokens.peek_n(-1).map_or(Span { index: 0, len: 0 }, |Token(_, span)| *span);
+        return Ok(Expression::new(ExpressionKind::Literal(Value::None), span));
+    }
+
+    let expression = parse_expression(tokens, current_indent, 0)?;
+
+    if continues(tokens, current_indent, 0) {
+        let Some(Token(kind, span)) = tokens.peek() else {
+            return compiler_err!("Expected a token after the expression");
+        };
+        return syntax_err!(codes::UNEXPECTED_TOKEN, Some(*span), "Unexpected token `{}` after expression", kind);
+    }
+
+    Ok(expression)
 }
 
-impl ExpressionFragment {
-    fn span(&self) -> Span {
-        match self {
-            ExpressionFragment::Parsed(.., span) |
-            ExpressionFragment::Unparsed(Token(.., span)) => *span
+/// Skips the line breaks which don't end the expression. Inside brackets that's all of them,
+/// and outside of them it's those before a line indented past `current_indent`.
+/// Returns whether the expression goes on, with the next token.
+fn continues(tokens: &mut Tokens, current_indent: usize, depth: usize) -> bool {
+    while let Some(Token(TokenKind::Newline(indent), ..)) = tokens.peek() {
+        if depth == 0 && *indent <= current_indent {
+            return false;
         }
+        tokens.next();
     }
+
+    tokens.peek().is_some()
 }
 
-pub fn parse_next_expression(tokens: &mut Tokens, current_indent: usize) -> Result<Expression> {
-    let current_indent = if let Some(Token(TokenKind::Newline(indent), ..)) = tokens.peek() {
-        let indent = *indent;
+/// Skips any line breaks, returning the indentation of the line after the last one
+fn skip_newlines(tokens: &mut Tokens) -> Option<usize> {
+    let mut line_indent = None;
+    while let Some(Token(TokenKind::Newline(indent), ..)) = tokens.peek() {
+        line_indent = Some(*indent);
         tokens.next();
-        indent
-    } else {
-        current_indent
-    };
-    
-    let end = find_end_of_expression(&mut tokens.clone(), current_indent);
-
-    let mut atoms: Vec<ExpressionFragment> = tokens.take(end)
-        .map(|token| ExpressionFragment::Unparsed(token.clone()))
-        .collect();
-        
-    evaluate_fragments_in_parentheses(&mut atoms, current_indent)?;
-
-    evaluate_fragments_on_match_statements(&mut atoms)?;
-
-    // Remove whitespace - it messes up the parsing
-    atoms.retain(|atom| !matches!(atom, ExpressionFragment::Unparsed(Token(TokenKind::Newline(_), ..))));
-    // Try to parse any singular parsable tokens.
-    // This will make our life easier when doing calls
-    for atom in atoms.iter_mut() {
-        if let ExpressionFragment::Unparsed(token) = atom {
-            if let Ok(expr) = parse_single_token(token) {
-                *atom = ExpressionFragment::Parsed(expr, token.1);
-            }
+    }
+
+    line_indent
+}
+
+/// Parses an expression inside `depth` brackets
+fn parse_expression(tokens: &mut Tokens, current_indent: usize, depth: usize) -> Result<Expression> {
+    parse_expression_with_precedence(tokens, current_indent, depth, 0)
+}
+
+/// Parses a chain of binary operations, only consuming operators which
+/// bind more tightly than `min_precedence`.
+fn parse_expression_with_precedence(tokens: &mut Tokens, current_indent: usize, depth: usize, min_precedence: u8) -> Result<Expression> {
+    let mut left = parse_operand(tokens, current_indent, depth)?;
+
+    loop {
+        if !continues(tokens, current_indent, depth) {
+            return Ok(left);
+        }
+
+        let Some(Token(kind, span)) = tokens.peek() else {
+            return Ok(left);
+        };
+        let Ok(operator) = kind.as_binary_operator(Some(*span)) else {
+            return Ok(left);
+        };
+
+        let precedence = operator.precedence();
+        if precedence <= min_precedence {
+            return Ok(left);
         }
+
+        tokens.next();
+        if !continues(tokens, current_indent, depth) {
+            return syntax_err!(codes::EXPECTED_EXPRESSION, Some(span.after()), "Expected expression after binary operator `{}`", kind);
+        }
+
+        let right = parse_expression_with_precedence(tokens, current_indent, depth, precedence)?;
+        let span = left.span + right.span;
+        left = Expression::new(ExpressionKind::Binary(Box::new(left), operator, Box::new(right)), span);
     }
+}
+
+/// Parses a unary expression followed by any number of calls, e.g. `-3 abs print`
+fn parse_operand(tokens: &mut Tokens, current_indent: usize, depth: usize) -> Result<Expression> {
+    let mut expression = parse_unary(tokens, current_indent, depth)?;
 
-    apply_unary_operator_if_present(&mut atoms)?;
+    while continues(tokens, current_indent, depth) {
+        match tokens.peek() {
+            Some(Token(TokenKind::Identifier(_) | TokenKind::Literal(_) | TokenKind::InterpolatedString(_) | TokenKind::LeftParen | TokenKind::Bar(_), ..)) => {
+                let callee = parse_primary(tokens, current_indent, depth)?;
+                let span = expression.span + callee.span;
+                expression = Expression::new(ExpressionKind::Call(Box::new(expression), Box::new(callee)), span);
+            }
+            _ => break,
+        }
+    }
 
-    evaluate_fragments_on_calls(&mut atoms)?;
+    Ok(expression)
+}
 
-    evaluate_fragments_on_binary_operators(&mut atoms)?;
+fn parse_unary(tokens: &mut Tokens, current_indent: usize, depth: usize) -> Result<Expression> {
+    if let Some(Token(kind, span)) = tokens.peek() {
+        if let Ok(operator) = kind.as_unary_operator(Some(*span)) {
+            tokens.next();
+            if !continues(tokens, current_indent, depth) {
+                return syntax_err!(codes::EXPECTED_EXPRESSION, Some(span.after()), "Expected expression after unary operator `{}`", kind);
+            }
 
-    if atoms.is_empty() {
-        // () = none. This will make sense once tuples are added
-        Ok(Expression::Literal(Value::None))
-    } else if atoms.len() == 1 {
-        match &atoms[0] {
-            ExpressionFragment::Parsed(expr, ..) => Ok(expr.clone()),
-            ExpressionFragment::Unparsed(token) => parse_single_token(token),
+            let operand = parse_unary(tokens, current_indent, depth)?;
+            let span = *span + operand.span;
+            return Ok(Expression::new(ExpressionKind::Unary(operator, Box::new(operand)), span));
         }
-    } else {
-        // Get span
-        // This is a big old mess
-        if let Some(first_frag) = atoms.get(0) {
-            if let Some(second_frag) = atoms.last() {
-                syntax_err!(Some(first_frag.span() + second_frag.span()), "Failed to parse into singular expression. Got: {:?}", atoms)
+    }
+
+    parse_primary(tokens, current_indent, depth)
+}
+
+/// Parses a single value followed by any number of indices, fields and static members,
+/// e.g. `grid[y][x]`, `person.name` or `Person::new`
+fn parse_primary(tokens: &mut Tokens, current_indent: usize, depth: usize) -> Result<Expression> {
+    let mut expression = parse_atom(tokens, current_indent, depth)?;
+
+    while let Some(Token(TokenKind::LeftSqrBrace | TokenKind::Dot | TokenKind::ColonColon, span)) = tokens.peek() {
+        let accessor = parse_accessor_in(tokens, current_indent, depth)?;
+        let span_with_accessor = tokens.span_from(expression.span);
+        let kind = match (accessor, expression.kind) {
+            (Accessor::Index(index), kind) => ExpressionKind::Index(Box::new(Expression::new(kind, expression.span)), index),
+            (Accessor::Field(field), kind) => ExpressionKind::Field(Box::new(Expression::new(kind, expression.span)), field),
+            (Accessor::Static(member), ExpressionKind::Identifier(typ)) => ExpressionKind::Static(typ, member),
+            (Accessor::Static(_), _) => return syntax_err!(codes::UNEXPECTED_TOKEN, Some(*span), "Only type names can be followed by `::`"),
+        };
+        expression = Expression::new(kind, span_with_accessor);
+    }
+
+    Ok(expression)
+}
+
+/// Parses an index, field or static member after a value, e.g. `[0]`, `.age` or `::count`
+pub(super) fn parse_accessor(tokens: &mut Tokens, current_indent: usize) -> Result<Accessor> {
+    parse_accessor_in(tokens, current_indent, 0)
+}
+
+fn parse_accessor_in(tokens: &mut Tokens, current_indent: usize, depth: usize) -> Result<Accessor> {
+    match tokens.peek() {
+        Some(Token(TokenKind::LeftSqrBrace, ..)) => Ok(Accessor::Index(parse_index(tokens, current_indent, depth)?)),
+        Some(Token(kind @ (TokenKind::Dot | TokenKind::ColonColon), span)) => {
+            tokens.next(); // Skip the DOT or COLON_COLON
+            let name = match tokens.next() {
+                Some(Token(TokenKind::Identifier(name), name_span)) => Identifier::new(name.get_str(), *name_span),
+                Some(Token(.., span)) => return syntax_err!(codes::EXPECTED_IDENTIFIER, Some(*span), "Expected name after `{}`", kind),
+                None => return syntax_err!(codes::EXPECTED_IDENTIFIER, Some(span.after()), "Expected name after `{}`", kind),
+            };
+
+            if *kind == TokenKind::Dot {
+                Ok(Accessor::Field(name))
             } else {
-                unreachable!("atoms is guaranteed to have at least one element!")
+                Ok(Accessor::Static(name))
             }
-        } else {
-            syntax_err!(None, "Failed to parse into singular expression. Got: {:?}", atoms)
         }
+        Some(Token(kind, span)) => syntax_err!(codes::UNEXPECTED_TOKEN, Some(*span), "Expected index, field or static member, found `{}`", kind),
+        None => compiler_err!("Tried to parse an accessor at the end of the tokens"),
     }
 }
 
-fn find_end_of_expression(tokens: &mut Tokens, env_indent: usize) -> usize {
-    if let Some(Token(TokenKind::Bar(match_indent), ..)) = tokens.peek() {
-        let match_indent = *match_indent;
-        // We need to find the end of the match statement
-        let mut length = 0;
-        while let Some(Token(TokenKind::Bar(indent), ..)) = tokens.peek() {
-            if *indent != match_indent {
-                // We are no longer continuing to parse the match statement
-                break;
+fn parse_atom(tokens: &mut Tokens, current_indent: usize, depth: usize) -> Result<Expression> {
+    match tokens.peek() {
+        Some(open @ Token(TokenKind::LeftParen, span)) => {
+            tokens.next(); // Skip the LEFT_PAREN
+            let (mut elements, has_comma) = parse_comma_separated(tokens, open, current_indent, depth + 1)?;
+            let span = tokens.span_from(*span);
+
+            if has_comma {
+                Ok(Expression::new(ExpressionKind::Tuple(elements), span))
+            } else if elements.is_empty() {
+                // () = none
+                Ok(Expression::new(ExpressionKind::Literal(Value::None), span))
+            } else {
+                let mut expression = elements.remove(0);
+                expression.span = span;
+                Ok(expression)
             }
-            let block_length = find_end_of_block(&mut tokens.clone(), match_indent + 1);
-            length += block_length;
-            tokens.skip(block_length);
-            // Skip newline
-            tokens.next();
-            length += 1;
         }
+        Some(open @ Token(TokenKind::LeftSqrBrace, span)) => {
+            tokens.next(); // Skip the LEFT_SQR_BRACE
+            let (elements, _) = parse_comma_separated(tokens, open, current_indent, depth + 1)?;
+
+            Ok(Expression::new(ExpressionKind::List(elements), tokens.span_from(*span)))
+        }
+        Some(open @ Token(TokenKind::LeftBrace, span)) => {
+            tokens.next(); // Skip the LEFT_BRACE
+            let entries = parse_dict_entries(tokens, open, current_indent, depth + 1)?;
 
-        // Remove the last newline we accounted for
-        length - 1
-    } else {
-        find_end_of_block(tokens, env_indent + 1)
+            Ok(Expression::new(ExpressionKind::Dict(entries), tokens.span_from(*span)))
+        }
+        Some(Token(TokenKind::Bar(_), ..)) => {
+            // A match expression continues until the end of the expression
+            parse_match_expression(tokens, current_indent, depth > 0)
+        }
+        Some(Token(TokenKind::Identifier(name), ..)) if starts_construct(tokens, name.get_str()) => {
+            // Like a match expression, a construction continues until the end of the expression
+            parse_construct(tokens, current_indent)
+        }
+        Some(Token(kind @ (TokenKind::Comma | TokenKind::RightParen | TokenKind::RightSqrBrace | TokenKind::RightBrace | TokenKind::Colon), span)) => {
+            syntax_err!(codes::EXPECTED_EXPRESSION, Some(*span), "Expected expression, found `{}`", kind)
+        }
+        Some(token) => {
+            tokens.next();
+            parse_single_token(token)
+        }
+        None => syntax_err!(codes::EXPECTED_EXPRESSION, None, "Expected expression"),
     }
 }
 
-fn evaluate_fragments_on_match_statements(atoms: &mut Vec<ExpressionFragment>) -> Result<()> {
-    // First, find if there is a BAR token, and if so, evaluate the rest of the tokens into a match expression
-    if let Some((idx, ExpressionFragment::Unparsed(Token(TokenKind::Bar(indent), span)))) = atoms.iter().enumerate().find(|(_, partial)|
-        matches!(partial, ExpressionFragment::Unparsed(Token(TokenKind::Bar(_), ..)))
-    ) {
-        let match_block = parse_match_expression(&mut Tokens::new(
-            &atoms.iter().skip(idx).map(|partial| {
-                match partial {
-                    ExpressionFragment::Unparsed(token) => token.clone(),
-                    _ => panic!("Expected unparsed token"),
-                }
-            }).collect()
-        ), *indent)?;
+/// Whether the tokens start with a construction like `Person: name: "Bob"`. Type names start with a
+/// capital letter, which tells a construction apart from a key in a dictionary like `{name: "Bob"}`.
+fn starts_construct(tokens: &Tokens, name: &str) -> bool {
+    name.starts_with(char::is_uppercase) && matches!(tokens.clone().nth(1), Some(Token(TokenKind::Colon, ..)))
+}
+
+/// The error for a bracket which is still open at the next token. If the token starts a line which isn't
+/// indented past the expression, or there are no tokens left, the bracket was most likely never closed.
+fn unclosed_bracket(open: &Token, tokens: &Tokens, current_indent: usize) -> Diagnostic {
+    let Token(open_kind, open_span) = open;
+    let (closer, name) = match open_kind {
+        TokenKind::LeftParen => (TokenKind::RightParen, "parenthesis"),
+        TokenKind::LeftSqrBrace => (TokenKind::RightSqrBrace, "square bracket"),
+        _ => (TokenKind::RightBrace, "brace"),
+    };
+    let starts_line = matches!(tokens.peek_n(-1), Some(Token(TokenKind::Newline(indent), ..)) if *indent <= current_indent);
 
-        let span = *span;
+    match tokens.peek() {
+        Some(Token(kind, span)) if !starts_line && kind.nesting() >= 0 => {
+            Diagnostic::new(DiagnosticKind::Syntax, codes::UNEXPECTED_TOKEN, format!("Expected `,` or `{}`, found `{}`", closer, kind))
+                .with_span(Some(*span))
+        }
+        _ => Diagnostic::new(DiagnosticKind::Syntax, codes::UNCLOSED_DELIMITER, format!("Expected closing {}", name))
+            .with_label(*open_span, format!("this `{}` is never closed", open_kind)),
+    }
+}
 
-        atoms.truncate(idx);
+/// Parses an index in square brackets, e.g. `[i + 1]`
+fn parse_index(tokens: &mut Tokens, current_indent: usize, depth: usize) -> Result<Index> {
+    let Some(open @ Token(TokenKind::LeftSqrBrace, open_span)) = tokens.next() else {
+        return compiler_err!("Tried to parse an index without a square bracket");
+    };
 
-        atoms.push(ExpressionFragment::Parsed(match_block, span));
+    skip_newlines(tokens);
+    if let Some(Token(TokenKind::RightSqrBrace, ..)) = tokens.peek() {
+        return syntax_err!(codes::EXPECTED_EXPRESSION, Some(open_span.after()), "Expected index inside square brackets");
     }
 
-    Ok(())
+    let expression = parse_expression(tokens, current_indent, depth + 1)?;
+
+    skip_newlines(tokens);
+    match tokens.peek() {
+        Some(Token(TokenKind::RightSqrBrace, ..)) => {
+            tokens.next();
+            Ok(Index {
+                span: expression.span,
+                expression: Box::new(expression),
+            })
+        }
+        _ => Err(unclosed_bracket(open, tokens, current_indent)),
+    }
 }
 
-fn evaluate_fragments_in_parentheses(atoms: &mut Vec<ExpressionFragment>, current_indent: usize) -> Result<()> {
-    let mut depth = 0;
-    while let Some((left_idx, token)) = atoms.iter().enumerate().find(|(_, partial)| {
-        match partial {
-            ExpressionFragment::Unparsed(Token(TokenKind::LeftParen, ..)) => {
-                depth += 1;
-                true
-            },
-            ExpressionFragment::Unparsed(Token(TokenKind::RightParen, ..)) => {
-                depth -= 1;
-                false
-            },
-            ExpressionFragment::Unparsed(Token(TokenKind::Bar(_), ..)) => {
-                depth == 0
-            },
-            _ => false,
+/// Parses a value of a declared type, e.g. `Person: name: "Bob", age: 31`. The fields can also
+/// go on their own lines, and a field on its own like `name` is short for `name: name`.
+fn parse_construct(tokens: &mut Tokens, current_indent: usize) -> Result<Expression> {
+    let Some(Token(TokenKind::Identifier(name), name_span)) = tokens.next() else {
+        return compiler_err!("Tried to parse a construction without a type name");
+    };
+    tokens.next(); // Skip the COLON
+
+    let mut fields: Vec<(Identifier, Expression)> = Vec::new();
+    // The indentation of the line the next field is on
+    let mut indent = current_indent;
+    loop {
+        // Fields are separated by commas, or go on their own lines indented past the construction
+        while let Some(Token(TokenKind::Newline(next_indent), ..)) = tokens.peek() {
+            if *next_indent <= current_indent {
+                break;
+            }
+            indent = *next_indent;
+            tokens.next();
         }
-    }) {
-        if let ExpressionFragment::Unparsed(Token(TokenKind::Bar(_), ..)) = token {
-            // Don't evaluate parentheses inside match blocks
-            break;
+
+        let (field, field_span) = match tokens.peek() {
+            Some(Token(TokenKind::Identifier(field), span)) => (Identifier::new(field.get_str(), *span), *span),
+            None | Some(Token(TokenKind::Newline(_), ..)) => break,
+            Some(Token(kind, ..)) if kind.nesting() < 0 => break,
+            Some(Token(.., span)) => return syntax_err!(codes::EXPECTED_IDENTIFIER, Some(*span), "Expected field name"),
+        };
+        tokens.next(); // Skip the IDENTIFIER
+        if fields.iter().any(|(existing, _)| existing.name == field.name) {
+            return syntax_err!(codes::DUPLICATE_FIELD, Some(field_span), "Field {} is given twice", field.name);
         }
 
-        let mut depth = 0;
-        if let Some((len_idx, _)) = atoms.iter().skip(left_idx).enumerate().find(|(_, partial)| {
-            match partial {
-                ExpressionFragment::Unparsed(Token(TokenKind::LeftParen, ..)) => {
-                    depth += 1;
-                    false
-                },
-                ExpressionFragment::Unparsed(Token(TokenKind::RightParen, ..)) => {
-                    depth -= 1;
-                    depth == 0
-                },
-                _ => false,
-            }
-        }) {
-            let subexpression_tokens = atoms.iter().skip(left_idx + 1).take(len_idx - 1).map(|partial| {
-                match partial {
-                    ExpressionFragment::Unparsed(token) => token.clone(),
-                    _ => panic!("Expected unparsed token"),
+        let value = match tokens.peek() {
+            Some(Token(TokenKind::Colon, ..)) => {
+                tokens.next(); // Skip the COLON
+                if !continues(tokens, indent, 0) {
+                    return syntax_err!(codes::EXPECTED_EXPRESSION, Some(field_span.after()), "Expected value for field {}", field.name);
                 }
-            }).collect();
-
-            let mut subqueue = Tokens::new(&subexpression_tokens);
-            
-            // Get span
-            // This is a big old mess
-            let span = if let Some(Token(.., first_span)) = subexpression_tokens.get(0) {
-                if let Some(Token(.., second_span)) = subexpression_tokens.last() {
-                    *first_span + *second_span
-                } else {
-                    unreachable!("subexpression_tokens is guaranteed to have at least one element!")
-                }
-            } else {
-                return syntax_err!(Some(token.span().after()), "Expected expression inside parentheses");
-            };
+                parse_expression(tokens, indent, 0)?
+            }
+            None | Some(Token(TokenKind::Comma | TokenKind::Newline(_), ..)) => Expression::new(ExpressionKind::Identifier(field.clone()), field_span),
+            Some(Token(kind, ..)) if kind.nesting() < 0 => Expression::new(ExpressionKind::Identifier(field.clone()), field_span),
+            Some(Token(.., span)) => return syntax_err!(codes::EXPECTED_COLON, Some(*span), "Expected colon between field name and value"),
+        };
+        fields.push((field, value));
+
+        match tokens.peek() {
+            Some(Token(TokenKind::Comma, ..)) => {
+                tokens.next();
+            }
+            Some(Token(TokenKind::Newline(next_indent), ..)) if *next_indent > current_indent => {}
+            _ => break,
+        }
+    }
+
+    Ok(Expression::new(ExpressionKind::Construct(Identifier::new(name.get_str(), *name_span), fields), tokens.span_from(*name_span)))
+}
 
-            let expr = parse_next_expression(&mut subqueue, current_indent)?;
+/// Parses expressions separated by commas up to the bracket which closes `open`, allowing a trailing comma.
+/// Also returns whether there were any commas.
+fn parse_comma_separated(tokens: &mut Tokens, open: &Token, current_indent: usize, depth: usize) -> Result<(Vec<Expression>, bool)> {
+    let mut elements = Vec::new();
+    let mut has_comma = false;
+
+    loop {
+        let line_indent = skip_newlines(tokens);
+        match tokens.peek() {
+            Some(Token(kind, ..)) if is_closer_of(kind, &open.0) => {
+                tokens.next();
+                return Ok((elements, has_comma));
+            }
+            Some(Token(TokenKind::Comma, span)) => {
+                return syntax_err!(codes::EXPECTED_EXPRESSION, Some(*span), "Expected expression before comma");
+            }
+            None | Some(Token(TokenKind::RightParen | TokenKind::RightSqrBrace | TokenKind::RightBrace, ..)) => {
+                return Err(unclosed_bracket(open, tokens, current_indent));
+            }
+            _ => {}
+        }
 
-            atoms[left_idx] = ExpressionFragment::Parsed(expr, span);
-            atoms.drain((left_idx + 1)..(left_idx + len_idx + 1));
+        elements.push(parse_expression(tokens, line_indent.unwrap_or(current_indent), depth)?);
 
-        } else {
-            return syntax_err!(Some(token.span()), "Expected closing parenthesis");
+        skip_newlines(tokens);
+        match tokens.peek() {
+            Some(Token(TokenKind::Comma, ..)) => has_comma = true,
+            Some(Token(kind, ..)) if is_closer_of(kind, &open.0) => {
+                tokens.next();
+                return Ok((elements, has_comma));
+            }
+            _ => return Err(unclosed_bracket(open, tokens, current_indent)),
         }
+        tokens.next(); // Skip the COMMA
     }
+}
 
-    Ok(())
+/// Whether `kind` is the bracket which closes `open`
+fn is_closer_of(kind: &TokenKind, open: &TokenKind) -> bool {
+    matches!(
+        (open, kind),
+        (TokenKind::LeftParen, TokenKind::RightParen) | (TokenKind::LeftSqrBrace, TokenKind::RightSqrBrace) | (TokenKind::LeftBrace, TokenKind::RightBrace)
+    )
 }
 
-fn apply_unary_operator_if_present(atoms: &mut Vec<ExpressionFragment>) -> Result<()> {
-    if let Some(ExpressionFragment::Unparsed(token)) = atoms.get(0) {
-        if let Ok(op) = token.0.as_unary_operator(Some(token.1)) {
-            let first_span = token.1;
-            atoms.remove(0);
-            if let Some(ExpressionFragment::Parsed(expr, span)) = atoms.get(0) {
-                atoms[0] = ExpressionFragment::Parsed(Expression::Unary(
-                    op, 
-                    Box::new(expr.clone())
-                ), first_span + *span);
-            } else {
-                return syntax_err!(Some(first_span.after()), "Expected parsed expression after unary operator. Got: {:?}", atoms.get(0));
+/// Parses the `key: value` entries of a dictionary up to its closing brace, separated by commas
+fn parse_dict_entries(tokens: &mut Tokens, open: &Token, current_indent: usize, depth: usize) -> Result<Vec<(Expression, Expression)>> {
+    let mut entries = Vec::new();
+
+    loop {
+        let line_indent = skip_newlines(tokens);
+        match tokens.peek() {
+            Some(Token(TokenKind::RightBrace, ..)) => {
+                tokens.next();
+                return Ok(entries);
+            }
+            Some(Token(TokenKind::Comma, span)) => {
+                return syntax_err!(codes::EXPECTED_EXPRESSION, Some(*span), "Expected dictionary entry before comma");
             }
+            None | Some(Token(TokenKind::RightParen | TokenKind::RightSqrBrace, ..)) => {
+                return Err(unclosed_bracket(open, tokens, current_indent));
+            }
+            _ => {}
         }
-    }
 
-    Ok(())
-} 
-
-fn evaluate_fragments_on_binary_operators(atoms: &mut Vec<ExpressionFragment>) -> Result<()> {
-    for ops in BinaryOperator::get_precedence_map() {
-        while let Some((op_idx, ExpressionFragment::Unparsed(token))) = atoms.iter().enumerate().find(|(_, partial)| {
-            if let ExpressionFragment::Unparsed(token) = partial {
-                if let Ok(encountered_op) = token.0.as_binary_operator(Some(token.1)) {
-                    if ops.iter().any(|op| op.clone() == encountered_op) {
-                        return true;
-                    }
-                }
+        let indent = line_indent.unwrap_or(current_indent);
+        let key = parse_expression(tokens, indent, depth)?;
+        skip_newlines(tokens);
+        match tokens.next() {
+            Some(Token(TokenKind::Colon, ..)) => {}
+            _ => return syntax_err!(codes::EXPECTED_COLON, Some(key.span), "Expected colon between dictionary key and value"),
+        }
+        let value = parse_expression(tokens, indent, depth)?;
+        entries.push((key, value));
+
+        skip_newlines(tokens);
+        match tokens.peek() {
+            Some(Token(TokenKind::Comma, ..)) => {}
+            Some(Token(TokenKind::RightBrace, ..)) => {
+                tokens.next();
+                return Ok(entries);
             }
-            false
-        }) {
-            let (left, right) = get_binary_operands(atoms, op_idx)?;
-            let op = token.0.as_binary_operator(Some(token.1))?;
+            _ => return Err(unclosed_bracket(open, tokens, current_indent)),
+        }
+        tokens.next(); // Skip the COMMA
+    }
+}
+
+pub fn parse_single_token(token: &Token) -> Result<Expression> {
+    let kind = match &token.0 {
+        TokenKind::Identifier(ident) => ExpressionKind::Identifier(Identifier::new(ident.get_str(), token.1)),
+        TokenKind::Literal(lit) => ExpressionKind::Literal(Value::try_from((*lit, token.1))?),
+        TokenKind::InterpolatedString(parts) => {
+            let parts = parts.iter()
+                .map(|part| match part {
+                    StringPart::Text(text) => Ok(InterpolatedPart::Text(text.clone())),
+                    StringPart::Expression(tokens) => parse_next_expression(&mut Tokens::new(tokens), 0).map(InterpolatedPart::Expression),
+                })
+                .collect::<Result<_>>()?;
+
+            ExpressionKind::Interpolated(parts)
+        }
+        kind => return syntax_err!(codes::UNEXPECTED_TOKEN, Some(token.1), "Unexpected token `{}`", kind),
+    };
+
+    Ok(Expression::new(kind, token.1))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tokenize::parse_tokens;
 
-            let expr = Expression::Binary(Box::new(left), op, Box::new(right));
+    fn parse(source: &str) -> Result<Expression> {
+        let tokens = parse_tokens(source).expect("The test's source should tokenize");
+        parse_next_expression(&mut Tokens::new(&tokens), 0)
+    }
 
-            atoms[op_idx - 1] = ExpressionFragment::Parsed(expr, atoms[op_idx - 1].span() + atoms[op_idx + 1].span());
-            atoms.remove(op_idx);
-            atoms.remove(op_idx);
+    /// Writes the expression with brackets around every operation and call, so its grouping can be compared
+    fn grouped(source: &str) -> String {
+        fn write(expression: &Expression) -> String {
+            match &expression.kind {
+                ExpressionKind::Binary(left, operator, right) => format!("({} {:?} {})", write(left), operator, write(right)),
+                ExpressionKind::Unary(operator, operand) => format!("({:?} {})", operator, write(operand)),
+                ExpressionKind::Call(argument, callee) => format!("({} {})", write(argument), write(callee)),
+                ExpressionKind::Tuple(elements) => format!("({},)", elements.iter().map(write).collect::<Vec<_>>().join(", ")),
+                ExpressionKind::Identifier(identifier) => identifier.name.clone(),
+                ExpressionKind::Literal(value) => value.to_string(),
+                other => panic!("Unexpected expression {:?}", other),
+            }
         }
+
+        write(&parse(source).expect("The test's source should parse"))
     }
 
-    Ok(())
-}
+    fn error(source: &str) -> (ErrorCode, String) {
+        let error = parse(source).expect_err("The test's source shouldn't parse");
+        (error.code, error.message)
+    }
 
+    #[test]
+    fn operators_bind_by_precedence() {
+        assert_eq!(grouped("1 + 2 * 3"), "(1 Plus (2 Mul 3))");
+        assert_eq!(grouped("1 * 2 + 3"), "((1 Mul 2) Plus 3)");
+        assert_eq!(grouped("a || b && c == d"), "(a Or (b And (c Equal d)))");
+        assert_eq!(grouped("1 + 2 < 4 % 3"), "((1 Plus 2) Less (4 Mod 3))");
+        assert_eq!(grouped("0..n $ print"), "((0 Range n) ForEach print)");
+        assert_eq!(grouped("xs @ double ||| even && ok"), "(((xs Map double) Any even) And ok)");
+    }
 
-fn get_binary_operands(atoms: &[ExpressionFragment], idx: usize) -> Result<(Expression, Expression)> {
-    let left = match &atoms.get(idx - 1) {
-        Some(ExpressionFragment::Parsed(expr, ..)) => expr.clone(),
-        Some(ExpressionFragment::Unparsed(token)) => parse_single_token(token)?,
-        None => return syntax_err!(Some(atoms[idx].span().after()), "Expected expression before binary operator {:?}", atoms[idx]),
-    };
+    #[test]
+    fn operators_of_equal_precedence_group_left_to_right() {
+        assert_eq!(grouped("1 - 2 - 3"), "((1 Minus 2) Minus 3)");
+        assert_eq!(grouped("8 / 4 * 2"), "((8 Div 4) Mul 2)");
+        assert_eq!(grouped("a == b != c"), "((a Equal b) NotEqual c)");
+    }
 
-    let right = match &atoms.get(idx + 1) {
-        Some(ExpressionFragment::Parsed(expr, ..)) => expr.clone(),
-        Some(ExpressionFragment::Unparsed(token)) => parse_single_token(token)?,
-        None => return syntax_err!(Some(atoms[idx].span().after()), "Expected expression after binary operator {:?}", atoms[idx]),
-    };
+    #[test]
+    fn unary_operators_and_calls() {
+        assert_eq!(grouped("1 + -2"), "(1 Plus (Minus 2))");
+        assert_eq!(grouped("- - 3"), "(Minus (Minus 3))");
+        assert_eq!(grouped("!a && b"), "((Not a) And b)");
+        assert_eq!(grouped("-3 abs print"), "(((Minus 3) abs) print)");
+        assert_eq!(grouped("1 + x f"), "(1 Plus (x f))");
+    }
 
-    Ok((left, right))
-}
+    #[test]
+    fn brackets_group_and_make_tuples() {
+        assert_eq!(grouped("(1 + 2) * 3"), "((1 Plus 2) Mul 3)");
+        assert_eq!(grouped("(1, (2, 3),)"), "(1, (2, 3,),)");
+        assert_eq!(grouped("()"), "None");
 
-#[allow(unused_variables)]
-fn evaluate_fragments_on_calls(atoms: &mut Vec<ExpressionFragment>) -> Result<()> {
-    while let Some((idx, [
-            ExpressionFragment::Parsed(expr1, span1), 
-            ExpressionFragment::Parsed(expr2, span2)
-        ])) = atoms.windows(2).enumerate().find(|(_, frags)| {
-        matches!((&frags[0], &frags[1]), (ExpressionFragment::Parsed(..), ExpressionFragment::Parsed(..)))
-    }) {
-        let call = Expression::Call(Box::from(expr1.clone()), Box::from(expr2.clone()));
-        atoms[idx] = ExpressionFragment::Parsed(call, *span1 + *span2);
-        atoms.remove(idx + 1);
+        let nested = format!("{}1{}", "(".repeat(50), ")".repeat(50));
+        assert_eq!(grouped(&nested), "1");
     }
 
-    Ok(())
-}
+    #[test]
+    fn lines_indented_further_continue_the_expression() {
+        assert_eq!(grouped("1 +\n  2\n  * 3"), "(1 Plus (2 Mul 3))");
+        assert_eq!(grouped("(1\n+ 2)"), "(1 Plus 2)");
+        assert_eq!(grouped("1\n+ 2"), "1");
+    }
 
-pub fn parse_single_token(token: &Token) -> Result<Expression> {
-    match token.0 {
-        TokenKind::Identifier(ident) => Ok(Expression::Identifier(Identifier { name: ident.get_str().to_string() })),
-        TokenKind::Literal(lit) => Ok(Expression::Literal(Value::try_from((lit, token.1))?)),
-        _ => syntax_err!(Some(token.1), "Unexpected token"),
+    #[test]
+    fn errors_spell_tokens_like_the_source() {
+        assert_eq!(error("1 +"), (codes::EXPECTED_EXPRESSION, "Expected expression after binary operator `+`".to_string()));
+        assert_eq!(error("!"), (codes::EXPECTED_EXPRESSION, "Expected expression after unary operator `!`".to_string()));
+        assert_eq!(error("x let"), (codes::UNEXPECTED_TOKEN, "Unexpected token `let` after expression".to_string()));
+        assert_eq!(error("1 )"), (codes::UNEXPECTED_TOKEN, "Unexpected token `)` after expression".to_string()));
+        assert_eq!(error("(1; 2)"), (codes::UNEXPECTED_TOKEN, "Expected `,` or `)`, found `;`".to_string()));
+        assert_eq!(error("p."), (codes::EXPECTED_IDENTIFIER, "Expected name after `.`".to_string()));
+        assert_eq!(error("(1, 2\n3"), (codes::UNCLOSED_DELIMITER, "Expected closing parenthesis".to_string()));
+        assert_eq!(error("[1, 2)"), (codes::UNCLOSED_DELIMITER, "Expected closing square bracket".to_string()));
     }
 }
@@ -1,115 +1,525 @@
 use crate::prelude::*;
-use super::parse_expr::{parse_next_expression, parse_single_token};
-use super::parse_ast::{parse_block, parse_next_statement, find_end_of_block};
+use super::parse_expr::parse_next_expression;
+use super::parse_ast::{parse_block, parse_next_statement};
+use super::parse_type::parse_type;
 use crate::constructs::token::{TokenKind, Token, Tokens};
+use crate::constructs::token::span::Span;
 use crate::constructs::ast::*;
 use crate::constructs::variable::*;
 
-#[allow(unused_variables)]
-pub fn parse_match_expression(tokens: &mut Tokens, current_indent: usize) -> Result<Expression> {
+/// Parses a match expression. Every arm starts with a bar lined up with the first one, e.g.
+/// ```text
+/// obj | int i : i + 1
+///     | float f :
+///       f * 2.0
+/// ```
+/// `env_indent` is the indentation of the line the expression is on. The body of an arm
+/// can go on the lines after its colon, as long as they are indented further than that.
+/// A match `in_brackets` also ends at a comma or the closing bracket.
+pub fn parse_match_expression(tokens: &mut Tokens, env_indent: usize, in_brackets: bool) -> Result<Expression> {
+    let Some(Token(TokenKind::Bar(match_indent), start)) = tokens.peek() else {
+        return expected_arm(tokens.peek());
+    };
+    let match_indent = *match_indent;
     let mut arms = vec![];
 
     loop {
-        let end = find_end_of_block(&mut tokens.clone(), current_indent + 1);
-        if end == 0 {
-            break;
-        }
-        let pattern;
+        let end = find_end_of_match_arm(&mut tokens.clone(), env_indent, match_indent, in_brackets);
+        let bar_span = tokens.peek().map_or(Span { index: 0, len: 0 }, |Token(_, span)| *span);
 
-        if let Some(Token(TokenKind::Bar(indent), span)) = tokens.next() {
-            if *indent != current_indent {
-                return syntax_err!(Some(*span), "Expected match arm to be indented");
+        match parse_match_arm(&mut tokens.take(end), env_indent, match_indent) {
+            Ok(arm) => arms.push(arm),
+            Err(err) => {
+                // The arm's tokens have been skipped, so parsing picks back up at the next arm
+                arms.push(MatchArm {
+                    pattern: Pattern {
+                        kind: PatternKind::Empty,
+                        guard: None,
+                        span: bar_span,
+                    },
+                    block: Block {
+                        statements: vec!(Statement::Error(err))
+                    }
+                });
             }
-        } else {
-            return syntax_err!(tokens.peek().map(|tok| tok.1), "Tried to parse match arm without bar. Instead, got: {:?}", tokens.peek());
         }
 
-        if let Some((idx, token)) = tokens.clone().enumerate().find(|(_, token)| {
-            matches!(token, Token(TokenKind::Colon, ..))
-        }) {
-            if idx > end {
-                return syntax_err!(None, "Expected colon after match pattern");
-            }
-            pattern = parse_pattern(&mut tokens.take(idx))?;
-        } else {
-            return syntax_err!(None, "Expected colon after match pattern");
+        if !starts_next_arm(tokens, match_indent) {
+            break;
         }
+        tokens.next(); // Skip the NEWLINE
+    }
+    
+    Ok(Expression::new(ExpressionKind::Literal(Value::Function(Function::Match {
+        arms,
+    })), tokens.span_from(*start)))
+}
 
-        tokens.next(); // Skip the COLON
+/// Returns the number of tokens in the match expression starting with the bar at `match_indent`
+pub(super) fn find_end_of_match(tokens: &mut Tokens, env_indent: usize, match_indent: usize) -> usize {
+    let mut length = 0;
+    loop {
+        let arm_length = find_end_of_match_arm(&mut tokens.clone(), env_indent, match_indent, false);
+        length += arm_length;
+        tokens.skip(arm_length);
 
-        let block = if let Some(Token(TokenKind::Newline(_), ..)) = tokens.peek() {
-            tokens.next();
-            parse_block(tokens, current_indent + 1)?
-        } else {
-            Block{
-                statements: vec!(parse_next_statement(tokens, current_indent)?)
-            }
-        };
+        if !starts_next_arm(tokens, match_indent) {
+            return length;
+        }
         tokens.next(); // Skip the NEWLINE
+        length += 1;
+    }
+}
 
-        arms.push(MatchArm {
-            pattern,
-            block,
-        });
+/// An arm ends at the first line which isn't indented past `env_indent`, 
+/// or which starts with the bar of the next arm. If the match is `in_brackets`, it also ends
+/// at a comma or a closing bracket it isn't nested in.
+fn find_end_of_match_arm(tokens: &mut Tokens, env_indent: usize, match_indent: usize, in_brackets: bool) -> usize {
+    let mut length = 0;
+    let mut depth = 0;
+    loop {
+        match tokens.next() {
+            Some(Token(kind, ..)) if in_brackets && depth == 0 && (kind.nesting() < 0 || *kind == TokenKind::Comma) => return length,
+            Some(Token(TokenKind::Newline(_), ..)) if depth > 0 => {}
+            Some(Token(TokenKind::Newline(indent), ..)) if *indent <= env_indent => return length,
+            Some(Token(TokenKind::Newline(_), ..)) if matches!(tokens.peek(), Some(Token(TokenKind::Bar(indent), ..)) if *indent == match_indent) => return length,
+            Some(Token(kind, ..)) => depth += kind.nesting(),
+            None => return length,
+        }
+        length += 1;
     }
-    
-    Ok(Expression::Literal(Value::Function(Function::Match {
-        arms,
-    })))
 }
 
-fn parse_pattern(tokens: &mut Tokens) -> Result<Pattern> {
-    if let Some((idx, _)) = tokens.clone().enumerate().find(|(_, token)| {
-        matches!(token, Token(TokenKind::Tilde, ..))
+fn starts_next_arm(tokens: &Tokens, match_indent: usize) -> bool {
+    let mut lookahead = tokens.clone();
+    matches!(
+        (lookahead.next(), lookahead.next()),
+        (Some(Token(TokenKind::Newline(_), ..)), Some(Token(TokenKind::Bar(indent), ..))) if *indent == match_indent
+    )
+}
+
+fn expected_arm<T>(found: Option<&Token>) -> Result<T> {
+    match found {
+        Some(Token(kind, span)) => syntax_err!(codes::EXPECTED_MATCH_ARM, Some(*span), "Expected match arm, found `{}`", kind),
+        None => syntax_err!(codes::EXPECTED_MATCH_ARM, None, "Expected match arm"),
+    }
+}
+
+fn parse_match_arm(tokens: &mut Tokens, env_indent: usize, match_indent: usize) -> Result<MatchArm> {
+    let pattern;
+
+    let Some(Token(TokenKind::Bar(_), bar_span)) = tokens.peek() else {
+        return expected_arm(tokens.peek());
+    };
+    tokens.next(); // Skip the BAR
+
+    // Skip colons in brackets, like the one in `| {string: int} counts :`
+    let mut depth = 0;
+    if let Some(idx) = tokens.clone().position(|Token(kind, ..)| {
+        depth += kind.nesting();
+        depth == 0 && *kind == TokenKind::Colon
     }) {
-        let mut identifier = None;
-        let mut typ = None;
+        let colon_span = tokens.clone().nth(idx).map_or(*bar_span, |Token(_, span)| *span);
+        pattern = parse_pattern(&mut tokens.take(idx), *bar_span + colon_span)?;
+    } else {
+        // Point just after the pattern, which ends with its line
+        let mut depth = 0;
+        let last = tokens.clone()
+            .take_while(|Token(kind, ..)| {
+                depth += kind.nesting();
+                depth != 0 || !matches!(kind, TokenKind::Newline(_))
+            })
+            .last();
+        let span = last.map_or(*bar_span, |Token(_, span)| *span).after();
+        return syntax_err!(codes::EXPECTED_COLON, Some(span), "Expected colon after match pattern");
+    }
 
-        if idx != 0 {
-            let pattern = parse_pattern(&mut tokens.take(idx))?;
-            identifier = pattern.identifier;
-            typ = pattern.typ;
+    let Some(Token(_, colon_span)) = tokens.next() else {
+        return compiler_err!("Tried to parse a match arm without a colon");
+    };
+
+    let block = if let Some(Token(TokenKind::Newline(indent), ..)) = tokens.peek() {
+        let block = parse_block(tokens, *indent);
+        if block.statements.is_empty() {
+            return syntax_err!(codes::EXPECTED_BLOCK, Some(colon_span.after()), "Expected block");
+        }
+
+        // The block stops at the first line indented less than it
+        if let Some(Token(kind, span)) = tokens.find(|token| !matches!(token, Token(TokenKind::Newline(_), ..))) {
+            return if let TokenKind::Bar(_) = kind {
+                syntax_err!(codes::MISALIGNED_MATCH_ARM, Some(*span), "Expected match arm to line up with the first arm")
+            } else {
+                syntax_err!(codes::UNEXPECTED_TOKEN, Some(*span), "Expected line to be indented like the rest of the block")
+            };
         }
 
+        block
+    } else {
+        // A line starting with a bar to the left of this one can't be part of an inline body,
+        // so it's most likely an arm which doesn't line up
+        let mut lookahead = tokens.clone();
+        while let Some(token) = lookahead.next() {
+            if let (Token(TokenKind::Newline(_), ..), Some(Token(TokenKind::Bar(indent), span))) = (token, lookahead.peek()) {
+                if *indent < match_indent {
+                    return syntax_err!(codes::MISALIGNED_MATCH_ARM, Some(*span), "Expected match arm to line up with the first arm");
+                }
+            }
+        }
+
+        Block{
+            statements: vec!(parse_next_statement(tokens, env_indent)?)
+        }
+    };
+
+    Ok(MatchArm {
+        pattern,
+        block,
+    })
+}
+
+fn parse_pattern(tokens: &mut Tokens, span: Span) -> Result<Pattern> {
+    if let Some(idx) = tokens.clone().position(|token| {
+        matches!(token, Token(TokenKind::Tilde, ..))
+    }) {
+        let kind = parse_pattern_kind(&mut tokens.take(idx))?;
+
         tokens.next(); // Skip the TILDE
 
+        // `|~ :` has no guard, which makes it the else of an if statement
+        let guard = if tokens.peek().is_some() {
+            Some(parse_next_expression(tokens, 0)?)
+        } else {
+            None
+        };
+
         Ok(Pattern {
-            identifier,
-            typ,
-            guard: Some(parse_next_expression(tokens, 0)?),
+            kind,
+            guard,
+            span,
         })
     } else {
-        // FML!!!
-        if let Some(first) = tokens.next() {
-            if let Expression::Identifier(id) = parse_single_token(first)? {
-                if let Some(second) = tokens.next() {
-                    if let Expression::Identifier(ident) = parse_single_token(second)? {
-                        Ok(Pattern {
-                            identifier: Some(ident),
-                            typ: Some(Type::from_id(&id.name).unwrap()),
-                            guard: None
-                        })
-                    } else {
-                        syntax_err!(Some(first.1.after()), "Expected identifier after type")
-                    }
-                } else {
-                    Ok(Pattern {
-                        identifier: Some(id),
-                        typ: None,
-                        guard: None
-                    })
-                }
+        Ok(Pattern {
+            kind: parse_pattern_kind(tokens)?,
+            guard: None,
+            span,
+        })
+    }
+}
+
+/// Parses the part of a pattern before its guard, e.g. `int num`, `(a, (int b, c))`, `Shape::Rect(w, h)` or `1 | 2 | 3`
+fn parse_pattern_kind(tokens: &mut Tokens) -> Result<PatternKind> {
+    if tokens.peek().is_none() {
+        return Ok(PatternKind::Empty);
+    }
+
+    let kind = parse_alternatives(tokens)?;
+    match tokens.next() {
+        Some(Token(kind, span)) => Err(unexpected_in_pattern(kind, *span)),
+        None => Ok(kind),
+    }
+}
+
+/// Parses a pattern along with any alternatives to it, up to a comma or bracket it isn't nested in
+fn parse_alternatives(tokens: &mut Tokens) -> Result<PatternKind> {
+    // An arm only starts at the beginning of a line, so a bar inside a pattern can only separate alternatives
+    let mut alternatives = Vec::new();
+    let mut previous_bar: Option<Span> = None;
+    loop {
+        match tokens.peek() {
+            Some(Token(TokenKind::Bar(_), span)) => {
+                return syntax_err!(codes::INVALID_PATTERN, Some(*span), "Expected pattern before bar");
+            }
+            next if previous_bar.is_some() && ends_pattern(next) => {
+                let span = previous_bar.map(|span| span.after());
+                return syntax_err!(codes::INVALID_PATTERN, span, "Expected pattern after bar");
+            }
+            _ => {}
+        }
+
+        let alternative_span = tokens.peek().map(|Token(_, span)| *span);
+        alternatives.push((parse_single_pattern(tokens)?, alternative_span));
+
+        match tokens.peek() {
+            Some(Token(TokenKind::Bar(_), span)) => {
+                previous_bar = Some(*span);
+                tokens.next();
+            }
+            _ => break,
+        }
+    }
+
+    if alternatives.len() == 1 {
+        return Ok(alternatives.remove(0).0);
+    }
+
+    let mut expected = Vec::new();
+    bound_identifiers(&alternatives[0].0, &mut expected);
+    expected.sort();
+    for (alternative, span) in &alternatives[1..] {
+        let mut bound = Vec::new();
+        bound_identifiers(alternative, &mut bound);
+        bound.sort();
+        if bound != expected {
+            return syntax_err!(codes::INVALID_PATTERN, *span, "Every alternative of a pattern has to bind the same identifiers");
+        }
+    }
+
+    Ok(PatternKind::Or(alternatives.into_iter().map(|(alternative, _)| alternative).collect()))
+}
+
+/// Whether the token ends the pattern it comes after
+fn ends_pattern(token: Option<&Token>) -> bool {
+    matches!(token, None | Some(Token(TokenKind::Bar(_) | TokenKind::Comma | TokenKind::RightParen, ..)))
+}
+
+fn unexpected_in_pattern(kind: &TokenKind, span: Span) -> Diagnostic {
+    let error = Diagnostic::new(DiagnosticKind::Syntax, codes::INVALID_PATTERN, format!("Unexpected token `{}` in pattern", kind))
+        .with_label(span, "unexpected token");
+    match kind {
+        TokenKind::DoubleBar => error.with_help("Alternatives are separated by a single bar, like `1 | 2`"),
+        _ => error,
+    }
+}
+
+/// Adds the name of every identifier the pattern binds to `names`
+fn bound_identifiers(pattern: &PatternKind, names: &mut Vec<String>) {
+    match pattern {
+        PatternKind::Binding { identifier, .. } => names.push(identifier.name.clone()),
+        PatternKind::Tuple(elements) | PatternKind::Variant { payload: elements, .. } => {
+            for element in elements {
+                bound_identifiers(element, names);
+            }
+        }
+        // Every alternative binds the same identifiers, so the first one is enough
+        PatternKind::Or(alternatives) => bound_identifiers(&alternatives[0], names),
+        PatternKind::Empty | PatternKind::Wildcard | PatternKind::Literal(_) | PatternKind::Range { .. } => {}
+    }
+}
+
+/// Parses a pattern without any alternatives
+fn parse_single_pattern(tokens: &mut Tokens) -> Result<PatternKind> {
+    let Some(Token(first_kind, first_span)) = tokens.peek() else {
+        return compiler_err!("Tried to parse an empty pattern");
+    };
+
+    let kind = match first_kind {
+        TokenKind::Literal(_) | TokenKind::Minus => parse_literal_pattern(tokens)?,
+        TokenKind::Identifier(first) if first.get_str() == "_" && ends_pattern(tokens.clone().nth(1)) => {
+            tokens.next();
+            PatternKind::Wildcard
+        }
+        TokenKind::LeftParen => {
+            tokens.next(); // Skip the LEFT_PAREN
+            let (mut elements, is_tuple) = parse_pattern_list(tokens, *first_span)?;
+
+            // Like in expressions, `(a)` is just `a`, while `(a, b)` and `(a,)` destructure tuples
+            if elements.is_empty() {
+                return syntax_err!(codes::INVALID_PATTERN, Some(*first_span), "Expected pattern inside parentheses");
+            } else if is_tuple {
+                PatternKind::Tuple(elements)
             } else {
-                syntax_err!(Some(first.1), "Expected identifier")
+                elements.remove(0)
             }
-        } else {
-            Ok(
-                Pattern {
-                    identifier: None,
+        }
+        TokenKind::Identifier(first) => match tokens.clone().nth(1) {
+            Some(Token(TokenKind::ColonColon, ..)) => parse_variant_pattern(tokens)?,
+            Some(Token(TokenKind::Identifier(second), second_span)) => {
+                tokens.skip(2);
+                match Type::from_id(first.get_str()) {
+                    Some(typ) => PatternKind::Binding {
+                        typ: Some(typ),
+                        identifier: Identifier::new(second.get_str(), *second_span),
+                    },
+                    None => return Err(Diagnostic::new(DiagnosticKind::Syntax, codes::UNKNOWN_TYPE, format!("Unknown type {}", first.get_str()))
+                        .with_label(*first_span, "expected a type")
+                        .with_note("The known types are int, float, string, bool, iter, match, tuple, none, lists like [int] and types declared with type")),
+                }
+            }
+            Some(next @ Token(.., span)) if !ends_pattern(Some(next)) && Type::from_id(first.get_str()).is_some() => {
+                return syntax_err!(codes::EXPECTED_IDENTIFIER, Some(*span), "Expected identifier after type");
+            }
+            _ => {
+                tokens.next();
+                PatternKind::Binding {
                     typ: None,
-                    guard: None
+                    identifier: Identifier::new(first.get_str(), *first_span),
+                }
+            }
+        },
+        TokenKind::LeftSqrBrace | TokenKind::LeftBrace => {
+            let typ = parse_type(tokens)?;
+            match tokens.next() {
+                Some(Token(TokenKind::Identifier(ident), ident_span)) => PatternKind::Binding {
+                    typ: Some(typ),
+                    identifier: Identifier::new(ident.get_str(), *ident_span),
+                },
+                other => {
+                    let span = other.map_or(first_span.after(), |Token(_, span)| *span);
+                    return syntax_err!(codes::EXPECTED_IDENTIFIER, Some(span), "Expected identifier after type");
                 }
-            )
+            }
+        }
+        kind => return syntax_err!(codes::INVALID_PATTERN, Some(*first_span), "Expected pattern, found `{}`", kind),
+    };
+
+    match tokens.peek() {
+        next if ends_pattern(next) => Ok(kind),
+        Some(Token(kind, span)) => Err(unexpected_in_pattern(kind, *span)),
+        None => Ok(kind),
+    }
+}
+
+/// Parses a literal pattern like `0`, `-1.5` or `"quit"`, or a range pattern like `1..10` or `"a"..="z"`
+fn parse_literal_pattern(tokens: &mut Tokens) -> Result<PatternKind> {
+    let (start, start_span) = parse_pattern_literal(tokens, None)?;
+    let Some(Token(TokenKind::DotDot, dot_dot_span)) = tokens.peek() else {
+        return Ok(PatternKind::Literal(start));
+    };
+    let mut range_span = *dot_dot_span;
+
+    tokens.next(); // Skip the DOT_DOT
+    let inclusive = matches!(tokens.peek(), Some(Token(TokenKind::Equal, ..)));
+    if let Some(Token(TokenKind::Equal, span)) = tokens.peek() {
+        range_span = range_span + *span;
+        tokens.next(); // Skip the EQUAL
+    }
+    let (end, end_span) = parse_pattern_literal(tokens, Some(range_span))?;
+
+    match (&start, &end) {
+        (Value::Int(_), Value::Int(_)) | (Value::Float(_), Value::Float(_)) | (Value::String(_), Value::String(_)) => {
+            Ok(PatternKind::Range { start, end, inclusive })
         }
+        _ => Err(Diagnostic::new(DiagnosticKind::Syntax, codes::INVALID_PATTERN, "Range pattern needs two ints, floats or strings".to_string())
+            .with_label(start_span + end_span, "can't be a range")),
+    }
+}
+
+/// Parses a literal in a pattern, which can be negated if it's a number. `previous` is the token
+/// before it, which is pointed at if the pattern ends there.
+fn parse_pattern_literal(tokens: &mut Tokens, previous: Option<Span>) -> Result<(Value, Span)> {
+    let negation = match tokens.peek() {
+        Some(Token(TokenKind::Minus, span)) => {
+            tokens.next();
+            Some(*span)
+        }
+        _ => None,
+    };
+
+    if ends_pattern(tokens.peek()) {
+        return syntax_err!(codes::INVALID_PATTERN, negation.or(previous), "Expected literal after {}", if negation.is_some() { "-" } else { "range" });
+    }
+    let (literal, span) = match tokens.next() {
+        Some(Token(TokenKind::Literal(literal), span)) => (Value::try_from((*literal, *span))?, *span),
+        Some(Token(kind, span)) => return syntax_err!(codes::INVALID_PATTERN, Some(*span), "Expected literal, found `{}`", kind),
+        None => return compiler_err!("Tried to parse a literal at the end of a pattern"),
+    };
+
+    match (negation, literal) {
+        (None, literal) => Ok((literal, span)),
+        (Some(minus_span), Value::Int(i)) => Ok((Value::Int(-i), minus_span + span)),
+        (Some(minus_span), Value::Float(f)) => Ok((Value::Float(-f), minus_span + span)),
+        (Some(minus_span), _) => syntax_err!(codes::INVALID_PATTERN, Some(minus_span + span), "Only numbers can be negated in a pattern"),
+    }
+}
+
+/// Parses a pattern for a variant of an enum type, e.g. `Shape::Rect(w, h)` or `Shape::Empty`
+fn parse_variant_pattern(tokens: &mut Tokens) -> Result<PatternKind> {
+    let Some(Token(TokenKind::Identifier(typ), typ_span)) = tokens.next() else {
+        return compiler_err!("Tried to parse a variant pattern without a type name");
+    };
+    let Some(Token(TokenKind::ColonColon, colons_span)) = tokens.next() else {
+        return compiler_err!("Tried to parse a variant pattern without ::");
+    };
+
+    let variant = match tokens.next() {
+        Some(Token(TokenKind::Identifier(variant), span)) => Identifier::new(variant.get_str(), *span),
+        Some(Token(.., span)) => return syntax_err!(codes::EXPECTED_IDENTIFIER, Some(*span), "Expected variant name after ::"),
+        None => return syntax_err!(codes::EXPECTED_IDENTIFIER, Some(colons_span.after()), "Expected variant name after ::"),
+    };
+
+    let payload = match tokens.peek() {
+        Some(Token(TokenKind::LeftParen, open_span)) => {
+            tokens.next(); // Skip the LEFT_PAREN
+            let (elements, _) = parse_pattern_list(tokens, *open_span)?;
+
+            if elements.is_empty() {
+                return syntax_err!(codes::INVALID_PATTERN, Some(*open_span), "Expected pattern inside parentheses");
+            }
+            elements
+        }
+        _ => Vec::new(),
+    };
+
+    Ok(PatternKind::Variant {
+        typ: Identifier::new(typ.get_str(), *typ_span),
+        variant,
+        payload,
+    })
+}
+
+/// Parses patterns separated by commas up to the closing parenthesis, allowing a trailing comma.
+/// Also returns whether there were any commas.
+fn parse_pattern_list(tokens: &mut Tokens, open_span: Span) -> Result<(Vec<PatternKind>, bool)> {
+    let mut elements = Vec::new();
+    let mut has_comma = false;
+
+    loop {
+        match tokens.peek() {
+            Some(Token(TokenKind::RightParen, ..)) => {
+                tokens.next();
+                return Ok((elements, has_comma));
+            }
+            Some(Token(TokenKind::Comma, span)) => {
+                return syntax_err!(codes::INVALID_PATTERN, Some(*span), "Expected pattern before comma");
+            }
+            None => return syntax_err!(codes::UNCLOSED_DELIMITER, Some(open_span), "Expected closing parenthesis"),
+            _ => {}
+        }
+
+        elements.push(parse_alternatives(tokens)?);
+
+        match tokens.next() {
+            Some(Token(TokenKind::Comma, ..)) => has_comma = true,
+            Some(Token(TokenKind::RightParen, ..)) => return Ok((elements, has_comma)),
+            Some(Token(kind, span)) => return Err(unexpected_in_pattern(kind, *span)),
+            None => return syntax_err!(codes::UNCLOSED_DELIMITER, Some(open_span), "Expected closing parenthesis"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tokenize::parse_tokens;
+
+    /// Writes the pattern of the first arm back out, with brackets around each tuple
+    fn pattern(source: &str) -> String {
+        fn write(kind: &PatternKind) -> String {
+            match kind {
+                PatternKind::Binding { typ: Some(typ), identifier } => format!("{} {}", typ, identifier.name),
+                PatternKind::Binding { typ: None, identifier } => identifier.name.clone(),
+                PatternKind::Tuple(elements) if elements.len() == 1 => format!("({},)", write(&elements[0])),
+                PatternKind::Tuple(elements) => format!("({})", elements.iter().map(write).collect::<Vec<_>>().join(", ")),
+                PatternKind::Wildcard => "_".to_string(),
+                other => panic!("Unexpected pattern {:?}", other),
+            }
+        }
+
+        let tokens = parse_tokens(&format!("| {} : 0", source)).expect("The test's source should tokenize");
+        let expression = parse_match_expression(&mut Tokens::new(&tokens), 0, false).expect("The test's source should parse");
+        let ExpressionKind::Literal(Value::Function(Function::Match { arms })) = expression.kind else {
+            panic!("Expected a match, got {:?}", expression.kind);
+        };
+        write(&arms[0].pattern.kind)
+    }
+
+    #[test]
+    fn tuple_patterns() {
+        assert_eq!(pattern("(a, b)"), "(a, b)");
+        assert_eq!(pattern("(int a, string b)"), "(int a, string b)");
+        assert_eq!(pattern("(int a, (b, (float c, _)))"), "(int a, (b, (float c, _)))");
+        assert_eq!(pattern("([int] xs, {string: int} counts)"), "([int] xs, {string: int} counts)");
+        assert_eq!(pattern("tuple t"), "tuple t");
+        assert_eq!(pattern("(int a,)"), "(int a,)");
     }
 }
@@ -0,0 +1,262 @@
+use crate::prelude::*;
+use super::parse_ast::find_end_of_block;
+use super::parse_expr::parse_next_expression;
+use crate::constructs::token::{TokenKind, Token, Tokens};
+use crate::constructs::ast::*;
+use crate::constructs::variable::{Type, TypeBody};
+
+/// Parses a type declaration. A type either has named fields, e.g.
+/// ```text
+/// type Person:
+///   name: string
+///   age: int
+/// ```
+/// or variants separated by bars, each with an optional payload, like `type Shape: Circle(float) | Rect(float, float)`.
+/// Short types can be declared on one line, like `type Point: x: int, y: int`
+pub(super) fn parse_type_declaration(tokens: &mut Tokens, current_indent: usize) -> Result<Statement> {
+    let end = find_end_of_block(&mut tokens.clone(), current_indent + 1);
+    let tokens = &mut tokens.take(end);
+    let name = parse_declaration_header(tokens)?;
+
+    // Fields never have bars in them, so any bar means the type has variants
+    let is_enum = tokens.clone().any(|token| matches!(token, Token(TokenKind::Bar(_), ..)));
+
+    let body = if is_enum {
+        TypeBody::Enum(parse_variants(tokens)?)
+    } else {
+        TypeBody::Struct(parse_fields(tokens, current_indent)?)
+    };
+
+    Ok(Statement::Type(TypeDeclaration { name, body }))
+}
+
+/// Parses the `name: type` fields of a type declaration
+fn parse_fields(tokens: &mut Tokens, current_indent: usize) -> Result<Vec<(String, Type)>> {
+    let mut fields: Vec<(String, Type)> = Vec::new();
+    for (mut member, _) in split_members(tokens, current_indent, true) {
+        let (field, field_span) = match member.next() {
+            Some(Token(TokenKind::Identifier(field), span)) => (field.get_str().to_string(), *span),
+            Some(Token(.., span)) => return syntax_err!(codes::EXPECTED_IDENTIFIER, Some(*span), "Expected field name"),
+            None => return compiler_err!("Tried to parse an empty field"),
+        };
+        if fields.iter().any(|(existing, _)| *existing == field) {
+            return syntax_err!(codes::DUPLICATE_FIELD, Some(field_span), "Field {} is declared twice", field);
+        }
+
+        match member.next() {
+            Some(Token(TokenKind::Colon, ..)) => {}
+            other => {
+                let span = other.map_or(field_span.after(), |Token(_, span)| *span);
+                return syntax_err!(codes::EXPECTED_COLON, Some(span), "Expected colon between field name and type");
+            }
+        }
+
+        let typ = parse_type(&mut member)?;
+        if let Some(Token(kind, span)) = member.next() {
+            return syntax_err!(codes::UNEXPECTED_TOKEN, Some(*span), "Unexpected token `{}` after field type", kind);
+        }
+        fields.push((field, typ));
+    }
+
+    Ok(fields)
+}
+
+/// Parses the variants of an enum type, e.g. `Circle(float) | Rect(float, float) | Empty`.
+/// The variants can also go on their own lines, each starting with a bar.
+fn parse_variants(tokens: &mut Tokens) -> Result<Vec<(String, Vec<Type>)>> {
+    let without_newlines: Vec<Token> = tokens.filter(|token| !matches!(token, Token(TokenKind::Newline(_), ..)))
+        .cloned()
+        .collect();
+    let tokens = &mut Tokens::new(&without_newlines);
+    // A bar before the first variant is allowed, so that every line can start with one
+    if let Some(Token(TokenKind::Bar(_), ..)) = tokens.peek() {
+        tokens.next();
+    }
+
+    let mut variants: Vec<(String, Vec<Type>)> = Vec::new();
+    loop {
+        let (variant, variant_span) = match tokens.next() {
+            Some(Token(TokenKind::Identifier(variant), span)) => (variant.get_str().to_string(), *span),
+            Some(Token(.., span)) => return syntax_err!(codes::EXPECTED_IDENTIFIER, Some(*span), "Expected variant name"),
+            None => return syntax_err!(codes::EXPECTED_IDENTIFIER, None, "Expected variant name"),
+        };
+        if variants.iter().any(|(existing, _)| *existing == variant) {
+            return syntax_err!(codes::DUPLICATE_FIELD, Some(variant_span), "Variant {} is declared twice", variant);
+        }
+
+        let mut payload = Vec::new();
+        if let Some(Token(TokenKind::LeftParen, open_span)) = tokens.peek() {
+            tokens.next(); // Skip the LEFT_PAREN
+            loop {
+                payload.push(parse_type(tokens)?);
+                match tokens.next() {
+                    Some(Token(TokenKind::Comma, ..)) => {}
+                    Some(Token(TokenKind::RightParen, ..)) => break,
+                    _ => return syntax_err!(codes::UNCLOSED_DELIMITER, Some(*open_span), "Expected closing parenthesis"),
+                }
+            }
+        }
+        variants.push((variant, payload));
+
+        match tokens.next() {
+            Some(Token(TokenKind::Bar(_), ..)) => {}
+            Some(Token(kind, span)) => return syntax_err!(codes::UNEXPECTED_TOKEN, Some(*span), "Unexpected token `{}` after variant", kind),
+            None => return Ok(variants),
+        }
+    }
+}
+
+/// Parses the static members of a type, e.g.
+/// ```text
+/// impl Person:
+///   count = 0
+///   new = | (name, age) : Person: name, age
+/// ```
+pub(super) fn parse_impl(tokens: &mut Tokens, current_indent: usize) -> Result<Statement> {
+    let end = find_end_of_block(&mut tokens.clone(), current_indent + 1);
+    let tokens = &mut tokens.take(end);
+    let name = parse_declaration_header(tokens)?;
+
+    let mut members: Vec<(Identifier, Expression)> = Vec::new();
+    for (mut member, indent) in split_members(tokens, current_indent, false) {
+        let (member_name, name_span) = match (member.next(), member.next()) {
+            (Some(Token(TokenKind::Identifier(member_name), span)), Some(Token(TokenKind::Equal, ..))) => (member_name.get_str().to_string(), *span),
+            (Some(Token(.., span)), _) => return Err(Diagnostic::new(DiagnosticKind::Syntax, codes::UNEXPECTED_TOKEN, "Expected a static member".to_string())
+                .with_label(*span, "expected `name = value`")),
+            (None, _) => return compiler_err!("Tried to parse an empty static member"),
+        };
+        if members.iter().any(|(existing, _)| existing.name == member_name) {
+            return syntax_err!(codes::DUPLICATE_FIELD, Some(name_span), "Static member {} is declared twice", member_name);
+        }
+
+        let value = parse_next_expression(&mut member, indent)?;
+        members.push((Identifier::new(member_name, name_span), value));
+    }
+
+    Ok(Statement::Impl(name, members))
+}
+
+/// Parses the `type Name:` or `impl Name:` at the start of a declaration, returning the name
+fn parse_declaration_header(tokens: &mut Tokens) -> Result<Identifier> {
+    let Some(Token(TokenKind::Keyword(keyword), keyword_span)) = tokens.next() else {
+        return compiler_err!("Tried to parse a declaration without a keyword");
+    };
+
+    let (name, name_span) = match tokens.next() {
+        Some(Token(TokenKind::Identifier(name), span)) => (name.get_str(), *span),
+        Some(Token(.., span)) => return syntax_err!(codes::EXPECTED_IDENTIFIER, Some(*span), "Expected type name after {}", keyword.get_str()),
+        None => return syntax_err!(codes::EXPECTED_IDENTIFIER, Some(keyword_span.after()), "Expected type name after {}", keyword.get_str()),
+    };
+    // Patterns tell declared types apart from bindings by the capital letter
+    if !name.starts_with(char::is_uppercase) {
+        return Err(Diagnostic::new(DiagnosticKind::Syntax, codes::EXPECTED_IDENTIFIER, format!("Expected type name, found {}", name))
+            .with_label(name_span, "doesn't start with a capital letter")
+            .with_help("Type names start with a capital letter, like Person"));
+    }
+
+    match tokens.next() {
+        Some(Token(TokenKind::Colon, ..)) => Ok(Identifier::new(name, name_span)),
+        other => {
+            let span = other.map_or(name_span.after(), |Token(_, span)| *span);
+            syntax_err!(codes::EXPECTED_COLON, Some(span), "Expected colon after type name")
+        }
+    }
+}
+
+/// Splits the members after the colon of a declaration or construction. They are either on the same line,
+/// or each on their own line indented past `current_indent`. With `commas`, members can also share a line
+/// when they're separated by commas. Each member comes with the indentation of the line it's on.
+pub(super) fn split_members<'a>(tokens: &mut Tokens<'a>, current_indent: usize, commas: bool) -> Vec<(Tokens<'a>, usize)> {
+    let mut members = Vec::new();
+    let mut indent = current_indent;
+
+    loop {
+        while let Some(Token(TokenKind::Newline(next_indent), ..)) = tokens.peek() {
+            indent = *next_indent;
+            tokens.next();
+        }
+        if tokens.peek().is_none() {
+            return members;
+        }
+
+        let end = find_end_of_block(&mut tokens.clone(), indent + 1);
+        let mut line = tokens.take(end);
+        if !commas {
+            members.push((line, indent));
+            continue;
+        }
+
+        loop {
+            let length = find_next_comma(&mut line.clone());
+            // Skip empty members, so that a trailing comma is allowed
+            if length > 0 {
+                members.push((Tokens::take(&mut line, length), indent));
+            }
+            if line.next().is_none() {
+                break;
+            }
+        }
+    }
+}
+
+/// Returns the number of tokens before the next comma which isn't nested in brackets,
+/// or the number of tokens left if there is no such comma
+fn find_next_comma(tokens: &mut Tokens) -> usize {
+    let mut depth = 0;
+    let mut length = 0;
+    for token in tokens {
+        if token.0 == TokenKind::Comma && depth == 0 {
+            return length;
+        }
+        depth += token.0.nesting();
+        length += 1;
+    }
+
+    length
+}
+
+/// Parses a type, e.g. `int`, `[int]`, `{string: [float]}`, `(int, string)` or `Person`
+pub(super) fn parse_type(tokens: &mut Tokens) -> Result<Type> {
+    match tokens.next() {
+        Some(Token(TokenKind::LeftParen, open_span)) => {
+            let mut elements = Vec::new();
+            loop {
+                elements.push(parse_type(tokens)?);
+                match tokens.next() {
+                    Some(Token(TokenKind::Comma, ..)) => {}
+                    Some(Token(TokenKind::RightParen, ..)) => return Ok(Type::Tuple(elements)),
+                    _ => return syntax_err!(codes::UNCLOSED_DELIMITER, Some(*open_span), "Expected closing parenthesis"),
+                }
+            }
+        }
+        Some(Token(TokenKind::Identifier(ident), span)) => match Type::from_id(ident.get_str()) {
+            Some(typ) => Ok(typ),
+            None => Err(Diagnostic::new(DiagnosticKind::Syntax, codes::UNKNOWN_TYPE, format!("Unknown type {}", ident.get_str()))
+                .with_label(*span, "expected a type")),
+        },
+        Some(Token(TokenKind::LeftSqrBrace, open_span)) => {
+            let element = parse_type(tokens)?;
+            match tokens.next() {
+                Some(Token(TokenKind::RightSqrBrace, ..)) => Ok(Type::List(Box::new(element))),
+                _ => syntax_err!(codes::UNCLOSED_DELIMITER, Some(*open_span), "Expected closing square bracket"),
+            }
+        }
+        Some(Token(TokenKind::LeftBrace, open_span)) => {
+            let key = parse_type(tokens)?;
+            match tokens.next() {
+                Some(Token(TokenKind::Colon, ..)) => {}
+                other => {
+                    let span = other.map_or(open_span.after(), |Token(_, span)| *span);
+                    return syntax_err!(codes::EXPECTED_COLON, Some(span), "Expected colon between key and value types");
+                }
+            }
+            let value = parse_type(tokens)?;
+            match tokens.next() {
+                Some(Token(TokenKind::RightBrace, ..)) => Ok(Type::Dict(Box::new(key), Box::new(value))),
+                _ => syntax_err!(codes::UNCLOSED_DELIMITER, Some(*open_span), "Expected closing brace"),
+            }
+        }
+        Some(Token(.., span)) => syntax_err!(codes::UNKNOWN_TYPE, Some(*span), "Expected a type"),
+        None => syntax_err!(codes::UNKNOWN_TYPE, None, "Expected a type"),
+    }
+}
@@ -0,0 +1,128 @@
+use crate::constructs::token::Token;
+use crate::constructs::token::span::Span;
+use crate::constructs::token::trivia::{LosslessToken, LosslessTokens, Trivia, TriviaKind};
+use crate::prelude::*;
+
+use super::{parse_tokens_with_options, TokenizeOptions};
+
+/// Tokenizes like [`parse_tokens_with_options`](super::parse_tokens_with_options), but also keeps the 
+/// comments and whitespace between tokens, for tools like formatters which need to reproduce the source.
+pub fn parse_tokens_lossless(mlg_str: &str, options: &TokenizeOptions) -> Result<LosslessTokens> {
+    let mut tokens = Vec::new();
+    let mut position = 0;
+
+    for token in parse_tokens_with_options(mlg_str, options)? {
+        let Token(_, span) = &token;
+        if span.beginning() < position {
+            return compiler_err!("Token {:?} overlaps the token before it", token);
+        }
+
+        let leading_trivia = find_trivia(mlg_str, position, span.beginning());
+        position = span.beginning() + span.length();
+        tokens.push(LosslessToken { leading_trivia, token });
+    }
+
+    Ok(LosslessTokens {
+        tokens,
+        trailing_trivia: find_trivia(mlg_str, position, mlg_str.len()),
+    })
+}
+
+/// Splits the source between two tokens into trivia
+fn find_trivia(mlg_str: &str, start: usize, end: usize) -> Vec<Trivia> {
+    let mut trivia = Vec::new();
+    let mut index = start;
+
+    while index < end {
+        let rest = &mlg_str[index..end];
+        // Line comments stop before a \r\n or \n line ending
+        let line_comment_len = || rest.find('\n').map_or(rest.len(), |idx| rest[..idx].trim_end_matches('\r').len());
+
+        let (kind, len) = if rest.starts_with("///") {
+            (TriviaKind::DocComment, line_comment_len())
+        } else if rest.starts_with("//") {
+            (TriviaKind::LineComment, line_comment_len())
+        } else if let Some(comment) = rest.strip_prefix("/*") {
+            (TriviaKind::BlockComment, comment.find("*/").map_or(rest.len(), |idx| idx + 4))
+        } else if rest.starts_with('\n') {
+            (TriviaKind::Newline, 1)
+        } else {
+            // Anything else the tokenizer skipped over is whitespace
+            let len = rest.find(['\n', '/']).filter(|&len| len > 0).unwrap_or(rest.len());
+            (TriviaKind::Whitespace, len)
+        };
+
+        trivia.push(Trivia { kind, span: Span { index: index as u32, len: len as u32 } });
+        index += len;
+    }
+
+    trivia
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tokenize::Indentation;
+
+    /// Tokenizes the source, checking that the tokens and trivia cover every byte of it in order
+    fn round_trip(source: &str, options: &TokenizeOptions) -> LosslessTokens {
+        let lossless = parse_tokens_lossless(source, options).expect("The source should tokenize");
+
+        let spans = lossless.tokens.iter()
+            .flat_map(|token| token.leading_trivia.iter().map(|trivia| trivia.span).chain([token.token.1]))
+            .chain(lossless.trailing_trivia.iter().map(|trivia| trivia.span));
+        let mut position = 0;
+        for span in spans {
+            assert_eq!(span.beginning(), position, "Expected the span {:?} to start where the one before it ended", span);
+            position = span.beginning() + span.length();
+        }
+        assert_eq!(position, source.len());
+
+        assert_eq!(lossless.to_source(source), source);
+        lossless
+    }
+
+    fn trivia_kinds(lossless: &LosslessTokens) -> Vec<TriviaKind> {
+        lossless.tokens.iter()
+            .flat_map(|token| &token.leading_trivia)
+            .chain(&lossless.trailing_trivia)
+            .map(|trivia| trivia.kind)
+            .filter(|kind| !matches!(kind, TriviaKind::Whitespace | TriviaKind::Newline))
+            .collect()
+    }
+
+    #[test]
+    fn line_endings_round_trip() {
+        let source = "let a = 1\nlet f = | x :\n  x + a\n";
+        round_trip(source, &TokenizeOptions::default());
+        round_trip(&source.replace('\n', "\r\n"), &TokenizeOptions::default());
+    }
+
+    #[test]
+    fn comments_round_trip() {
+        let source = "// A line comment\nlet a = 1 // After a token\n/* A block\n   comment */ let b = 2\n/// A doc comment\nlet c = 3 /* trailing */";
+        let lossless = round_trip(source, &TokenizeOptions::default());
+        assert_eq!(trivia_kinds(&lossless), [
+            TriviaKind::LineComment,
+            TriviaKind::LineComment,
+            TriviaKind::BlockComment,
+            TriviaKind::DocComment,
+            TriviaKind::BlockComment,
+        ]);
+
+        round_trip(&source.replace('\n', "\r\n"), &TokenizeOptions::default());
+    }
+
+    #[test]
+    fn strings_round_trip() {
+        let source = "let raw = r#\"C:\\Users \"quoted\"\"#\nlet poem = \"\"\"\n    Roses are red,\n      violets are blue\n    \"\"\"\nlet sum = \"{raw} and {1 + 2} {{braces}}\"\n";
+        round_trip(source, &TokenizeOptions::default());
+        round_trip(&source.replace('\n', "\r\n"), &TokenizeOptions::default());
+    }
+
+    #[test]
+    fn tabs_and_unicode_round_trip() {
+        let source = "let größe = 3\nlet f = | x :\n\tx + größe // ünïcödé\n";
+        round_trip(source, &TokenizeOptions { indentation: Indentation::TabWidth(4) });
+    }
+}
@@ -1,30 +1,75 @@
 use crate::constructs::token::span::Span;
 use crate::constructs::token::symbol::{Symbol, builtin_symbols};
 use crate::prelude::*;
-use crate::constructs::token::{TokenKind, LiteralKind, Literal, Token};
+use crate::constructs::token::{TokenKind, LiteralKind, Literal, Token, StringPart};
+use unicode_normalization::UnicodeNormalization;
+
+mod lossless;
+pub use lossless::parse_tokens_lossless;
 
 enum Parsing {
     Tokens,
     String,
+    /// A string without escapes, closed by a quote and this many hashes, e.g. `r#"..."#`
+    RawString(usize),
+    /// A string spanning several lines, closed by `"""`
+    MultilineString,
     Number,
     SingleLineComment,
     MultilineComment,
     Symbol,
 }
 
+/// How lines may be indented
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Indentation {
+    /// Lines are indented with spaces, and a tab in indentation is an error
+    #[default]
+    Spaces,
+    /// Tabs are allowed, and move to the next multiple of this many columns
+    TabWidth(usize),
+    /// Lines may be indented with tabs or spaces, but a file can't use both. Each tab counts as one column
+    Consistent,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct TokenizeOptions {
+    pub indentation: Indentation,
+}
+
 pub fn parse_tokens(mlg_str: &str) -> Result<Vec<Token>> {
-    let mut tokens = Vec::new();
-    let mut chars = mlg_str.chars().peekable();
+    parse_tokens_with_options(mlg_str, &TokenizeOptions::default())
+}
 
+pub fn parse_tokens_with_options(mlg_str: &str, options: &TokenizeOptions) -> Result<Vec<Token>> {
+    if u32::try_from(mlg_str.len()).is_err() {
+        return syntax_err!(codes::SOURCE_TOO_LARGE, None, "Source is {} bytes long, but at most {} bytes are supported", mlg_str.len(), u32::MAX);
+    }
+
+    let mut tokens = Vec::new();
     let mut indent = 0;
 
     let mut prev_two_chars = (None, None);
+    // byte index of the start of the current line
+    let mut line_start: u32 = 0;
+    // the first character used to indent a line, and where, for Indentation::Consistent
+    let mut indent_style: Option<(char, Span)> = None;
 
     // buffer for building strings, numbers, etc.
     let mut buf: String = String::new();
+    // byte index of the first character of the string, number, etc.
+    let mut token_start: u32 = 0;
+    // byte index of the first character inside the quotes of a string
+    let mut content_start: u32 = 0;
+    // whether the previous character in a string was an unescaped backslash
+    let mut escaped = false;
+    // whether the previous character in a string was the u of a \u{...} escape
+    let mut unicode_escape = false;
+    // number of upcoming characters already handled by looking ahead, e.g. the quotes of `"""`
+    let mut skip = 0;
     let mut currently_parsing = Parsing::Tokens;
 
-    for (index, c) in chars.by_ref().enumerate() {
+    for (index, c) in mlg_str.char_indices() {
         let index = index as u32;
         macro_rules! push_token {
             ($kind:expr) => {
@@ -35,46 +80,81 @@ pub fn parse_tokens(mlg_str: &str) -> Result<Vec<Token>> {
             };
         }
         indent += 1;
+        if let (Some('\n'), _) = prev_two_chars {
+            line_start = index;
+        }
+        if skip > 0 {
+            skip -= 1;
+            prev_two_chars = (Some(c), prev_two_chars.0);
+            continue;
+        }
         match currently_parsing {
-            Parsing::String => {
-                if '"' == c {
+            Parsing::String | Parsing::RawString(_) | Parsing::MultilineString => {
+                if '\n' == c {
+                    indent = 0;
+                }
+
+                let is_end = match currently_parsing {
+                    Parsing::RawString(hashes) => '"' == c && mlg_str[index as usize + 1..].bytes().take_while(|&b| b == b'#').count() >= hashes,
+                    Parsing::MultilineString => '"' == c && !escaped && mlg_str[index as usize..].starts_with("\"\"\""),
+                    _ => '"' == c && !escaped,
+                };
+                let was_escaped = escaped;
+                escaped = !escaped && '\\' == c;
+
+                // Skip over interpolated expressions, so quotes inside them don't end the string
+                if '{' == c && !was_escaped && !unicode_escape && !matches!(currently_parsing, Parsing::RawString(_)) {
+                    let rest = &mlg_str[index as usize + 1..];
+                    skip = if rest.starts_with('{') {
+                        1
+                    } else {
+                        find_interpolation_end(rest).map_or(0, |len| len + 1)
+                    };
+                }
+                unicode_escape = was_escaped && 'u' == c;
+
+                if is_end {
+                    let content = &mlg_str[content_start as usize..index as usize];
+                    let (kind, end) = match currently_parsing {
+                        Parsing::RawString(hashes) => (string_token(vec![StringPart::Text(content.to_string())]), index + 1 + hashes as u32),
+                        Parsing::MultilineString => (string_token(multiline_string(content, content_start, options)?), index + 3),
+                        _ => {
+                            let mut parts = Vec::new();
+                            push_string_parts(content, content_start, options, &mut parts)?;
+                            (string_token(parts), index + 1)
+                        }
+                    };
+                    skip = (end - index - 1) as usize;
+
                     tokens.push(Token(
-                        TokenKind::Literal(Literal {
-                            kind: LiteralKind::String,
-                            symbol: Symbol::from(buf.as_str())
-                        }), 
+                        kind,
                         Span {
-                            index: index.saturating_sub(buf.len() as u32 + 1),
-                            len: buf.len() as u16 + 2 // +2 to include the "quotation marks"
+                            index: token_start,
+                            len: (end - token_start) // include the "quotation marks"
                         }
                     ));
-                    buf.clear();
                     // We need to do this manually because we're not using the loop
                     prev_two_chars = (Some('"'), prev_two_chars.0);
                     currently_parsing = Parsing::Tokens;
                     continue;
-                } else {
-                    match (buf.chars().last(), c) {
-                        (Some('\\'), 'n') => {
-                            buf.pop();
-                            buf.push('\n')
-                        },
-                        _ => buf.push(c)
-                    }
                 }
             }
             Parsing::Number => {
-                if c.is_numeric() {
+                let is_decimal = !matches!(buf.get(..2), Some("0x" | "0X" | "0o" | "0O" | "0b" | "0B"));
+                // Only take a dot if a digit follows it, so that `0..10` stays a range
+                let is_fraction = '.' == c && is_decimal && !buf.contains(['.', 'e', 'E'])
+                    && mlg_str.as_bytes().get(index as usize + 1).is_some_and(u8::is_ascii_digit);
+                let is_exponent_sign = matches!(c, '+' | '-') && is_decimal && buf.ends_with(['e', 'E']);
+
+                // Letters are taken too, so that malformed numbers like `12ab` give a clear error
+                if c.is_alphanumeric() || '_' == c || is_fraction || is_exponent_sign {
                     buf.push(c);
                 } else {
                     tokens.push(Token(
-                        TokenKind::Literal(Literal {
-                            kind: if buf.contains('.') { LiteralKind::Float } else { LiteralKind::Int },
-                            symbol: Symbol::from(buf.as_str())
-                        }),
+                        number_literal(&buf, token_start)?,
                         Span {
-                            index: index - buf.len() as u32,
-                            len: buf.len() as u16
+                            index: token_start,
+                            len: (index - token_start)
                         }
                     ));
                     buf.clear();
@@ -82,23 +162,39 @@ pub fn parse_tokens(mlg_str: &str) -> Result<Vec<Token>> {
                 }
             }
             Parsing::Symbol => {
-                if c.is_alphanumeric() || '_' == c {
+                if unicode_ident::is_xid_continue(c) {
                     buf.push(c);
+                } else if buf == "r" && matches!(c, '"' | '#') && mlg_str[index as usize..].trim_start_matches('#').starts_with('"') {
+                    // A raw string, e.g. r"C:\path" or r#"say "hi""#
+                    let hashes = mlg_str[index as usize..].bytes().take_while(|&b| b == b'#').count();
+                    skip = hashes;
+                    content_start = index + hashes as u32 + 1;
+                    buf.clear();
+                    currently_parsing = Parsing::RawString(hashes);
+                    prev_two_chars = (Some(c), prev_two_chars.0);
+                    continue;
                 } else {
-                    push_token!(get_kind_from_symbol_string(&buf));
+                    tokens.push(Token(
+                        get_kind_from_symbol_string(&buf),
+                        Span {
+                            index: token_start,
+                            len: (index - token_start)
+                        }
+                    ));
                     buf.clear();
                     currently_parsing = Parsing::Tokens;
                 }
             }
-            Parsing::SingleLineComment => {
-                if '\n' == c {
-                    currently_parsing = Parsing::Tokens;
-                }
+            Parsing::SingleLineComment if '\n' == c => {
+                currently_parsing = Parsing::Tokens;
             }
-            Parsing::MultilineComment => {
-                if '/' == c {
-                    if let (Some('*'), _) = prev_two_chars {
+            Parsing::MultilineComment if '/' == c => {
+                // token_start is the opening slash, so /*/ doesn't close itself
+                if let (Some('*'), _) = prev_two_chars {
+                    if index >= token_start + 3 {
                         currently_parsing = Parsing::Tokens;
+                        prev_two_chars = (Some(c), prev_two_chars.0);
+                        continue;
                     }
                 }
             }
@@ -110,6 +206,8 @@ pub fn parse_tokens(mlg_str: &str) -> Result<Vec<Token>> {
                 ')' => push_token!(TokenKind::RightParen),
                 '[' => push_token!(TokenKind::LeftSqrBrace),
                 ']' => push_token!(TokenKind::RightSqrBrace),
+                '{' => push_token!(TokenKind::LeftBrace),
+                '}' => push_token!(TokenKind::RightBrace),
                 ',' => push_token!(TokenKind::Comma),
                 '-' => push_token!(TokenKind::Minus),
                 '+' => push_token!(TokenKind::Plus),
@@ -158,6 +256,7 @@ pub fn parse_tokens(mlg_str: &str) -> Result<Vec<Token>> {
                         push_token!(TokenKind::TripleBar, 3)
                     },
                     (Some('|'), _) => {
+                        tokens.pop();
                         push_token!(TokenKind::DoubleBar, 2)
                     }
                     _ => push_token!(TokenKind::Bar(indent - 1))
@@ -179,49 +278,70 @@ pub fn parse_tokens(mlg_str: &str) -> Result<Vec<Token>> {
                     }
                 }
                 '*' => {
-                    if let (Some('/'), _) = prev_two_chars {
+                    if let Some(Token(TokenKind::Slash, span)) = tokens.last().filter(|Token(_, span)| span.index + 1 == index) {
+                        token_start = span.index;
+                        tokens.pop();
                         currently_parsing = Parsing::MultilineComment;
                     } else {
                         push_token!(TokenKind::Star)
                     }
                 }
                 '"' => {
-                    currently_parsing = Parsing::String;
-                    buf.clear();
+                    token_start = index;
+                    escaped = false;
+                    unicode_escape = false;
+                    if mlg_str[index as usize..].starts_with("\"\"\"") {
+                        currently_parsing = Parsing::MultilineString;
+                        content_start = index + 3;
+                        skip = 2;
+                    } else {
+                        currently_parsing = Parsing::String;
+                        content_start = index + 1;
+                    }
                 }
                 '0'..='9' => {
                     currently_parsing = Parsing::Number;
+                    token_start = index;
                     buf.clear();
                     buf.push(c);
                 }
-                'a'..='z' | 'A'..='Z' | '_' => {
+                // Identifiers follow UAX #31, e.g. `größe` or `_count`
+                c if '_' == c || unicode_ident::is_xid_start(c) => {
                     currently_parsing = Parsing::Symbol;
+                    token_start = index;
                     buf.clear();
                     buf.push(c);
                 }
                 '/' => {
-                    if let (Some('/'), _) = prev_two_chars {
+                    if let Some(Token(TokenKind::Slash, ..)) = tokens.last().filter(|Token(_, span)| span.index + 1 == index) {
+                        tokens.pop();
                         currently_parsing = Parsing::SingleLineComment;
                     } else {
                         push_token!(TokenKind::Slash)
                     }
                 }
-                ' ' => {
-                    if let Some(Token(TokenKind::Newline(_), Span{index: newline_start, ..})) = tokens.last() {
-                        let newline_start = *newline_start;
+                ' ' | '\t' => {
+                    let in_indentation = mlg_str[line_start as usize..index as usize].bytes().all(|b| b == b' ' || b == b'\t');
+                    if in_indentation {
+                        check_indentation(mlg_str, index, c, options.indentation, &mut indent_style)?;
+                    }
+                    if let ('\t', Indentation::TabWidth(width)) = (c, options.indentation) {
+                        let width = width.max(1);
+                        indent = (indent - 1) / width * width + width;
+                    }
+
+                    if let Some(Token(TokenKind::Newline(_), span)) = tokens.last() {
+                        // The newline's span covers the indentation directly after the line break.
+                        // Spaces after a comment on the same line are left out, so that spans never overlap comments.
+                        let span = if span.index + span.len == index {
+                            Span { index: span.index, len: span.len + 1 }
+                        } else {
+                            *span
+                        };
                         tokens.pop();
-                        tokens.push(Token(
-                            TokenKind::Newline(indent - 1), 
-                            Span {
-                                index: newline_start, 
-                                len: (index - newline_start) as u16
-                            }
-                        ));
+                        tokens.push(Token(TokenKind::Newline(indent), span));
                     }
                 }
-                '\t' => {
-                    return syntax_err!(Some(Span::unit(index)), "Tabs are currently not allowed. Use spaces instead.");
-                }
                 '\r' => {}
                 '\n' => {
                     if let Some(Token(TokenKind::Newline(_), ..)) = tokens.last() {
@@ -230,7 +350,10 @@ pub fn parse_tokens(mlg_str: &str) -> Result<Vec<Token>> {
                     push_token!(TokenKind::Newline(0), 0);
                     indent = 0;
                 }
-                _ => return syntax_err!(Some(Span::unit(index)), "Unexpected character: {}", c),
+                _ => return syntax_err!(codes::UNEXPECTED_CHARACTER, Some(Span {
+                    index,
+                    len: c.len_utf8() as u32
+                }), "Unexpected character: {}", c),
             }
         }
         
@@ -240,19 +363,16 @@ pub fn parse_tokens(mlg_str: &str) -> Result<Vec<Token>> {
     }
 
     match currently_parsing {
-        Parsing::String => return syntax_err!(Some(Span { 
-            index: (mlg_str.len().saturating_sub(buf.len() + 1)) as u32,
-            len: buf.len() as u16 + 1
-        }), "Unterminated string: {}", buf),
+        Parsing::String | Parsing::RawString(_) | Parsing::MultilineString => return syntax_err!(codes::UNTERMINATED_STRING, Some(Span { 
+            index: token_start,
+            len: (content_start - token_start)
+        }), "Unterminated string"),
         Parsing::Number => {
             tokens.push(Token(
-                TokenKind::Literal(Literal {
-                    kind: if buf.contains('.') { LiteralKind::Float } else { LiteralKind::Int },
-                    symbol: Symbol::from(buf.as_str())
-                }),
+                number_literal(&buf, token_start)?,
                 Span {
-                    index: (mlg_str.len() - buf.len()) as u32,
-                    len: buf.len() as u16
+                    index: token_start,
+                    len: (mlg_str.len() as u32 - token_start)
                 }
             ));
         },
@@ -260,8 +380,8 @@ pub fn parse_tokens(mlg_str: &str) -> Result<Vec<Token>> {
             tokens.push(Token(
                 get_kind_from_symbol_string(&buf),
                 Span {
-                    index: (mlg_str.len() - buf.len()) as u32,
-                    len: buf.len() as u16
+                    index: token_start,
+                    len: (mlg_str.len() as u32 - token_start)
                 }
             ));
         },
@@ -274,7 +394,7 @@ pub fn parse_tokens(mlg_str: &str) -> Result<Vec<Token>> {
     }
 
     if let Some(token) = tokens.iter().find(|tok| tok.0 == TokenKind::Amp) {
-        return syntax_err!(Some(token.1), "Ampersands are not currently supported!");
+        return syntax_err!(codes::UNSUPPORTED_TOKEN, Some(token.1), "Ampersands are not currently supported!");
     }
 
     Ok(tokens)
@@ -285,10 +405,565 @@ fn get_kind_from_symbol_string(buf: &str) -> TokenKind {
         "let" => TokenKind::Keyword(*builtin_symbols::LET),
         "struct" => TokenKind::Keyword(*builtin_symbols::STRUCT),
         "impl" => TokenKind::Keyword(*builtin_symbols::IMPL),
+        "type" => TokenKind::Keyword(*builtin_symbols::TYPE),
         "return" => TokenKind::Keyword(*builtin_symbols::RETURN),
         "true" => TokenKind::Literal(Literal {kind: LiteralKind::Bool, symbol: *builtin_symbols::TRUE}),
         "false" => TokenKind::Literal(Literal {kind: LiteralKind::Bool, symbol: *builtin_symbols::FALSE}),
         "yield" => TokenKind::Keyword(*builtin_symbols::YIELD),
-        _ => TokenKind::Identifier(Symbol::from(buf))
+        _ if buf.is_ascii() => TokenKind::Identifier(Symbol::from(buf)),
+        // Identifiers which look the same are the same, however their characters are encoded
+        _ => TokenKind::Identifier(Symbol::from(buf.nfc().collect::<String>().as_str()))
     }
-}
\ No newline at end of file
+}
+
+/// Checks a tab or space in the indentation of a line against the indentation policy
+fn check_indentation(mlg_str: &str, index: u32, c: char, indentation: Indentation, indent_style: &mut Option<(char, Span)>) -> Result<()> {
+    let span = Span::unit(index);
+    let name = |c| if '\t' == c { "tab" } else { "space" };
+
+    match indentation {
+        Indentation::Spaces if '\t' == c => {
+            Err(Diagnostic::new(DiagnosticKind::Syntax, codes::TAB_INDENTATION, "Tabs are not allowed in indentation".to_string())
+                .with_label(span, "tab found here")
+                .with_help("Use spaces instead, or allow tabs with a tab width"))
+        }
+        Indentation::Consistent => {
+            // Whitespace on a blank line doesn't count
+            let rest = mlg_str[index as usize..].trim_start_matches([' ', '\t', '\r']);
+            if rest.is_empty() || rest.starts_with('\n') {
+                return Ok(());
+            }
+
+            match *indent_style {
+                None => *indent_style = Some((c, span)),
+                Some((style, first)) if style != c => {
+                    return Err(Diagnostic::new(DiagnosticKind::Syntax, codes::MIXED_INDENTATION, "Indentation mixes tabs and spaces".to_string())
+                        .with_label(span, format!("{} found here", name(c)))
+                        .with_secondary_label(first, format!("indentation first uses a {} here", name(style)))
+                        .with_help("Indent every line with only tabs or only spaces"));
+                }
+                Some(_) => {}
+            }
+            Ok(())
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Checks that a number literal is well formed, and works out whether it is an int or a float.
+/// `buf` is the literal as written, e.g. `0x1F`, `1_000` or `6.02e-23`, and `start` is the index of its first byte.
+fn number_literal(buf: &str, start: u32) -> Result<TokenKind> {
+    let char_span = |offset: usize, c: char| Some(Span { index: start + offset as u32, len: c.len_utf8() as u32 });
+    let literal = |kind| Ok(TokenKind::Literal(Literal { kind, symbol: Symbol::from(buf) }));
+
+    let radix = match buf.get(..2) {
+        Some("0x" | "0X") => Some((16, "hexadecimal")),
+        Some("0o" | "0O") => Some((8, "octal")),
+        Some("0b" | "0B") => Some((2, "binary")),
+        _ => None,
+    };
+
+    // An underscore separates digits, so it must have a digit on either side
+    let misplaced_underscore = |offset: usize, radix: u32| {
+        let is_digit = |c: Option<char>| c.is_some_and(|c| c.is_digit(radix));
+        !is_digit(buf[..offset].chars().next_back()) || !is_digit(buf[offset + 1..].chars().next())
+    };
+    let underscore_err = |offset: usize| syntax_err!(codes::INVALID_LITERAL, char_span(offset, '_'), "Misplaced _ in number literal {}", buf);
+
+    if let Some((radix, name)) = radix {
+        for (offset, c) in buf.char_indices().skip(2) {
+            if '_' == c {
+                if offset == 2 || misplaced_underscore(offset, radix) {
+                    return underscore_err(offset);
+                }
+            } else if !c.is_digit(radix) {
+                return syntax_err!(codes::INVALID_LITERAL, char_span(offset, c), "Invalid character '{}' in {} literal {}", c, name, buf);
+            }
+        }
+
+        if !buf[2..].chars().any(|c| c.is_digit(radix)) {
+            return syntax_err!(codes::INVALID_LITERAL, Some(Span { index: start, len: buf.len() as u32 }), "Expected digits after {}", &buf[..2]);
+        }
+
+        return literal(LiteralKind::Int);
+    }
+
+    let mut kind = LiteralKind::Int;
+    let mut in_exponent = false;
+    let mut exponent_has_digits = false;
+    let mut prev = None;
+
+    for (offset, c) in buf.char_indices() {
+        match c {
+            '0'..='9' => exponent_has_digits |= in_exponent,
+            '_' if misplaced_underscore(offset, 10) => return underscore_err(offset),
+            '_' => {}
+            // The tokenizer only takes a dot when it is the first one, and a digit follows it
+            '.' => kind = LiteralKind::Float,
+            'e' | 'E' if !in_exponent => {
+                in_exponent = true;
+                kind = LiteralKind::Float;
+            }
+            '+' | '-' if matches!(prev, Some('e' | 'E')) => {}
+            _ => return syntax_err!(codes::INVALID_LITERAL, char_span(offset, c), "Invalid character '{}' in number literal {}", c, buf),
+        }
+        prev = Some(c);
+    }
+
+    if in_exponent && !exponent_has_digits {
+        return syntax_err!(codes::INVALID_LITERAL, Some(Span { index: start, len: buf.len() as u32 }), "Expected digits in the exponent of {}", buf);
+    }
+
+    literal(kind)
+}
+
+/// Replaces the escape sequences in the contents of a string literal.
+/// `start` is the index of the first byte of `raw`, so that bad escapes can be pointed at.
+fn unescape(raw: &str, start: u32) -> Result<String> {
+    let mut string = String::with_capacity(raw.len());
+    let mut chars = raw.char_indices();
+
+    while let Some((offset, c)) = chars.next() {
+        if '\\' != c {
+            string.push(c);
+            continue;
+        }
+
+        let escape_span = |len: usize| Some(Span { index: start + offset as u32, len: len as u32 });
+        match chars.next() {
+            Some((_, 'n')) => string.push('\n'),
+            Some((_, 't')) => string.push('\t'),
+            Some((_, 'r')) => string.push('\r'),
+            Some((_, '0')) => string.push('\0'),
+            Some((_, '"')) => string.push('"'),
+            Some((_, '\\')) => string.push('\\'),
+            Some((_, 'u')) => {
+                // e.g. \u{1F600}
+                let Some(digits) = raw[offset + 2..].strip_prefix('{').and_then(|rest| rest.split_once('}')).map(|(digits, _)| digits) else {
+                    return syntax_err!(codes::INVALID_ESCAPE, escape_span(2), "Expected {{ after \\u");
+                };
+
+                let chr = Some(digits)
+                    .filter(|digits| (1..=6).contains(&digits.len()) && digits.chars().all(|c| c.is_ascii_hexdigit()))
+                    .and_then(|digits| u32::from_str_radix(digits, 16).ok())
+                    .and_then(char::from_u32);
+                let Some(chr) = chr else {
+                    return Err(Diagnostic::new(DiagnosticKind::Syntax, codes::INVALID_ESCAPE, format!("Invalid unicode escape \\u{{{}}}", digits))
+                        .with_span(escape_span(digits.len() + 4))
+                        .with_help("Unicode escapes take 1 to 6 hexadecimal digits, which must make a valid code point, e.g. \\u{1F600}"));
+                };
+
+                string.push(chr);
+                // Skip the braces and digits
+                chars.nth(digits.len() + 1);
+            }
+            Some((_, other)) => {
+                return Err(Diagnostic::new(DiagnosticKind::Syntax, codes::INVALID_ESCAPE, format!("Unknown escape sequence \\{}", other))
+                    .with_span(escape_span(1 + other.len_utf8()))
+                    .with_help(r#"The escape sequences are \" \\ \n \t \r \0 and \u{...}. Use a raw string, e.g. r"\d", to avoid escapes"#));
+            }
+            None => return syntax_err!(codes::INVALID_ESCAPE, escape_span(1), "Expected escape sequence after \\"),
+        }
+    }
+
+    Ok(string)
+}
+
+/// Works out the value of a multi-line string from everything between its `"""`s, e.g.
+/// ```text
+/// let poem = """
+///     Roses are red,
+///       violets are blue
+///     """
+/// ```
+/// The text starts on the line after the opening quotes, and ends on the line before the closing quotes.
+/// The indentation of the closing quotes is removed from every line, so the string can be
+/// indented to fit the block it is in. Escape sequences work as in other strings.
+fn multiline_string(raw: &str, start: u32, options: &TokenizeOptions) -> Result<Vec<StringPart>> {
+    let Some((first_line, _)) = raw.split_once('\n').filter(|(first_line, _)| first_line.trim().is_empty()) else {
+        return syntax_err!(codes::INVALID_MULTILINE_STRING, Some(Span { index: start, len: 1 }), "Expected a new line after \"\"\"");
+    };
+
+    let body_start = first_line.len() + 1;
+    let (body, closing_line) = raw[body_start..].rsplit_once('\n').unwrap_or(("", &raw[body_start..]));
+    if !closing_line.chars().all(|c| c == ' ' || c == '\t' || c == '\r') {
+        return syntax_err!(codes::INVALID_MULTILINE_STRING, Some(Span { index: start + raw.len() as u32, len: 3 }), "The closing \"\"\" of a multi-line string must be on its own line");
+    }
+    let indent = closing_line.trim_end_matches('\r');
+
+    let mut parts = Vec::new();
+    let mut line_start = body_start;
+    for (idx, line) in body.split('\n').enumerate() {
+        if idx > 0 {
+            push_text(&mut parts, "\n");
+        }
+
+        let line_span = Some(Span { index: start + line_start as u32, len: line.len().max(1) as u32 });
+        let line_without_cr = line.trim_end_matches('\r');
+        line_start += line.len() + 1;
+
+        if line_without_cr.trim().is_empty() {
+            continue;
+        }
+        let Some(text) = line_without_cr.strip_prefix(indent) else {
+            return syntax_err!(codes::INVALID_MULTILINE_STRING, line_span, "Line is indented less than the closing \"\"\" of its multi-line string");
+        };
+
+        let text_start = start + (line_start - line.len() - 1 + indent.len()) as u32;
+        push_string_parts(text, text_start, options, &mut parts)?;
+    }
+
+    Ok(parts)
+}
+
+/// Makes the token for a string literal. Strings without interpolated expressions are plain literals.
+fn string_token(mut parts: Vec<StringPart>) -> TokenKind {
+    match parts.as_mut_slice() {
+        [] => TokenKind::Literal(Literal { kind: LiteralKind::String, symbol: Symbol::from("") }),
+        [StringPart::Text(text)] => TokenKind::Literal(Literal { kind: LiteralKind::String, symbol: Symbol::from(text.as_str()) }),
+        _ => TokenKind::InterpolatedString(parts),
+    }
+}
+
+fn push_text(parts: &mut Vec<StringPart>, text: &str) {
+    match parts.last_mut() {
+        Some(StringPart::Text(previous)) => previous.push_str(text),
+        _ if text.is_empty() => {}
+        _ => parts.push(StringPart::Text(text.to_string())),
+    }
+}
+
+/// Splits the contents of a string literal into text and interpolated expressions, e.g.
+/// `{num} squared is {num * num}`, replacing the escape sequences in the text.
+/// `{{` and `}}` stand for literal braces. `start` is the index of the first byte of `raw`, and the
+/// expressions are tokenized with the same `options` as the rest of the file.
+fn push_string_parts(raw: &str, start: u32, options: &TokenizeOptions, parts: &mut Vec<StringPart>) -> Result<()> {
+    let bytes = raw.as_bytes();
+    let mut text_start = 0;
+    let mut idx = 0;
+
+    while idx < raw.len() {
+        match (bytes[idx], bytes.get(idx + 1)) {
+            (b'\\', Some(b'u')) if bytes.get(idx + 2) == Some(&b'{') => {
+                // Leave unicode escapes to unescape
+                idx += raw[idx..].find('}').map_or(raw.len() - idx, |end| end + 1);
+            }
+            (b'\\', _) => idx += 2,
+            (b'{', Some(b'{')) | (b'}', Some(b'}')) => {
+                push_text(parts, &unescape(&raw[text_start..idx], start + text_start as u32)?);
+                push_text(parts, &raw[idx..idx + 1]);
+                idx += 2;
+                text_start = idx;
+            }
+            (b'}', _) => {
+                return Err(Diagnostic::new(DiagnosticKind::Syntax, codes::INVALID_INTERPOLATION, "Unmatched } in string".to_string())
+                    .with_span(Some(Span { index: start + idx as u32, len: 1 }))
+                    .with_help("Use }} for a literal }"));
+            }
+            (b'{', _) => {
+                push_text(parts, &unescape(&raw[text_start..idx], start + text_start as u32)?);
+
+                let brace_span = Span { index: start + idx as u32, len: 1 };
+                let Some(len) = find_interpolation_end(&raw[idx + 1..]) else {
+                    return Err(Diagnostic::new(DiagnosticKind::Syntax, codes::INVALID_INTERPOLATION, "Expected } to close the interpolated expression".to_string())
+                        .with_label(brace_span, "interpolation starts here")
+                        .with_help("Use {{ for a literal {"));
+                };
+
+                let expression = &raw[idx + 1..idx + 1 + len];
+                let expression_start = start + idx as u32 + 1;
+                if expression.trim().is_empty() {
+                    return syntax_err!(codes::INVALID_INTERPOLATION, Some(brace_span + Span { index: expression_start + len as u32, len: 1 }), "Expected expression inside {{}}");
+                }
+
+                // Leading whitespace isn't indentation, so leave it out
+                let expression_start = expression_start + (expression.len() - expression.trim_start().len()) as u32;
+                let mut tokens = parse_tokens_with_options(expression.trim_start(), options).map_err(|err| offset_diagnostic(err, expression_start))?;
+                offset_tokens(&mut tokens, expression_start);
+                parts.push(StringPart::Expression(tokens));
+
+                idx += len + 2;
+                text_start = idx;
+            }
+            _ => idx += 1,
+        }
+    }
+
+    push_text(parts, &unescape(&raw[text_start..], start + text_start as u32)?);
+    Ok(())
+}
+
+/// Returns the length of an interpolated expression, given the source just after its opening brace,
+/// or `None` if the expression is never closed. Braces inside strings in the expression are ignored.
+fn find_interpolation_end(src: &str) -> Option<usize> {
+    let mut depth = 0;
+    let mut in_string = false;
+    let mut escaped = false;
+
+    for (idx, c) in src.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' if in_string => escaped = true,
+            '"' => in_string = !in_string,
+            '{' if !in_string => depth += 1,
+            '}' if !in_string && depth == 0 => return Some(idx),
+            '}' if !in_string => depth -= 1,
+            '\n' => return None,
+            _ => {}
+        }
+    }
+
+    None
+}
+
+/// Moves the spans of tokens from an interpolated expression to where the expression is in the file
+fn offset_tokens(tokens: &mut [Token], offset: u32) {
+    for Token(kind, span) in tokens {
+        span.index += offset;
+        if let TokenKind::InterpolatedString(parts) = kind {
+            for part in parts {
+                if let StringPart::Expression(tokens) = part {
+                    offset_tokens(tokens, offset);
+                }
+            }
+        }
+    }
+}
+
+fn offset_diagnostic(mut diagnostic: Diagnostic, offset: u32) -> Diagnostic {
+    for label in diagnostic.primary.iter_mut().chain(diagnostic.secondary.iter_mut()) {
+        label.span.index += offset;
+    }
+    diagnostic
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::constructs::variable::Value;
+
+    fn kinds(source: &str) -> Vec<TokenKind> {
+        kinds_with(source, Indentation::Spaces)
+    }
+
+    fn kinds_with(source: &str, indentation: Indentation) -> Vec<TokenKind> {
+        let tokens = parse_tokens_with_options(source, &TokenizeOptions { indentation }).expect("The test's source should tokenize");
+        tokens.into_iter().map(|Token(kind, _)| kind).collect()
+    }
+
+    fn newlines(source: &str) -> Vec<usize> {
+        kinds(source).into_iter()
+            .filter_map(|kind| match kind {
+                TokenKind::Newline(indent) => Some(indent),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn newlines_hold_the_indentation_of_the_next_line() {
+        assert_eq!(newlines("a\nb"), [0]);
+        assert_eq!(newlines("a\n  b\n    c\nd"), [2, 4, 0]);
+        // Blank lines and comments in between don't count
+        assert_eq!(newlines("a\n\n    \n  b"), [2]);
+        assert_eq!(newlines("a // comment\n  b"), [2]);
+        // Trailing line breaks are dropped
+        assert_eq!(newlines("a\n  \n"), Vec::<usize>::new());
+    }
+
+    #[test]
+    fn newlines_line_up_with_bars() {
+        let kinds = kinds("let f = | 0 : 1\n        | n : n");
+        let bars: Vec<_> = kinds.iter().filter_map(|kind| match kind { TokenKind::Bar(column) => Some(*column), _ => None }).collect();
+        assert_eq!(bars, [8, 8]);
+        assert!(kinds.contains(&TokenKind::Newline(8)));
+    }
+
+    #[test]
+    fn tabs_in_indentation() {
+        assert_eq!(kinds_with("a\n\tb", Indentation::TabWidth(4))[1], TokenKind::Newline(4));
+        assert_eq!(kinds_with("a\n  \tb", Indentation::TabWidth(4))[1], TokenKind::Newline(4));
+        assert_eq!(kinds_with("a\n\t\tb", Indentation::Consistent)[1], TokenKind::Newline(2));
+    }
+
+    /// Describes the parts of the one string in `source`, writing each expression out as the
+    /// source its tokens' spans point at, so that the spans are checked too
+    fn string_parts(source: &str) -> Vec<String> {
+        let tokens = parse_tokens(source).expect("The test's source should tokenize");
+        let describe = |tokens: &[Token]| {
+            let pieces: Vec<_> = tokens.iter().map(|Token(_, span)| &source[span.beginning()..span.beginning() + span.length()]).collect();
+            format!("{{{}}}", pieces.join(" "))
+        };
+        match &tokens[0].0 {
+            TokenKind::InterpolatedString(parts) => parts.iter()
+                .map(|part| match part {
+                    StringPart::Text(text) => text.clone(),
+                    StringPart::Expression(tokens) => describe(tokens),
+                })
+                .collect(),
+            TokenKind::Literal(literal) => vec![literal.symbol.to_string()],
+            kind => panic!("Expected a string, got {:?}", kind),
+        }
+    }
+
+    #[test]
+    fn interpolation() {
+        assert_eq!(string_parts("\"{num} squared is {  num * num}\""), ["{num}", " squared is ", "{num * num}"]);
+        // Braces inside strings in the expression don't end it
+        assert_eq!(string_parts("\"a {\"}}\" + \"{b}\"} c\""), ["a ", "{\"}}\" + \"{b}\"}", " c"]);
+        assert_eq!(string_parts("\"{{x}} {{ }}\""), ["{x} { }"]);
+        assert_eq!(string_parts("\"{{{x}}}\""), ["{", "{x}", "}"]);
+    }
+
+    #[test]
+    fn errors_inside_interpolation_point_into_the_string() {
+        let source = "let s = \"a {  1e } b\"";
+        let error = parse_tokens(source).unwrap_err();
+        assert_eq!(error.code, codes::INVALID_LITERAL);
+        assert_eq!(error.span(), Some(Span { index: 14, len: 2 }));
+
+        let error = parse_tokens("\"{x\" + 1").unwrap_err();
+        assert_eq!(error.code, codes::INVALID_INTERPOLATION);
+    }
+
+    /// The value of the one number in `source`, written with Debug so that ints and floats differ,
+    /// or the error from tokenizing it or working out its value
+    fn number(source: &str) -> Result<String> {
+        match parse_tokens(source)?.as_slice() {
+            [Token(TokenKind::Literal(literal), span)] => Value::try_from((*literal, *span)).map(|value| format!("{:?}", value)),
+            tokens => panic!("Expected a single literal, got {:?}", tokens),
+        }
+    }
+
+    fn number_error(source: &str) -> (String, Option<Span>) {
+        let error = number(source).unwrap_err();
+        assert_eq!(error.code, codes::INVALID_LITERAL);
+        (error.message.clone(), error.span())
+    }
+
+    #[test]
+    fn numbers() {
+        assert_eq!(number("42").unwrap(), "Int(42)");
+        assert_eq!(number("1_000_000").unwrap(), "Int(1000000)");
+        assert_eq!(number("2.5").unwrap(), "Float(2.5)");
+        assert_eq!(number("1_0.2_5").unwrap(), "Float(10.25)");
+        assert_eq!(number("1e3").unwrap(), "Float(1000.0)");
+        assert_eq!(number("2.5E-2").unwrap(), "Float(0.025)");
+        assert_eq!(number("1e+2").unwrap(), "Float(100.0)");
+        assert_eq!(number("0xFF").unwrap(), "Int(255)");
+        assert_eq!(number("0x_").map_err(|error| error.code), Err(codes::INVALID_LITERAL));
+        assert_eq!(number("0o17").unwrap(), "Int(15)");
+        assert_eq!(number("0b1010_1010").unwrap(), "Int(170)");
+
+        assert_eq!(number_error("1e"), ("Expected digits in the exponent of 1e".to_string(), Some(Span { index: 0, len: 2 })));
+        assert_eq!(number_error("0b102").0, "Invalid character '2' in binary literal 0b102");
+        assert_eq!(number_error("0o8").0, "Invalid character '8' in octal literal 0o8");
+        assert_eq!(number_error("0xG").0, "Invalid character 'G' in hexadecimal literal 0xG");
+        assert_eq!(number_error("0x").0, "Expected digits after 0x");
+    }
+
+    #[test]
+    fn underscores_go_between_digits() {
+        assert_eq!(number_error("1_"), ("Misplaced _ in number literal 1_".to_string(), Some(Span { index: 1, len: 1 })));
+        assert_eq!(number_error("1__0").1, Some(Span { index: 1, len: 1 }));
+        assert_eq!(number_error("1_.5").1, Some(Span { index: 1, len: 1 }));
+        assert_eq!(number_error("1e_5").1, Some(Span { index: 2, len: 1 }));
+        assert_eq!(number_error("0x_FF").1, Some(Span { index: 2, len: 1 }));
+        assert_eq!(number_error("0b1__0").1, Some(Span { index: 3, len: 1 }));
+        assert_eq!(number_error("0o7_").1, Some(Span { index: 3, len: 1 }));
+    }
+
+    #[test]
+    fn int_overflow() {
+        assert_eq!(number(&isize::MAX.to_string()).unwrap(), format!("Int({})", isize::MAX));
+        let too_large = format!("{}0", isize::MAX);
+        assert_eq!(number_error(&too_large), (format!("Int literal {} is too large", too_large), Some(Span { index: 0, len: too_large.len() as u32 })));
+        assert_eq!(number_error("0x1_0000_0000_0000_0000").0, "Int literal 0x1_0000_0000_0000_0000 is too large");
+    }
+
+    fn string(source: &str) -> String {
+        string_parts(source).concat()
+    }
+
+    fn string_error(source: &str) -> (ErrorCode, Option<Span>) {
+        let error = parse_tokens(source).unwrap_err();
+        (error.code, error.span())
+    }
+
+    #[test]
+    fn escapes() {
+        assert_eq!(string(r#""a\tb\nc\r\0 \"q\" \\""#), "a\tb\nc\r\0 \"q\" \\");
+        assert_eq!(string(r#""\u{48}\u{1F600}""#), "H😀");
+        assert_eq!(string(r#""{{\u{7B}}}""#), "{{}");
+
+        assert_eq!(string_error(r#""ab\q""#), (codes::INVALID_ESCAPE, Some(Span { index: 3, len: 2 })));
+        assert_eq!(string_error(r#""\u41""#), (codes::INVALID_ESCAPE, Some(Span { index: 1, len: 2 })));
+        assert_eq!(string_error(r#""\u{110000}""#), (codes::INVALID_ESCAPE, Some(Span { index: 1, len: 10 })));
+        assert_eq!(string_error(r#""\u{}""#), (codes::INVALID_ESCAPE, Some(Span { index: 1, len: 4 })));
+    }
+
+    #[test]
+    fn raw_strings() {
+        assert_eq!(string(r#"r"\d+ {x}""#), r"\d+ {x}");
+        assert_eq!(string(r##"r#"say "hi""#"##), r#"say "hi""#);
+        assert_eq!(string(r###"r##"a "# b"##"###), r##"a "# b"##);
+        assert_eq!(kinds(r#"r"a" b"#).len(), 2);
+    }
+
+    #[test]
+    fn multi_line_strings() {
+        let source = "let poem = \"\"\"\n    Roses are red,\n\n      violets are blue\n    \"\"\"";
+        let TokenKind::Literal(literal) = &kinds(source)[3] else { panic!("Expected a string literal") };
+        assert_eq!(literal.symbol.to_string(), "Roses are red,\n\n  violets are blue");
+
+        // Escapes and interpolation work as in other strings, and CRLF line endings are dropped
+        assert_eq!(string("\"\"\"\r\n  a\\tb\r\n  {x}\r\n  \"\"\""), "a\tb\n{x}");
+        assert_eq!(string("\"\"\"\n\"\"\""), "");
+
+        assert_eq!(string_error("\"\"\"a\n\"\"\"").0, codes::INVALID_MULTILINE_STRING);
+        assert_eq!(string_error("\"\"\"\n  a\"\"\"").0, codes::INVALID_MULTILINE_STRING);
+        assert_eq!(string_error("\"\"\"\n  a\n b\n  \"\"\""), (codes::INVALID_MULTILINE_STRING, Some(Span { index: 8, len: 2 })));
+    }
+
+    fn identifiers(source: &str) -> Vec<String> {
+        kinds(source).into_iter()
+            .filter_map(|kind| match kind {
+                TokenKind::Identifier(symbol) => Some(symbol.to_string()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn unicode_identifiers() {
+        assert_eq!(identifiers("let größe = _count + 名前"), ["größe", "_count", "名前"]);
+        assert_eq!(identifiers("x·y δ2"), ["x·y", "δ2"]);
+        // `é` written as one character or as `e` and a combining accent is the same identifier
+        assert_eq!(identifiers("caf\u{e9} caf\u{65}\u{301}"), ["café", "café"]);
+
+        let tokens = parse_tokens("größe println").unwrap();
+        assert_eq!(tokens[0].1, Span { index: 0, len: 7 });
+        assert_eq!(tokens[1].1, Span { index: 8, len: 7 });
+
+        // Characters which can't start an identifier are still errors
+        assert!(parse_tokens("€").is_err());
+        assert!(parse_tokens("\u{301}a").is_err());
+    }
+
+    #[test]
+    fn tab_indentation_policies() {
+        let options = |indentation| TokenizeOptions { indentation };
+        let error = parse_tokens("a\n  \tb").unwrap_err();
+        assert_eq!((error.code, error.span()), (codes::TAB_INDENTATION, Some(Span { index: 4, len: 1 })));
+        // Tabs after the indentation are only whitespace
+        assert_eq!(kinds("a\tb"), kinds("a b"));
+
+        assert_eq!(kinds_with("a\n\t\tb\n\tc", Indentation::TabWidth(2)), kinds("a\n    b\n  c"));
+        assert_eq!(kinds_with("a\n \tb", Indentation::TabWidth(8))[1], TokenKind::Newline(8));
+
+        let error = parse_tokens_with_options("a\n\tb\n  c", &options(Indentation::Consistent)).unwrap_err();
+        assert_eq!((error.code, error.span()), (codes::MIXED_INDENTATION, Some(Span { index: 5, len: 1 })));
+        assert_eq!(error.secondary[0].span, Span { index: 2, len: 1 });
+        // Whitespace on blank lines doesn't decide the style
+        assert!(parse_tokens_with_options("a\n  \n\tb\n\tc", &options(Indentation::Consistent)).is_ok());
+    }
+}
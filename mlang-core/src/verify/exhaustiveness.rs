@@ -0,0 +1,560 @@
+use std::collections::HashMap;
+use std::fmt::Display;
+
+use crate::prelude::*;
+use crate::constructs::ast::*;
+use crate::constructs::variable::{Type, TypeBody, Value};
+use super::VerifyOptions;
+
+/// What kind of value something is, before looking inside it. Values with different heads
+/// are never built by the same constructor.
+#[derive(Debug, Clone, PartialEq)]
+enum Head {
+    Int,
+    Float,
+    String,
+    Bool,
+    Tuple(usize),
+    Named(String),
+    /// Any other type, which patterns can only test for as a whole, like `[int]`
+    Other(Type),
+}
+
+impl Head {
+    fn of_type(typ: &Type) -> Head {
+        match typ {
+            Type::Int => Head::Int,
+            Type::Float => Head::Float,
+            Type::String => Head::String,
+            Type::Boolean => Head::Bool,
+            Type::Named(name) => Head::Named(name.clone()),
+            typ => Head::Other(typ.clone()),
+        }
+    }
+}
+
+impl Display for Head {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Head::Int => write!(f, "int"),
+            Head::Float => write!(f, "float"),
+            Head::String => write!(f, "string"),
+            Head::Bool => write!(f, "bool"),
+            Head::Tuple(length) => write!(f, "({})", vec!["_"; *length].join(", ")),
+            Head::Named(name) => write!(f, "{}", name),
+            Head::Other(typ) => write!(f, "{}", typ),
+        }
+    }
+}
+
+/// One way of building a value, which patterns can take apart
+#[derive(Debug, Clone, PartialEq)]
+enum Constructor {
+    /// A literal or a range, told apart by how it's written. Types with literals have too many
+    /// values to list, so these are never the only way of building a value.
+    Value(Head, String),
+    Bool(bool),
+    Tuple(usize),
+    /// A variant of an enum type, with the names of the type and the variant
+    Variant(String, String),
+}
+
+impl Constructor {
+    fn head(&self) -> Head {
+        match self {
+            Constructor::Value(head, _) => head.clone(),
+            Constructor::Bool(_) => Head::Bool,
+            Constructor::Tuple(length) => Head::Tuple(*length),
+            Constructor::Variant(typ, _) => Head::Named(typ.clone()),
+        }
+    }
+}
+
+/// A pattern, reduced to which values it matches
+#[derive(Debug, Clone)]
+enum Pat {
+    /// Matches any value
+    Wild,
+    /// Matches any value with the head, like `int num`
+    Type(Head),
+    /// Matches values built by the constructor, with one pattern per argument of the constructor
+    Constructor(Constructor, Vec<Pat>),
+    Or(Vec<Pat>),
+}
+
+impl Pat {
+    fn head(&self) -> Option<Head> {
+        match self {
+            Pat::Type(head) => Some(head.clone()),
+            Pat::Constructor(constructor, _) => Some(constructor.head()),
+            Pat::Wild | Pat::Or(_) => None,
+        }
+    }
+}
+
+fn lower(kind: &PatternKind) -> Pat {
+    match kind {
+        PatternKind::Empty | PatternKind::Wildcard | PatternKind::Binding { typ: None, .. } => Pat::Wild,
+        PatternKind::Binding { typ: Some(typ), .. } => lower_type(typ),
+        PatternKind::Tuple(elements) => Pat::Constructor(Constructor::Tuple(elements.len()), elements.iter().map(lower).collect()),
+        PatternKind::Literal(Value::Boolean(boolean)) => Pat::Constructor(Constructor::Bool(*boolean), Vec::new()),
+        PatternKind::Literal(value) => {
+            let head = Head::of_type(&value.get_type());
+            Pat::Constructor(Constructor::Value(head, format!("{:?}", value)), Vec::new())
+        }
+        // A range that's empty, like `10..1`, matches nothing, as does an or-pattern with no alternatives
+        PatternKind::Range { start, end, inclusive } if is_empty_range(start, end, *inclusive) => Pat::Or(Vec::new()),
+        PatternKind::Range { start, end, inclusive } => {
+            let head = Head::of_type(&start.get_type());
+            let written = format!("{:?}..{}{:?}", start, if *inclusive { "=" } else { "" }, end);
+            Pat::Constructor(Constructor::Value(head, written), Vec::new())
+        }
+        PatternKind::Or(alternatives) => Pat::Or(alternatives.iter().map(lower).collect()),
+        PatternKind::Variant { typ, variant, payload } => Pat::Constructor(
+            Constructor::Variant(typ.name.clone(), variant.name.clone()),
+            payload.iter().map(lower).collect(),
+        ),
+    }
+}
+
+fn is_empty_range(start: &Value, end: &Value, inclusive: bool) -> bool {
+    let order = match (start, end) {
+        (Value::Int(start), Value::Int(end)) => start.partial_cmp(end),
+        (Value::Float(start), Value::Float(end)) => start.partial_cmp(end),
+        (Value::String(start), Value::String(end)) => start.partial_cmp(end),
+        _ => return false,
+    };
+    match order {
+        Some(std::cmp::Ordering::Less) => false,
+        Some(std::cmp::Ordering::Equal) => !inclusive,
+        _ => true,
+    }
+}
+
+/// The first range in the pattern that's empty, as written
+fn find_empty_range(kind: &PatternKind) -> Option<String> {
+    match kind {
+        PatternKind::Range { start, end, inclusive } if is_empty_range(start, end, *inclusive) => {
+            let written = |value: &Value| match value {
+                Value::Float(float) => format!("{:?}", float),
+                Value::String(string) => format!("{:?}", string),
+                value => value.to_string(),
+            };
+            Some(format!("{}..{}{}", written(start), if *inclusive { "=" } else { "" }, written(end)))
+        }
+        PatternKind::Tuple(patterns) | PatternKind::Or(patterns) | PatternKind::Variant { payload: patterns, .. } => patterns.iter().find_map(find_empty_range),
+        _ => None,
+    }
+}
+
+/// Turns the type of a binding into a pattern. A tuple type tests the type of each element.
+fn lower_type(typ: &Type) -> Pat {
+    match typ {
+        Type::Tuple(types) if !types.is_empty() => Pat::Constructor(Constructor::Tuple(types.len()), types.iter().map(lower_type).collect()),
+        typ => Pat::Type(Head::of_type(typ)),
+    }
+}
+
+/// An example of values which no arm matches
+#[derive(Debug, Clone)]
+enum Witness {
+    Wild,
+    Type(Head),
+    Constructor(Constructor, Vec<Witness>),
+}
+
+impl Display for Witness {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let Witness::Constructor(constructor, arguments) = self else {
+            return match self {
+                Witness::Type(head) => write!(f, "{}", head),
+                _ => write!(f, "_"),
+            };
+        };
+
+        let arguments: Vec<String> = arguments.iter().map(Witness::to_string).collect();
+        match constructor {
+            Constructor::Value(_, written) => write!(f, "{}", written),
+            Constructor::Bool(boolean) => write!(f, "{}", boolean),
+            Constructor::Tuple(_) => write!(f, "({})", arguments.join(", ")),
+            Constructor::Variant(typ, variant) if arguments.is_empty() => write!(f, "{}::{}", typ, variant),
+            Constructor::Variant(typ, variant) => write!(f, "{}::{}({})", typ, variant, arguments.join(", ")),
+        }
+    }
+}
+
+type Row = Vec<Pat>;
+
+/// Checks that every match covers the values it can be given, and that each of its arms can be reached
+pub(super) fn check(ast: &AST, options: &VerifyOptions, diagnostics: &mut Vec<Diagnostic>) {
+    let mut checker = Checker {
+        enums: HashMap::new(),
+        options,
+        diagnostics,
+    };
+    checker.check_block(&ast.0);
+}
+
+struct Checker<'a> {
+    /// The variants of each enum type declared in the blocks checked so far, with the length of their payloads
+    enums: HashMap<String, Vec<(String, usize)>>,
+    options: &'a VerifyOptions,
+    diagnostics: &'a mut Vec<Diagnostic>,
+}
+
+impl Checker<'_> {
+    fn check_block(&mut self, block: &Block) {
+        // A match can be written before the types it takes apart, since it runs once it's called
+        for statement in &block.statements {
+            if let Statement::Type(TypeDeclaration { name, body: TypeBody::Enum(variants) }) = statement {
+                let variants = variants.iter().map(|(variant, payload)| (variant.clone(), payload.len())).collect();
+                self.enums.insert(name.name.clone(), variants);
+            }
+        }
+
+        for statement in &block.statements {
+            match statement {
+                Statement::Expression(expr) |
+                Statement::Let(_, expr) |
+                Statement::Set(_, expr) |
+                Statement::Return(expr) |
+                Statement::Break(Some(expr)) => self.check_expression(expr),
+                Statement::SetPlace(_, accessors, expr) => {
+                    for accessor in accessors {
+                        if let Accessor::Index(index) = accessor {
+                            self.check_expression(&index.expression);
+                        }
+                    }
+                    self.check_expression(expr);
+                }
+                Statement::Impl(_, members) => {
+                    for (_, expr) in members {
+                        self.check_expression(expr);
+                    }
+                }
+                Statement::Type(_) | Statement::Break(None) | Statement::Continue | Statement::Error(_) => {}
+            }
+        }
+    }
+
+    fn check_expression(&mut self, expr: &Expression) {
+        match &expr.kind {
+            ExpressionKind::Literal(Value::Function(Function::Match { arms })) => self.check_match(arms, true),
+            ExpressionKind::Literal(_) | ExpressionKind::Identifier(_) | ExpressionKind::Static(..) => {}
+            ExpressionKind::Binary(left, BinaryOperator::ForEach, right) => {
+                self.check_expression(left);
+                // The results of `$` are thrown away, so its match only needs arms for the values it does something with
+                match &right.kind {
+                    ExpressionKind::Literal(Value::Function(Function::Match { arms })) => self.check_match(arms, false),
+                    _ => self.check_expression(right),
+                }
+            }
+            ExpressionKind::Binary(left, _, right) | ExpressionKind::Call(left, right) => {
+                self.check_expression(left);
+                self.check_expression(right);
+            }
+            ExpressionKind::Unary(_, expr) | ExpressionKind::Field(expr, _) => self.check_expression(expr),
+            ExpressionKind::Index(expr, index) => {
+                self.check_expression(expr);
+                self.check_expression(&index.expression);
+            }
+            ExpressionKind::Tuple(elements) | ExpressionKind::List(elements) => {
+                for element in elements {
+                    self.check_expression(element);
+                }
+            }
+            ExpressionKind::Construct(_, fields) => {
+                for (_, value) in fields {
+                    self.check_expression(value);
+                }
+            }
+            ExpressionKind::Dict(entries) => {
+                for (key, value) in entries {
+                    self.check_expression(key);
+                    self.check_expression(value);
+                }
+            }
+            ExpressionKind::Interpolated(parts) => {
+                for part in parts {
+                    if let InterpolatedPart::Expression(expr) = part {
+                        self.check_expression(expr);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Warns about arms which earlier arms leave nothing to match, and, if `must_cover`,
+    /// about values which none of the arms match.
+    fn check_match(&mut self, arms: &[MatchArm], must_cover: bool) {
+        let mut rows: Vec<Row> = Vec::new();
+        let mut row_spans = Vec::new();
+
+        for arm in arms {
+            if let Some(guard) = &arm.pattern.guard {
+                self.check_expression(guard);
+            }
+            self.check_block(&arm.block);
+
+            let row = vec![lower(&arm.pattern.kind)];
+            // Any value could be given to a match, so an arm is reachable if it matches a type no earlier arm tests for
+            if self.is_useful(&[], &row, false).is_none() {
+                // The arm matches nothing even with no arms before it
+                let mut diagnostic = Diagnostic::new(DiagnosticKind::Semantic, codes::UNREACHABLE_ARM, "Unreachable match arm".to_string())
+                    .with_severity(Severity::Warning)
+                    .with_label(arm.pattern.span, "no value matches this pattern");
+                if let Some(range) = find_empty_range(&arm.pattern.kind) {
+                    diagnostic = diagnostic.with_help(format!("The range {} is empty, since its start isn't before its end", range));
+                }
+                self.diagnostics.push(diagnostic);
+            } else if self.is_useful(&rows, &row, false).is_none() {
+                let mut diagnostic = Diagnostic::new(DiagnosticKind::Semantic, codes::UNREACHABLE_ARM, "Unreachable match arm".to_string())
+                    .with_severity(Severity::Warning)
+                    .with_label(arm.pattern.span, "every value this matches is matched by an earlier arm");
+                let shadowing = rows.iter().zip(&row_spans)
+                    .find(|(earlier, _)| self.is_useful(std::slice::from_ref(*earlier), &row, false).is_none());
+                if let Some((_, span)) = shadowing {
+                    diagnostic = diagnostic.with_secondary_label(*span, "this arm matches them first");
+                }
+                self.diagnostics.push(diagnostic);
+            }
+
+            // A guard can always fail, so a guarded arm doesn't stop a value from reaching later arms
+            let is_guarded = !matches!(arm.pattern.guard, None | Some(Expression { kind: ExpressionKind::Literal(Value::Boolean(true)), .. }));
+            if !is_guarded {
+                rows.push(row);
+                row_spans.push(arm.pattern.span);
+            }
+        }
+
+        // Chains of `|~ condition :` arms are the way to write if statements, which don't need an else
+        let is_if_chain = arms.iter().all(|arm| matches!(arm.pattern.kind, PatternKind::Empty));
+        let Some(first_arm) = arms.first() else {
+            return;
+        };
+        if !must_cover || is_if_chain {
+            return;
+        }
+
+        // Without knowing what the match is given, assume it's only given the types its patterns test for
+        let Some(witness) = self.is_useful(&rows, &[Pat::Wild], true).and_then(|witnesses| witnesses.into_iter().next()) else {
+            return;
+        };
+        let severity = if self.options.strict { Severity::Error } else { Severity::Warning };
+        let diagnostic = match witness {
+            Witness::Wild => Diagnostic::new(DiagnosticKind::Semantic, codes::NON_EXHAUSTIVE_MATCH, "Match doesn't cover every value".to_string())
+                .with_label(first_arm.pattern.span, "some values match none of this match's arms")
+                .with_help("Add a catch-all arm, like `| _ : ...`"),
+            // Only some values of the type are matched, like by `| 0 :`
+            Witness::Type(head) => Diagnostic::new(DiagnosticKind::Semantic, codes::NON_EXHAUSTIVE_MATCH, format!("Match doesn't cover every {}", head))
+                .with_label(first_arm.pattern.span, format!("some {} values match none of this match's arms", head))
+                .with_help(format!("Add an arm like `| {} value :`, or a catch-all arm like `| _ : ...`", head)),
+            witness => Diagnostic::new(DiagnosticKind::Semantic, codes::NON_EXHAUSTIVE_MATCH, format!("Match doesn't cover `{}`", witness))
+                .with_label(first_arm.pattern.span, format!("`{}` matches none of this match's arms", witness))
+                .with_help(format!("Add an arm for `{}`, or a catch-all arm like `| _ : ...`", witness)),
+        };
+        self.diagnostics.push(diagnostic
+            .with_severity(severity)
+            .with_note("A match returns none when none of its arms match"));
+    }
+
+    /// Looks for values which `row` matches but none of `rows` do, returning an example if there are any.
+    /// When `closed`, values are only looked for among the types the patterns test for, rather than among every type.
+    /// This is the usefulness algorithm from "Warnings for pattern matching" by Luc Maranget.
+    fn is_useful(&self, rows: &[Row], row: &[Pat], closed: bool) -> Option<Vec<Witness>> {
+        let Some((first, rest)) = row.split_first() else {
+            return rows.is_empty().then(Vec::new);
+        };
+        let rows = expand_or(rows);
+
+        match first {
+            Pat::Or(alternatives) => alternatives.iter().find_map(|alternative| {
+                let row: Row = std::iter::once(alternative.clone()).chain(rest.iter().cloned()).collect();
+                self.is_useful(&rows, &row, closed)
+            }),
+            Pat::Constructor(constructor, arguments) => {
+                let row: Row = arguments.iter().chain(rest).cloned().collect();
+                self.is_useful(&specialize(&rows, constructor, arguments.len()), &row, closed)
+                    .map(|witnesses| rebuild(constructor.clone(), arguments.len(), witnesses))
+            }
+            Pat::Type(head) => self.is_useful_for_head(&rows, head, rest, closed),
+            Pat::Wild => {
+                let mut heads: Vec<Head> = Vec::new();
+                for head in rows.iter().filter_map(|row| row[0].head()) {
+                    if !heads.contains(&head) {
+                        heads.push(head);
+                    }
+                }
+
+                if closed && !heads.is_empty() {
+                    heads.iter().find_map(|head| self.is_useful_for_head(&rows, head, rest, closed))
+                } else {
+                    // There's a type none of the rows test for, so only rows which match anything match its values
+                    let defaults: Vec<Row> = rows.iter()
+                        .filter(|row| matches!(row[0], Pat::Wild))
+                        .map(|row| row[1..].to_vec())
+                        .collect();
+                    self.is_useful(&defaults, rest, closed).map(|witnesses| prepend(Witness::Wild, witnesses))
+                }
+            }
+        }
+    }
+
+    /// Looks for values with the head which `rest` matches but none of `rows` do
+    fn is_useful_for_head(&self, rows: &[Row], head: &Head, rest: &[Pat], closed: bool) -> Option<Vec<Witness>> {
+        if let Some(constructors) = self.constructors(head, rows) {
+            return constructors.into_iter().find_map(|(constructor, arity)| {
+                let row: Row = std::iter::repeat_n(Pat::Wild, arity).chain(rest.iter().cloned()).collect();
+                self.is_useful(&specialize(rows, &constructor, arity), &row, closed)
+                    .map(|witnesses| rebuild(constructor, arity, witnesses))
+            });
+        }
+
+        // Some values with the head aren't built by any constructor the rows match, so only rows matching the whole type match them
+        let defaults: Vec<Row> = rows.iter()
+            .filter(|row| match &row[0] {
+                Pat::Wild => true,
+                Pat::Type(other) => other == head,
+                _ => false,
+            })
+            .map(|row| row[1..].to_vec())
+            .collect();
+        self.is_useful(&defaults, rest, closed).map(|witnesses| prepend(Witness::Type(head.clone()), witnesses))
+    }
+
+    /// Every constructor of values with the head, along with its arity, or None if there are too many to list
+    fn constructors(&self, head: &Head, rows: &[Row]) -> Option<Vec<(Constructor, usize)>> {
+        match head {
+            Head::Bool => Some(vec![(Constructor::Bool(true), 0), (Constructor::Bool(false), 0)]),
+            Head::Tuple(length) => Some(vec![(Constructor::Tuple(*length), *length)]),
+            Head::Named(name) => match self.enums.get(name) {
+                Some(variants) => Some(variants.iter()
+                    .map(|(variant, arity)| (Constructor::Variant(name.clone(), variant.clone()), *arity))
+                    .collect()),
+                // The type is declared somewhere else, like in an earlier repl input, so trust the patterns to know its variants
+                None => {
+                    let mut constructors = Vec::new();
+                    for row in rows {
+                        if let Pat::Constructor(constructor @ Constructor::Variant(..), arguments) = &row[0] {
+                            if !constructors.iter().any(|(existing, _)| existing == constructor) {
+                                constructors.push((constructor.clone(), arguments.len()));
+                            }
+                        }
+                    }
+                    (!constructors.is_empty()).then_some(constructors)
+                }
+            },
+            _ => None,
+        }
+    }
+}
+
+/// Splits rows starting with an or-pattern into one row per alternative
+fn expand_or(rows: &[Row]) -> Vec<Row> {
+    let mut expanded = Vec::new();
+    for row in rows {
+        match row.first() {
+            Some(Pat::Or(alternatives)) => {
+                for alternative in alternatives {
+                    let row: Row = std::iter::once(alternative.clone()).chain(row[1..].iter().cloned()).collect();
+                    expanded.extend(expand_or(&[row]));
+                }
+            }
+            _ => expanded.push(row.clone()),
+        }
+    }
+    expanded
+}
+
+/// Keeps the rows which match values built by the constructor, replacing their first pattern with a pattern for each argument
+fn specialize(rows: &[Row], constructor: &Constructor, arity: usize) -> Vec<Row> {
+    rows.iter()
+        .filter_map(|row| {
+            let arguments = match &row[0] {
+                Pat::Wild => vec![Pat::Wild; arity],
+                Pat::Type(head) if *head == constructor.head() => vec![Pat::Wild; arity],
+                Pat::Constructor(other, arguments) if other == constructor && arguments.len() == arity => arguments.clone(),
+                _ => return None,
+            };
+            Some(arguments.into_iter().chain(row[1..].iter().cloned()).collect())
+        })
+        .collect()
+}
+
+/// Puts the first `arity` witnesses back together as the arguments of the constructor
+fn rebuild(constructor: Constructor, arity: usize, mut witnesses: Vec<Witness>) -> Vec<Witness> {
+    let rest = witnesses.split_off(arity);
+    prepend(Witness::Constructor(constructor, witnesses), rest)
+}
+
+fn prepend(witness: Witness, rest: Vec<Witness>) -> Vec<Witness> {
+    std::iter::once(witness).chain(rest).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SHAPE: &str = "type Shape: Circle(float) | Rect(float, float) | Empty\n";
+
+    fn diagnostics(source: &str, options: &VerifyOptions) -> Vec<(ErrorCode, Severity, String)> {
+        let mut diagnostics = Vec::new();
+        check(&super::super::parse(source), options, &mut diagnostics);
+        diagnostics.into_iter().map(|diagnostic| (diagnostic.code, diagnostic.severity, diagnostic.message)).collect()
+    }
+
+    fn messages(source: &str) -> Vec<String> {
+        diagnostics(source, &VerifyOptions::default()).into_iter().map(|(.., message)| message).collect()
+    }
+
+    #[test]
+    fn nested_enums_and_tuples_name_a_missing_value() {
+        let source = format!("{}let f = | (Shape::Circle(_), true) : 1\n        | (Shape::Rect(_, _), _) : 2\n        | (Shape::Empty, false) : 3\n", SHAPE);
+        assert_eq!(messages(&source), ["Match doesn't cover `(Shape::Circle(_), false)`"]);
+
+        let source = format!("{}let f = | (Shape::Circle(_), _) : 1\n        | (Shape::Rect(_, _), _) : 2\n        | (Shape::Empty, true) : 3\n        | (Shape::Empty, false) : 4\n", SHAPE);
+        assert!(messages(&source).is_empty());
+    }
+
+    #[test]
+    fn arms_after_ones_that_match_everything_are_unreachable() {
+        assert_eq!(messages("let f = | _ : 1\n        | 2 : 2\n"), ["Unreachable match arm"]);
+
+        let source = format!("{}let f = | (Shape::Circle(_), _) : 1\n        | (Shape::Rect(_, _), _) : 2\n        | (Shape::Empty, _) : 3\n        | (Shape::Empty, true) : 4\n", SHAPE);
+        assert_eq!(messages(&source), ["Unreachable match arm"]);
+    }
+
+    #[test]
+    fn matches_can_come_before_their_type() {
+        let source = format!("let f = | Shape::Circle(_) : 1\n        | Shape::Rect(_, _) : 2\n{}", SHAPE);
+        assert_eq!(messages(&source), ["Match doesn't cover `Shape::Empty`"]);
+    }
+
+    #[test]
+    fn strict_mode_makes_missing_values_errors() {
+        let source = "let f = | true : 1\n";
+        let strict = VerifyOptions { strict: true, ..VerifyOptions::default() };
+        assert_eq!(diagnostics(source, &VerifyOptions::default())[0].1, Severity::Warning);
+        assert_eq!(diagnostics(source, &strict)[0].1, Severity::Error);
+    }
+
+    #[test]
+    fn empty_ranges_match_nothing() {
+        let source = "let f = | 10..1 : 1\n        | 5..5 : 2\n        | 5..=5 : 3\n        | (_, 2.5..=1.0) : 4\n        | \"b\"..\"a\" : 5\n        | _ : 6\n";
+        let mut diagnostics = Vec::new();
+        check(&super::super::parse(source), &VerifyOptions::default(), &mut diagnostics);
+        let helps: Vec<_> = diagnostics.iter()
+            .inspect(|diagnostic| assert_eq!((diagnostic.code, diagnostic.severity), (codes::UNREACHABLE_ARM, Severity::Warning)))
+            .map(|diagnostic| diagnostic.help.clone().unwrap())
+            .collect();
+        assert_eq!(helps, [
+            "The range 10..1 is empty, since its start isn't before its end",
+            "The range 5..5 is empty, since its start isn't before its end",
+            "The range 2.5..=1.0 is empty, since its start isn't before its end",
+            "The range \"b\"..\"a\" is empty, since its start isn't before its end",
+        ]);
+
+        // An empty range doesn't stop values reaching later arms
+        assert_eq!(messages("let f = | 10..1 : 1\n        | 10 : 2\n        | _ : 3\n"), ["Unreachable match arm"]);
+    }
+}
@@ -1,11 +1,40 @@
-use crate::prelude::*; 
+use crate::prelude::*;
 use crate::constructs::ast::AST;
 
-pub fn verify(ast: AST) -> Result<AST> {
-    // Check type information
+mod exhaustiveness;
+mod resolve;
+mod types;
 
-    // etc.
+#[derive(Debug, Clone, Default)]
+pub struct VerifyOptions {
+    /// Whether matches that don't cover every value are errors rather than warnings
+    pub strict: bool,
+    /// Identifiers defined before the program runs, like those from earlier REPL inputs
+    pub defined: Vec<String>,
+}
 
-    // Temporary
-    Ok(ast)
-}
\ No newline at end of file
+/// Checks a parsed program before it runs. Returns the program along with every warning and error
+/// found, so a program is only fit to run if none of the diagnostics are errors.
+pub fn verify(ast: AST) -> (AST, Vec<Diagnostic>) {
+    verify_with_options(ast, &VerifyOptions::default())
+}
+
+pub fn verify_with_options(mut ast: AST, options: &VerifyOptions) -> (AST, Vec<Diagnostic>) {
+    let mut diagnostics = Vec::new();
+    resolve::resolve(&mut ast, options, &mut diagnostics);
+    exhaustiveness::check(&ast, options, &mut diagnostics);
+    types::check(&ast, &mut diagnostics);
+
+    (ast, diagnostics)
+}
+
+/// Parses the source of a test, which has to be valid syntax
+#[cfg(test)]
+fn parse(source: &str) -> AST {
+    use crate::constructs::token::Tokens;
+
+    let tokens = crate::tokenize::parse_tokens(source).expect("The test's source should tokenize");
+    let (ast, errors) = crate::parse::parse(&mut Tokens::new(&tokens));
+    assert!(errors.is_empty(), "The test's source should parse, but got {:?}", errors);
+    ast
+}
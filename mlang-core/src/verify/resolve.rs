@@ -0,0 +1,382 @@
+use std::collections::HashMap;
+
+use crate::prelude::*;
+use crate::constructs::ast::*;
+use crate::constructs::token::span::Span;
+use crate::constructs::variable::Value;
+use super::VerifyOptions;
+
+/// The identifiers every program starts with
+const BUILTINS: [&str; 8] = ["print", "println", "assert", "get", "set", "remove", "contains", "args"];
+
+/// Works out which binding each identifier refers to, filling in [`Identifier::binding`], and reports
+/// identifiers which aren't defined, `let`s of names which already are, and assignments to names which aren't.
+pub(super) fn resolve(ast: &mut AST, options: &VerifyOptions, diagnostics: &mut Vec<Diagnostic>) {
+    let mut resolver = Resolver {
+        scopes: vec![Scope::default()],
+        floor: 0,
+        next_id: 0,
+        diagnostics,
+    };
+
+    for name in BUILTINS.iter().copied().chain(options.defined.iter().map(String::as_str)) {
+        let id = resolver.fresh_id();
+        resolver.scopes[0].defined.insert(name.to_string(), (id, None));
+    }
+    resolver.resolve_statements(&mut ast.0);
+}
+
+#[derive(Default)]
+struct Scope {
+    /// The names defined so far, with where they're defined. Names defined before the program have no span.
+    defined: HashMap<String, (BindingId, Option<Span>)>,
+    /// The names the block defines further down, in order. Matches written above them can use them,
+    /// since they run later.
+    later: Vec<(String, BindingId, Span)>,
+}
+
+struct Resolver<'a> {
+    scopes: Vec<Scope>,
+    /// How many scopes are outside the innermost match being resolved. Those scopes can use the names
+    /// they define later, since the match won't run until it's called.
+    floor: usize,
+    next_id: u32,
+    diagnostics: &'a mut Vec<Diagnostic>,
+}
+
+impl Resolver<'_> {
+    /// Resolves the statements of a block in the innermost scope, after noting the names it defines
+    fn resolve_statements(&mut self, block: &mut Block) {
+        for statement in &block.statements {
+            let name = match statement {
+                Statement::Let(identifier, _) => identifier,
+                Statement::Type(declaration) => &declaration.name,
+                _ => continue,
+            };
+            let id = self.fresh_id();
+            self.scope().later.push((name.name.clone(), id, name.span));
+        }
+
+        for statement in &mut block.statements {
+            self.resolve_statement(statement);
+        }
+    }
+
+    fn resolve_statement(&mut self, statement: &mut Statement) {
+        match statement {
+            Statement::Expression(expression) | Statement::Return(expression) | Statement::Break(Some(expression)) => {
+                self.resolve_expression(expression);
+            }
+            Statement::Let(identifier, expression) => {
+                self.resolve_expression(expression);
+                self.define(identifier, true);
+            }
+            Statement::Set(identifier, expression) => {
+                self.resolve_expression(expression);
+                match self.lookup(&identifier.name) {
+                    Some((id, _)) => identifier.binding = Some(id),
+                    None => self.diagnostics.push(Diagnostic::new(DiagnosticKind::Semantic, codes::UNDEFINED_ASSIGNMENT, format!("Identifier {} does not exist", identifier.name))
+                        .with_label(identifier.span, "assigned to before being defined")
+                        .with_help(format!("Use `let {} = ...` to define it", identifier.name))),
+                }
+            }
+            Statement::SetPlace(identifier, accessors, expression) => {
+                self.resolve_use(identifier);
+                for accessor in accessors {
+                    if let Accessor::Index(index) = accessor {
+                        self.resolve_expression(&mut index.expression);
+                    }
+                }
+                self.resolve_expression(expression);
+            }
+            Statement::Type(declaration) => self.define(&mut declaration.name, false),
+            Statement::Impl(typ, members) => {
+                self.resolve_use(typ);
+                for (_, expression) in members {
+                    self.resolve_expression(expression);
+                }
+            }
+            Statement::Break(None) | Statement::Continue | Statement::Error(_) => {}
+        }
+    }
+
+    fn resolve_expression(&mut self, expression: &mut Expression) {
+        match &mut expression.kind {
+            ExpressionKind::Literal(Value::Function(Function::Match { arms })) => {
+                if arms.iter().all(|arm| matches!(arm.pattern.kind, PatternKind::Empty)) {
+                    self.resolve_if_chain(arms);
+                } else {
+                    self.resolve_match(arms);
+                }
+            }
+            ExpressionKind::Literal(_) => {}
+            ExpressionKind::Identifier(identifier) | ExpressionKind::Static(identifier, _) => self.resolve_use(identifier),
+            ExpressionKind::Binary(left, _, right) | ExpressionKind::Call(left, right) => {
+                self.resolve_expression(left);
+                self.resolve_expression(right);
+            }
+            ExpressionKind::Unary(_, expression) | ExpressionKind::Field(expression, _) => self.resolve_expression(expression),
+            ExpressionKind::Index(expression, index) => {
+                self.resolve_expression(expression);
+                self.resolve_expression(&mut index.expression);
+            }
+            ExpressionKind::Tuple(elements) | ExpressionKind::List(elements) => {
+                for element in elements {
+                    self.resolve_expression(element);
+                }
+            }
+            ExpressionKind::Construct(typ, fields) => {
+                self.resolve_use(typ);
+                for (_, value) in fields {
+                    self.resolve_expression(value);
+                }
+            }
+            ExpressionKind::Dict(entries) => {
+                for (key, value) in entries {
+                    self.resolve_expression(key);
+                    self.resolve_expression(value);
+                }
+            }
+            ExpressionKind::Interpolated(parts) => {
+                for part in parts {
+                    if let InterpolatedPart::Expression(expression) = part {
+                        self.resolve_expression(expression);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Resolves each arm in a scope of its own, holding what its pattern binds
+    fn resolve_match(&mut self, arms: &mut [MatchArm]) {
+        let floor = std::mem::replace(&mut self.floor, self.scopes.len());
+
+        for arm in arms {
+            self.scopes.push(Scope::default());
+            self.bind_pattern(&mut arm.pattern.kind, &mut HashMap::new(), &mut HashMap::new());
+            self.resolve_arm(arm);
+            self.scopes.pop();
+        }
+
+        self.floor = floor;
+    }
+
+    /// An if-chain runs straight away, in the scope it's written in, so the names its arms define
+    /// stay around after it. Only one of the arms runs, so they can define the same names.
+    fn resolve_if_chain(&mut self, arms: &mut [MatchArm]) {
+        let mut defined = HashMap::new();
+        for arm in arms {
+            self.scopes.push(Scope::default());
+            self.resolve_arm(arm);
+
+            let arm_scope = self.scopes.pop().expect("The arm's scope was just pushed");
+            for (name, binding) in arm_scope.defined {
+                defined.entry(name).or_insert(binding);
+            }
+        }
+
+        for (name, binding) in defined {
+            self.scope().defined.entry(name).or_insert(binding);
+        }
+    }
+
+    fn resolve_arm(&mut self, arm: &mut MatchArm) {
+        if let Some(guard) = &mut arm.pattern.guard {
+            self.resolve_expression(guard);
+        }
+        self.resolve_statements(&mut arm.block);
+    }
+
+    /// Defines each identifier the pattern binds, noting where in `bound`. Each alternative of an
+    /// or-pattern binds the same identifiers, so they share a binding through `ids`.
+    fn bind_pattern(&mut self, pattern: &mut PatternKind, bound: &mut HashMap<String, Span>, ids: &mut HashMap<String, BindingId>) {
+        match pattern {
+            PatternKind::Binding { identifier, .. } => {
+                if let Some(first) = bound.get(&identifier.name) {
+                    self.diagnostics.push(Diagnostic::new(DiagnosticKind::Semantic, codes::DUPLICATE_BINDING, format!("Identifier {} is bound more than once in the pattern", identifier.name))
+                        .with_label(identifier.span, "bound again here")
+                        .with_secondary_label(*first, "first bound here"));
+                } else {
+                    bound.insert(identifier.name.clone(), identifier.span);
+                }
+
+                let id = match ids.get(&identifier.name) {
+                    Some(id) => *id,
+                    None => self.fresh_id(),
+                };
+                ids.insert(identifier.name.clone(), id);
+                identifier.binding = Some(id);
+                self.scope().defined.insert(identifier.name.clone(), (id, Some(identifier.span)));
+            }
+            PatternKind::Or(alternatives) => {
+                // Only one alternative matches, so they can each bind the same names
+                let mut merged = None;
+                for alternative in alternatives {
+                    let mut alternative_bound = bound.clone();
+                    self.bind_pattern(alternative, &mut alternative_bound, ids);
+                    merged.get_or_insert(alternative_bound);
+                }
+                if let Some(merged) = merged {
+                    *bound = merged;
+                }
+            }
+            PatternKind::Tuple(elements) | PatternKind::Variant { payload: elements, .. } => {
+                for element in elements {
+                    self.bind_pattern(element, bound, ids);
+                }
+            }
+            PatternKind::Empty | PatternKind::Wildcard | PatternKind::Literal(_) | PatternKind::Range { .. } => {}
+        }
+    }
+
+    fn resolve_use(&mut self, identifier: &mut Identifier) {
+        if let Some((id, _)) = self.lookup(&identifier.name) {
+            identifier.binding = Some(id);
+            return;
+        }
+
+        let later = self.scopes.iter()
+            .flat_map(|scope| &scope.later)
+            .find(|(name, ..)| *name == identifier.name);
+        let diagnostic = match later {
+            Some((_, _, span)) => Diagnostic::new(DiagnosticKind::Semantic, codes::UNRESOLVED_IDENTIFIER, format!("Identifier {} is used before it's defined", identifier.name))
+                .with_label(identifier.span, "used here")
+                .with_secondary_label(*span, "defined here"),
+            None => Diagnostic::new(DiagnosticKind::Semantic, codes::UNRESOLVED_IDENTIFIER, format!("Identifier {} not found", identifier.name))
+                .with_label(identifier.span, "not found in this scope"),
+        };
+        self.diagnostics.push(diagnostic);
+    }
+
+    /// Defines the identifier in the innermost scope, which is an error if it's already defined
+    fn define(&mut self, identifier: &mut Identifier, is_variable: bool) {
+        if let Some((_, previous)) = self.lookup(&identifier.name) {
+            let mut diagnostic = Diagnostic::new(DiagnosticKind::Semantic, codes::REDEFINED_IDENTIFIER, format!("Identifier {} already exists", identifier.name))
+                .with_label(identifier.span, "defined again here");
+            let is_builtin = previous.is_none() && BUILTINS.contains(&identifier.name.as_str());
+            diagnostic = match previous {
+                Some(span) => diagnostic.with_secondary_label(span, "first defined here"),
+                None if is_builtin => diagnostic.with_note(format!("{} is a builtin", identifier.name)),
+                None => diagnostic.with_note(format!("{} was defined by an earlier input", identifier.name)),
+            };
+            if is_variable && !is_builtin {
+                diagnostic = diagnostic.with_help(format!("Use `{} = ...` to give it a new value", identifier.name));
+            }
+            self.diagnostics.push(diagnostic);
+        }
+
+        let scope = self.scope();
+        let id = match scope.later.iter().position(|(name, ..)| *name == identifier.name) {
+            Some(idx) => scope.later.remove(idx).1,
+            None => self.fresh_id(),
+        };
+        identifier.binding = Some(id);
+        self.scope().defined.insert(identifier.name.clone(), (id, Some(identifier.span)));
+    }
+
+    /// Finds the binding a name refers to from the innermost scope
+    fn lookup(&self, name: &str) -> Option<(BindingId, Option<Span>)> {
+        for (idx, scope) in self.scopes.iter().enumerate().rev() {
+            if let Some(binding) = scope.defined.get(name) {
+                return Some(*binding);
+            }
+            if idx < self.floor {
+                if let Some((_, id, span)) = scope.later.iter().find(|(later, ..)| later == name) {
+                    return Some((*id, Some(*span)));
+                }
+            }
+        }
+        None
+    }
+
+    fn scope(&mut self) -> &mut Scope {
+        self.scopes.last_mut().expect("There is always a scope")
+    }
+
+    fn fresh_id(&mut self) -> BindingId {
+        self.next_id += 1;
+        BindingId(self.next_id - 1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn resolved(source: &str, options: &VerifyOptions) -> (AST, Vec<Diagnostic>) {
+        let mut ast = super::super::parse(source);
+        let mut diagnostics = Vec::new();
+        resolve(&mut ast, options, &mut diagnostics);
+        (ast, diagnostics)
+    }
+
+    fn reported(source: &str) -> Vec<(ErrorCode, String)> {
+        resolved(source, &VerifyOptions::default()).1.into_iter()
+            .map(|diagnostic| (diagnostic.code, diagnostic.message))
+            .collect()
+    }
+
+    #[test]
+    fn undefined_names_are_reported() {
+        assert_eq!(reported("let a = b + 1\nc = 3\n"), [
+            (codes::UNRESOLVED_IDENTIFIER, "Identifier b not found".to_string()),
+            (codes::UNDEFINED_ASSIGNMENT, "Identifier c does not exist".to_string()),
+        ]);
+        assert_eq!(reported("let a = b + 1\nlet b = 2\n"), [
+            (codes::UNRESOLVED_IDENTIFIER, "Identifier b is used before it's defined".to_string()),
+        ]);
+    }
+
+    #[test]
+    fn matches_can_use_names_defined_after_them() {
+        assert!(reported("let f = | _ : g\nlet g = 1\n").is_empty());
+    }
+
+    #[test]
+    fn names_defined_earlier_can_be_used() {
+        let options = VerifyOptions { defined: vec!["x".to_string()], ..VerifyOptions::default() };
+        assert!(resolved("let y = x + 1\n", &options).1.is_empty());
+    }
+
+    #[test]
+    fn redefined_names_are_reported() {
+        assert_eq!(reported("let a = 1\nlet a = 2\nlet print = 3\n"), [
+            (codes::REDEFINED_IDENTIFIER, "Identifier a already exists".to_string()),
+            (codes::REDEFINED_IDENTIFIER, "Identifier print already exists".to_string()),
+        ]);
+        assert_eq!(reported("let f = | (a, a) : a\n"), [
+            (codes::DUPLICATE_BINDING, "Identifier a is bound more than once in the pattern".to_string()),
+        ]);
+    }
+
+    #[test]
+    fn or_pattern_alternatives_share_bindings() {
+        let (ast, diagnostics) = resolved("let f = | (0, y) | (y, 0) : y\n", &VerifyOptions::default());
+        assert!(diagnostics.is_empty());
+
+        let Statement::Let(_, Expression { kind: ExpressionKind::Literal(Value::Function(Function::Match { arms })), .. }) = &ast.0.statements[0] else {
+            panic!("Expected a match, got {:?}", ast.0.statements[0]);
+        };
+        let PatternKind::Or(alternatives) = &arms[0].pattern.kind else {
+            panic!("Expected an or-pattern, got {:?}", arms[0].pattern.kind);
+        };
+        let bindings: Vec<Option<BindingId>> = alternatives.iter()
+            .flat_map(|alternative| match alternative {
+                PatternKind::Tuple(elements) => elements.iter(),
+                _ => [].iter(),
+            })
+            .filter_map(|element| match element {
+                PatternKind::Binding { identifier, .. } => Some(identifier.binding),
+                _ => None,
+            })
+            .collect();
+        let Statement::Expression(Expression { kind: ExpressionKind::Identifier(used), .. }) = &arms[0].block.statements[0] else {
+            panic!("Expected an identifier, got {:?}", arms[0].block.statements[0]);
+        };
+
+        assert_eq!(bindings.len(), 2);
+        assert!(bindings[0].is_some());
+        assert_eq!(bindings[0], bindings[1]);
+        assert_eq!(used.binding, bindings[0]);
+    }
+}
@@ -0,0 +1,1011 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt::Display;
+
+use crate::prelude::*;
+use crate::constructs::ast::*;
+use crate::constructs::token::span::Span;
+use crate::constructs::variable::{Type, TypeBody, Value};
+
+/// The type of a value as far as the verifier can work it out. Unlike [`Type`], this has types for
+/// matches, for what an iterator gives, and for types which aren't known yet.
+#[derive(Debug, Clone, PartialEq)]
+enum Ty {
+    Int,
+    Float,
+    String,
+    Bool,
+    None,
+    Tuple(Vec<Ty>),
+    List(Box<Ty>),
+    Dict(Box<Ty>, Box<Ty>),
+    Named(String),
+    /// The type of a declared type itself, like the value of `Person`
+    Type,
+    /// A range, filter or map, giving values of the type
+    Iter(Box<Ty>),
+    /// A match, builtin or variant constructor, taking the first type and giving the second
+    Function(Box<Ty>, Box<Ty>),
+    /// A type still being worked out, which is the same type everywhere it's used
+    Var(usize),
+    /// A type which can't be known before running, like that of a variable given values of
+    /// different types. Any type can be used as it.
+    Any,
+}
+
+impl Ty {
+    fn function(parameter: Ty, result: Ty) -> Ty {
+        Ty::Function(Box::new(parameter), Box::new(result))
+    }
+
+    fn of_type(typ: &Type) -> Ty {
+        match typ {
+            Type::Int => Ty::Int,
+            Type::Float => Ty::Float,
+            Type::String => Ty::String,
+            Type::Boolean => Ty::Bool,
+            Type::None => Ty::None,
+            // `tuple` is any tuple, whatever its length
+            Type::Tuple(types) if types.is_empty() => Ty::Any,
+            Type::Tuple(types) => Ty::Tuple(types.iter().map(Ty::of_type).collect()),
+            Type::List(element) => Ty::List(Box::new(Ty::of_type(element))),
+            Type::Dict(key, value) => Ty::Dict(Box::new(Ty::of_type(key)), Box::new(Ty::of_type(value))),
+            Type::Named(name) => Ty::Named(name.clone()),
+            Type::Type => Ty::Type,
+            Type::Iter => Ty::Iter(Box::new(Ty::Any)),
+            Type::Match | Type::Builtin => Ty::function(Ty::Any, Ty::Any),
+        }
+    }
+
+    /// The types inside the type, like the element type of a list
+    fn children(&self) -> Vec<&Ty> {
+        match self {
+            Ty::Tuple(types) => types.iter().collect(),
+            Ty::List(inner) | Ty::Iter(inner) => vec![inner],
+            Ty::Dict(first, second) | Ty::Function(first, second) => vec![first, second],
+            _ => Vec::new(),
+        }
+    }
+
+    /// Makes the same type with `f` applied to each type inside it
+    fn map(&self, mut f: impl FnMut(&Ty) -> Ty) -> Ty {
+        match self {
+            Ty::Tuple(types) => Ty::Tuple(types.iter().map(f).collect()),
+            Ty::List(element) => Ty::List(Box::new(f(element))),
+            Ty::Iter(element) => Ty::Iter(Box::new(f(element))),
+            Ty::Dict(key, value) => Ty::Dict(Box::new(f(key)), Box::new(f(value))),
+            Ty::Function(parameter, result) => Ty::function(f(parameter), f(result)),
+            ty => ty.clone(),
+        }
+    }
+
+    fn is_known(&self) -> bool {
+        !matches!(self, Ty::Var(_) | Ty::Any)
+    }
+
+    /// Whether values of the type can be dictionary keys, as far as is known
+    fn is_key(&self) -> bool {
+        match self {
+            Ty::Int | Ty::String | Ty::Bool | Ty::Var(_) | Ty::Any => true,
+            Ty::Tuple(types) => types.iter().all(Ty::is_key),
+            _ => false,
+        }
+    }
+}
+
+/// Writes the type the way it's written in mlang, with `_` for types which aren't known
+impl Display for Ty {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Ty::Int => write!(f, "int"),
+            Ty::Float => write!(f, "float"),
+            Ty::String => write!(f, "string"),
+            Ty::Bool => write!(f, "bool"),
+            Ty::None => write!(f, "none"),
+            Ty::Tuple(types) => {
+                let types: Vec<String> = types.iter().map(Ty::to_string).collect();
+                write!(f, "({})", types.join(", "))
+            }
+            Ty::List(element) => write!(f, "[{}]", element),
+            Ty::Dict(key, value) => write!(f, "{{{}: {}}}", key, value),
+            Ty::Named(name) => write!(f, "{}", name),
+            Ty::Type => write!(f, "type"),
+            Ty::Iter(_) => write!(f, "iter"),
+            Ty::Function(parameter, result) if matches!(**parameter, Ty::Function(..)) => write!(f, "({}) -> {}", parameter, result),
+            Ty::Function(parameter, result) => write!(f, "{} -> {}", parameter, result),
+            Ty::Var(_) | Ty::Any => write!(f, "_"),
+        }
+    }
+}
+
+/// The type of a name. A match given a name can be used at a different type each time it's used,
+/// so the type variables in `vars` are replaced with new ones on every use.
+#[derive(Debug, Clone)]
+struct Scheme {
+    vars: Vec<usize>,
+    ty: Ty,
+}
+
+impl From<Ty> for Scheme {
+    fn from(ty: Ty) -> Self {
+        Scheme { vars: Vec::new(), ty }
+    }
+}
+
+/// Works out the type of every expression in the program, and reports the places where a value
+/// is used in a way its type doesn't allow, like `"a" - 1`. Types are only checked where they're
+/// known, so names the program doesn't define, like those from earlier REPL inputs, can be anything.
+pub(super) fn check(ast: &AST, diagnostics: &mut Vec<Diagnostic>) {
+    let mut inference = Inference {
+        substitution: Vec::new(),
+        scopes: vec![HashMap::new()],
+        types: HashMap::new(),
+        statics: HashMap::new(),
+        diagnostics,
+    };
+
+    inference.define_builtins();
+    inference.declare(&ast.0);
+    inference.infer_block(&ast.0);
+}
+
+struct Inference<'a> {
+    /// What each type variable has been worked out to be
+    substitution: Vec<Option<Ty>>,
+    scopes: Vec<HashMap<String, Scheme>>,
+    /// The declared types, by name
+    types: HashMap<String, TypeBody>,
+    /// The static members of each type, including the variants of enum types
+    statics: HashMap<String, HashMap<String, Scheme>>,
+    diagnostics: &'a mut Vec<Diagnostic>,
+}
+
+impl Inference<'_> {
+    fn define_builtins(&mut self) {
+        self.bind("print", Ty::function(Ty::Any, Ty::None).into());
+        self.bind("println", Ty::function(Ty::Any, Ty::None).into());
+        self.bind("assert", Ty::function(Ty::Bool, Ty::None).into());
+        self.bind("args", Ty::List(Box::new(Ty::String)).into());
+
+        // The dictionary builtins work on any dictionary, with keys and values of its types.
+        // `get` gives none for a missing key, so what it gives could be anything.
+        let vars = vec![self.fresh_var(), self.fresh_var()];
+        let (key, value) = (Ty::Var(vars[0]), Ty::Var(vars[1]));
+        let dict = Ty::Dict(Box::new(key.clone()), Box::new(value.clone()));
+        let generic = |ty| Scheme { vars: vars.clone(), ty };
+
+        self.bind("get", generic(Ty::function(Ty::Tuple(vec![dict.clone(), key.clone()]), Ty::Any)));
+        self.bind("set", generic(Ty::function(Ty::Tuple(vec![dict.clone(), key.clone(), value]), dict.clone())));
+        self.bind("remove", generic(Ty::function(Ty::Tuple(vec![dict.clone(), key.clone()]), dict.clone())));
+        self.bind("contains", generic(Ty::function(Ty::Tuple(vec![dict, key]), Ty::Bool)));
+    }
+
+    /// Makes the types and static members declared in the block known before checking it, so that
+    /// matches can use the ones declared after them
+    fn declare(&mut self, block: &Block) {
+        for statement in &block.statements {
+            match statement {
+                Statement::Type(declaration) => self.declare_type(declaration),
+                Statement::Impl(typ, members) => self.declare_statics(typ, members),
+                _ => {}
+            }
+        }
+    }
+
+    fn declare_type(&mut self, declaration: &TypeDeclaration) {
+        let name = &declaration.name.name;
+        let statics = self.statics.entry(name.clone()).or_default();
+        if let TypeBody::Enum(variants) = &declaration.body {
+            for (variant, payload) in variants {
+                statics.insert(variant.clone(), variant_type(name, payload).into());
+            }
+        }
+
+        self.types.insert(name.clone(), declaration.body.clone());
+        self.bind(name, Ty::Type.into());
+    }
+
+    /// Gives each member a type still to be worked out, so that members can use each other
+    fn declare_statics(&mut self, typ: &Identifier, members: &[(Identifier, Expression)]) {
+        for (member, _) in members {
+            let declared = self.statics.get(&typ.name).is_some_and(|statics| statics.contains_key(&member.name));
+            if !declared {
+                let ty = self.fresh();
+                self.statics.entry(typ.name.clone()).or_default().insert(member.name.clone(), ty.into());
+            }
+        }
+    }
+
+    /// The type of the value a block gives, which is that of its first `return` or `break`,
+    /// or otherwise its last statement
+    fn infer_block(&mut self, block: &Block) -> Ty {
+        let mut result = None;
+
+        for (idx, statement) in block.statements.iter().enumerate() {
+            let ty = self.infer_statement(statement);
+            let stops = matches!(statement, Statement::Return(_) | Statement::Break(_) | Statement::Continue);
+            if result.is_none() && (stops || idx == block.statements.len() - 1) {
+                result = Some(ty);
+            }
+        }
+
+        result.unwrap_or(Ty::None)
+    }
+
+    fn infer_statement(&mut self, statement: &Statement) -> Ty {
+        match statement {
+            Statement::Expression(expression) | Statement::Return(expression) | Statement::Break(Some(expression)) => self.infer(expression),
+            Statement::Let(identifier, expression) => {
+                let scheme = self.infer_definition(Some(&identifier.name), expression);
+                self.bind(&identifier.name, scheme);
+                Ty::None
+            }
+            Statement::Set(identifier, expression) => {
+                let ty = self.infer(expression);
+                let compatible = match self.lookup(&identifier.name).cloned() {
+                    Some(scheme) => scheme.vars.is_empty() && self.unify(&scheme.ty, &ty),
+                    None => true,
+                };
+                if !compatible {
+                    self.widen(&identifier.name);
+                }
+                Ty::None
+            }
+            Statement::SetPlace(identifier, accessors, expression) => {
+                let mut place = self.infer_identifier(&identifier.name);
+                for (idx, accessor) in accessors.iter().enumerate() {
+                    place = match accessor {
+                        Accessor::Index(index) => {
+                            let index_ty = self.infer(&index.expression);
+                            self.index_type(&place, &index_ty, index.span)
+                        }
+                        Accessor::Field(field) => self.field_type(&place, &field.name).unwrap_or_else(|message| {
+                            self.diagnostics.push(Diagnostic::new(DiagnosticKind::Semantic, codes::UNKNOWN_MEMBER, message)
+                                .with_label(field.span, "no such field"));
+                            Ty::Any
+                        }),
+                        Accessor::Static(member) if idx == 0 => self.static_type(&identifier.name, &member.name).unwrap_or_else(|message| {
+                            self.diagnostics.push(Diagnostic::new(DiagnosticKind::Semantic, codes::UNKNOWN_MEMBER, message)
+                                .with_label(member.span, "no such static member"));
+                            Ty::Any
+                        }),
+                        Accessor::Static(_) => Ty::Any,
+                    };
+                }
+
+                // Parts of a value can be given values of a different type, like an element of a list
+                let ty = self.infer(expression);
+                if !self.unify(&place, &ty) {
+                    self.widen(&identifier.name);
+                }
+                Ty::None
+            }
+            Statement::Type(declaration) => {
+                self.declare_type(declaration);
+                Ty::None
+            }
+            Statement::Impl(typ, members) => {
+                self.declare_statics(typ, members);
+                for (member, expression) in members {
+                    let scheme = self.infer_definition(None, expression);
+                    if let Some(declared) = self.statics.get(&typ.name).and_then(|statics| statics.get(&member.name)).cloned() {
+                        if declared.vars.is_empty() {
+                            self.unify(&declared.ty, &scheme.ty);
+                        }
+                    }
+                    self.statics.entry(typ.name.clone()).or_default().insert(member.name.clone(), scheme);
+                }
+                Ty::None
+            }
+            Statement::Break(None) | Statement::Continue => Ty::None,
+            Statement::Error(_) => Ty::Any,
+        }
+    }
+
+    /// Works out the type of a value being given a name. A match can be used at a different type
+    /// each time it's used, and can call itself by its name.
+    fn infer_definition(&mut self, name: Option<&str>, expression: &Expression) -> Scheme {
+        if !matches!(expression.kind, ExpressionKind::Literal(Value::Function(Function::Match { .. }))) {
+            return self.infer(expression).into();
+        }
+
+        let itself = self.fresh();
+        self.scopes.push(name.map(|name| (name.to_string(), itself.clone().into())).into_iter().collect());
+        let ty = self.infer(expression);
+        self.unify(&itself, &ty);
+        self.scopes.pop();
+
+        self.generalize(&ty)
+    }
+
+    fn infer(&mut self, expression: &Expression) -> Ty {
+        match &expression.kind {
+            ExpressionKind::Literal(Value::Function(Function::Match { arms })) => {
+                if arms.iter().all(|arm| matches!(arm.pattern.kind, PatternKind::Empty)) {
+                    self.infer_if_chain(arms)
+                } else {
+                    self.infer_match(arms)
+                }
+            }
+            ExpressionKind::Literal(value) => literal_type(value),
+            ExpressionKind::Identifier(identifier) => self.infer_identifier(&identifier.name),
+            ExpressionKind::Interpolated(parts) => {
+                for part in parts {
+                    if let InterpolatedPart::Expression(expression) = part {
+                        self.infer(expression);
+                    }
+                }
+                Ty::String
+            }
+            ExpressionKind::Tuple(elements) => Ty::Tuple(elements.iter().map(|element| self.infer(element)).collect()),
+            ExpressionKind::List(elements) => {
+                // Lists can hold values of different types, but then their elements can be anything
+                let mut element_ty = self.fresh();
+                for element in elements {
+                    let ty = self.infer(element);
+                    if !self.unify(&element_ty, &ty) {
+                        element_ty = Ty::Any;
+                    }
+                }
+                Ty::List(Box::new(element_ty))
+            }
+            ExpressionKind::Dict(entries) => {
+                let (mut key_ty, mut value_ty) = (self.fresh(), self.fresh());
+                for (key, value) in entries {
+                    let ty = self.infer(key);
+                    if !self.apply(&ty).is_key() {
+                        let ty = self.show(&ty);
+                        self.diagnostics.push(Diagnostic::new(DiagnosticKind::Semantic, codes::MISMATCHED_TYPES, format!("Cannot use {} as a dictionary key", ty))
+                            .with_label(key.span, format!("this is {}", ty))
+                            .with_note("Keys can be ints, strings, bools, or tuples of them"));
+                    } else if !self.unify(&key_ty, &ty) {
+                        key_ty = Ty::Any;
+                    }
+
+                    let ty = self.infer(value);
+                    if !self.unify(&value_ty, &ty) {
+                        value_ty = Ty::Any;
+                    }
+                }
+                Ty::Dict(Box::new(key_ty), Box::new(value_ty))
+            }
+            ExpressionKind::Index(indexed, index) => {
+                let ty = self.infer(indexed);
+                let index_ty = self.infer(&index.expression);
+                self.index_type(&ty, &index_ty, index.span)
+            }
+            ExpressionKind::Construct(typ, fields) => self.infer_construct(typ, fields, expression.span),
+            ExpressionKind::Field(value, field) => {
+                let ty = self.infer(value);
+                self.field_type(&ty, &field.name).unwrap_or_else(|message| {
+                    self.diagnostics.push(Diagnostic::new(DiagnosticKind::Semantic, codes::UNKNOWN_MEMBER, message)
+                        .with_label(expression.span, "no such field"));
+                    Ty::Any
+                })
+            }
+            ExpressionKind::Static(typ, member) => {
+                self.static_type(&typ.name, &member.name).unwrap_or_else(|message| {
+                    self.diagnostics.push(Diagnostic::new(DiagnosticKind::Semantic, codes::UNKNOWN_MEMBER, message)
+                        .with_label(expression.span, "no such static member"));
+                    Ty::Any
+                })
+            }
+            ExpressionKind::Binary(left, operator, right) => self.infer_binary(left, operator, right, expression.span),
+            ExpressionKind::Unary(operator, operand) => {
+                let ty = self.infer(operand);
+                let valid = match operator {
+                    UnaryOperator::Minus => !self.resolve(&ty).is_known() || matches!(self.resolve(&ty), Ty::Int | Ty::Float),
+                    UnaryOperator::Not => self.unify(&ty, &Ty::Bool),
+                };
+
+                if !valid {
+                    let shown = self.show(&ty);
+                    self.diagnostics.push(Diagnostic::new(DiagnosticKind::Semantic, codes::MISMATCHED_TYPES, format!("Cannot negate {}", shown))
+                        .with_label(operand.span, format!("this is {}", shown)));
+                    return Ty::Any;
+                }
+                match operator {
+                    UnaryOperator::Minus => ty,
+                    UnaryOperator::Not => Ty::Bool,
+                }
+            }
+            ExpressionKind::Call(argument, callee) => {
+                let argument_ty = self.infer(argument);
+                let callee_ty = self.infer(callee);
+                self.call(&callee_ty, &argument_ty, argument.span, callee.span, false)
+            }
+        }
+    }
+
+    fn infer_identifier(&mut self, name: &str) -> Ty {
+        match self.lookup(name).cloned() {
+            Some(scheme) => self.instantiate(&scheme),
+            None => Ty::Any,
+        }
+    }
+
+    fn infer_binary(&mut self, left: &Expression, operator: &BinaryOperator, right: &Expression, span: Span) -> Ty {
+        let left_ty = self.infer(left);
+        let right_ty = self.infer(right);
+
+        let (verb, result) = match operator {
+            BinaryOperator::ForEach | BinaryOperator::Map | BinaryOperator::Filter | BinaryOperator::All | BinaryOperator::Any => {
+                let element = self.element_type(&left_ty, left.span);
+                let result = self.call(&right_ty, &element, left.span, right.span, true);
+
+                return match operator {
+                    BinaryOperator::ForEach => Ty::None,
+                    BinaryOperator::Map => Ty::Iter(Box::new(result)),
+                    _ => {
+                        if !self.unify(&result, &Ty::Bool) {
+                            let symbol = match operator {
+                                BinaryOperator::Filter => "#",
+                                BinaryOperator::All => "&&&",
+                                _ => "|||",
+                            };
+                            let result = self.show(&result);
+                            self.diagnostics.push(Diagnostic::new(DiagnosticKind::Semantic, codes::MISMATCHED_TYPES, format!("The match given to {} must return a boolean, found {}", symbol, result))
+                                .with_label(right.span, format!("this returns {}", result)));
+                        }
+                        if *operator == BinaryOperator::Filter { Ty::Iter(Box::new(element)) } else { Ty::Bool }
+                    }
+                };
+            }
+            BinaryOperator::Range => {
+                for (operand, ty) in [(left, &left_ty), (right, &right_ty)] {
+                    if !self.unify(ty, &Ty::Int) {
+                        let (left_ty, right_ty, ty) = (self.show(&left_ty), self.show(&right_ty), self.show(ty));
+                        self.diagnostics.push(Diagnostic::new(DiagnosticKind::Semantic, codes::MISMATCHED_TYPES, format!("Cannot create range {} and {}", left_ty, right_ty))
+                            .with_label(operand.span, format!("expected int, found {}", ty)));
+                        break;
+                    }
+                }
+                return Ty::Iter(Box::new(Ty::Int));
+            }
+            BinaryOperator::Plus => ("add", None),
+            BinaryOperator::Minus => ("subtract", None),
+            BinaryOperator::Mul => ("multiply", None),
+            BinaryOperator::Div => ("divide", None),
+            BinaryOperator::Mod => ("modulo", None),
+            BinaryOperator::And => ("AND", Some(Ty::Bool)),
+            BinaryOperator::Or => ("OR", Some(Ty::Bool)),
+            BinaryOperator::NotEqual | BinaryOperator::Equal | BinaryOperator::Greater |
+            BinaryOperator::GreaterEqual | BinaryOperator::Less | BinaryOperator::LessEqual => ("compare", Some(Ty::Bool)),
+        };
+
+        if !self.unify(&left_ty, &right_ty) {
+            let (left_ty, right_ty) = (self.show(&left_ty), self.show(&right_ty));
+            self.diagnostics.push(Diagnostic::new(DiagnosticKind::Semantic, codes::MISMATCHED_TYPES, format!("Cannot {} {} and {}", verb, left_ty, right_ty))
+                .with_label(right.span, format!("this is {}", right_ty))
+                .with_secondary_label(left.span, format!("this is {}", left_ty))
+                .with_note("Both sides of an operator must have the same type"));
+            return result.unwrap_or(Ty::Any);
+        }
+
+        // Operators which only work on one type tell what their operands are
+        match operator {
+            BinaryOperator::Mod => { self.unify(&left_ty, &Ty::Int); }
+            BinaryOperator::And | BinaryOperator::Or => { self.unify(&left_ty, &Ty::Bool); }
+            _ => {}
+        }
+
+        let ty = self.resolve(&left_ty);
+        if ty.is_known() && !supports(operator, &ty) {
+            let ty = self.show(&ty);
+            self.diagnostics.push(Diagnostic::new(DiagnosticKind::Semantic, codes::MISMATCHED_TYPES, format!("Cannot {} {} and {}", verb, ty, ty))
+                .with_label(span, format!("both sides are {}", ty)));
+            return result.unwrap_or(Ty::Any);
+        }
+
+        result.unwrap_or(left_ty)
+    }
+
+    /// Works out what calling `function` with `argument` gives. If `iterated`, the function is
+    /// called with each of the values `argument_span` gives rather than the value itself.
+    fn call(&mut self, function: &Ty, argument: &Ty, argument_span: Span, callee_span: Span, iterated: bool) -> Ty {
+        match self.resolve(function) {
+            Ty::Function(parameter, result) => {
+                let unified = match (self.resolve(&parameter), self.resolve(argument)) {
+                    // Each value of a tuple is unified on its own, so that the error shows what the others worked out
+                    (Ty::Tuple(parameters), Ty::Tuple(arguments)) if parameters.len() == arguments.len() => {
+                        parameters.iter().zip(&arguments).fold(true, |unified, (parameter, argument)| self.unify(parameter, argument) && unified)
+                    }
+                    _ => self.unify(&parameter, argument),
+                };
+
+                if !unified {
+                    let (expected, found) = (self.show(&parameter), self.show(argument));
+                    let label = if iterated {
+                        format!("this gives {} values", found)
+                    } else {
+                        format!("expected {}, found {}", expected, found)
+                    };
+                    self.diagnostics.push(Diagnostic::new(DiagnosticKind::Semantic, codes::MISMATCHED_TYPES, "Mismatched types".to_string())
+                        .with_label(argument_span, label)
+                        .with_secondary_label(callee_span, format!("this takes {}", expected)));
+                }
+                *result
+            }
+            Ty::Var(var) => {
+                let result = self.fresh();
+                self.unify(&Ty::Var(var), &Ty::function(argument.clone(), result.clone()));
+                result
+            }
+            Ty::Any => Ty::Any,
+            ty => {
+                let ty = self.show(&ty);
+                self.diagnostics.push(Diagnostic::new(DiagnosticKind::Semantic, codes::MISMATCHED_TYPES, format!("Cannot call {}", ty))
+                    .with_label(callee_span, format!("this is {}, not a match", ty)));
+                Ty::Any
+            }
+        }
+    }
+
+    /// The type of the values iterating over a value of the type gives
+    fn element_type(&mut self, iterable: &Ty, span: Span) -> Ty {
+        match self.resolve(iterable) {
+            Ty::List(element) | Ty::Iter(element) => *element,
+            Ty::String => Ty::String,
+            Ty::Dict(key, value) => Ty::Tuple(vec![*key, *value]),
+            Ty::Var(_) | Ty::Any => Ty::Any,
+            ty => {
+                let ty = self.show(&ty);
+                self.diagnostics.push(Diagnostic::new(DiagnosticKind::Semantic, codes::MISMATCHED_TYPES, format!("Cannot iterate over {}", ty))
+                    .with_label(span, format!("this is {}", ty)));
+                Ty::Any
+            }
+        }
+    }
+
+    fn index_type(&mut self, indexed: &Ty, index: &Ty, span: Span) -> Ty {
+        let result = match (self.resolve(indexed), self.resolve(index)) {
+            (Ty::Var(_) | Ty::Any, _) => Some(Ty::Any),
+            // An index which isn't known could be an int or a range
+            (Ty::List(_) | Ty::String, Ty::Var(_) | Ty::Any) => Some(Ty::Any),
+            (Ty::List(element), Ty::Int) => Some(*element),
+            (Ty::String, Ty::Int) => Some(Ty::String),
+            (ty @ (Ty::List(_) | Ty::String), Ty::Iter(_)) => Some(ty),
+            (Ty::Dict(key, value), index) => self.unify(&key, &index).then_some(*value),
+            _ => None,
+        };
+
+        result.unwrap_or_else(|| {
+            let (indexed, index) = (self.show(indexed), self.show(index));
+            self.diagnostics.push(Diagnostic::new(DiagnosticKind::Semantic, codes::MISMATCHED_TYPES, format!("Cannot index {} with {}", indexed, index))
+                .with_label(span, format!("this is {}", index)));
+            Ty::Any
+        })
+    }
+
+    /// The type of a field of a value of the type, or why it has no such field
+    fn field_type(&self, ty: &Ty, field: &str) -> std::result::Result<Ty, String> {
+        match self.resolve(ty) {
+            Ty::Named(name) => match self.types.get(&name) {
+                Some(TypeBody::Struct(fields)) => fields.iter()
+                    .find(|(declared, _)| declared == field)
+                    .map(|(_, typ)| Ty::of_type(typ))
+                    .ok_or_else(|| format!("{} has no field {}", name, field)),
+                Some(TypeBody::Enum(_)) => Err(format!("{} has no field {}", name, field)),
+                // Types the program doesn't declare may be from earlier REPL inputs
+                None => Ok(Ty::Any),
+            },
+            Ty::Var(_) | Ty::Any => Ok(Ty::Any),
+            ty => Err(format!("{} has no field {}", self.show(&ty), field)),
+        }
+    }
+
+    /// The type of a static member of a type, or why it has no such member
+    fn static_type(&mut self, typ: &str, member: &str) -> std::result::Result<Ty, String> {
+        match self.statics.get(typ).and_then(|statics| statics.get(member)).cloned() {
+            Some(scheme) => Ok(self.instantiate(&scheme)),
+            None if self.types.contains_key(typ) => Err(format!("{} has no static member {}", typ, member)),
+            None => Ok(Ty::Any),
+        }
+    }
+
+    fn infer_construct(&mut self, typ: &Identifier, fields: &[(Identifier, Expression)], span: Span) -> Ty {
+        let values: Vec<Ty> = fields.iter().map(|(_, value)| self.infer(value)).collect();
+        let name_span = Span { index: span.index, len: typ.name.len() as u32 };
+
+        let declared = match self.types.get(&typ.name) {
+            Some(TypeBody::Struct(declared)) => declared.clone(),
+            Some(TypeBody::Enum(_)) => {
+                self.diagnostics.push(Diagnostic::new(DiagnosticKind::Semantic, codes::MISMATCHED_TYPES, format!("{} has variants instead of fields, so it's made with one of them, like {}::Variant", typ.name, typ.name))
+                    .with_label(name_span, "made with fields here"));
+                return Ty::Named(typ.name.clone());
+            }
+            None => return Ty::Any,
+        };
+
+        for ((field, value), ty) in fields.iter().zip(&values) {
+            match declared.iter().find(|(name, _)| *name == field.name) {
+                Some((_, field_type)) => {
+                    let expected = Ty::of_type(field_type);
+                    if !self.unify(&expected, ty) {
+                        let (expected, found) = (self.show(&expected), self.show(ty));
+                        self.diagnostics.push(Diagnostic::new(DiagnosticKind::Semantic, codes::MISMATCHED_TYPES, format!("Field {} of {} must be {}, found {}", field.name, typ.name, expected, found))
+                            .with_label(value.span, format!("expected {}, found {}", expected, found)));
+                    }
+                }
+                None => self.diagnostics.push(Diagnostic::new(DiagnosticKind::Semantic, codes::UNKNOWN_MEMBER, format!("{} has no field {}", typ.name, field.name))
+                    .with_label(value.span, "given to a field that doesn't exist")),
+            }
+        }
+
+        let missing: Vec<&str> = declared.iter()
+            .map(|(name, _)| name.as_str())
+            .filter(|name| !fields.iter().any(|(field, _)| field.name == *name))
+            .collect();
+        if !missing.is_empty() {
+            let plural = if missing.len() == 1 { "" } else { "s" };
+            self.diagnostics.push(Diagnostic::new(DiagnosticKind::Semantic, codes::MISSING_FIELDS, format!("Missing field{} {} of {}", plural, missing.join(", "), typ.name))
+                .with_label(name_span, format!("{} needs a value for every field", typ.name)));
+        }
+
+        Ty::Named(typ.name.clone())
+    }
+
+    fn infer_match(&mut self, arms: &[MatchArm]) -> Ty {
+        let patterns: Vec<(Ty, Vec<(String, Ty)>)> = arms.iter()
+            .map(|arm| {
+                let mut bindings = Vec::new();
+                (self.pattern_type(&arm.pattern.kind, &mut bindings), bindings)
+            })
+            .collect();
+
+        // The match takes the type its patterns are for. If they're for different types, or an arm
+        // after the first matches anything, the match can take anything.
+        let mut parameter = self.fresh();
+        for (idx, (arm, (ty, _))) in arms.iter().zip(&patterns).enumerate() {
+            if (idx > 0 && catches_all(&arm.pattern)) || !self.unify(&parameter, ty) {
+                parameter = Ty::Any;
+            }
+        }
+
+        let mut result = self.fresh();
+        for (arm, (_, bindings)) in arms.iter().zip(patterns) {
+            let ty = self.infer_arm(arm, bindings);
+            if !self.unify(&result, &ty) {
+                result = Ty::Any;
+            }
+        }
+
+        Ty::function(parameter, result)
+    }
+
+    /// The type an if-chain gives, which runs straight away rather than being called
+    fn infer_if_chain(&mut self, arms: &[MatchArm]) -> Ty {
+        let mut result = self.fresh();
+        for arm in arms {
+            let ty = self.infer_arm(arm, Vec::new());
+            if !self.unify(&result, &ty) {
+                result = Ty::Any;
+            }
+        }
+
+        // An if-chain without an arm that always runs can run none of them and give none
+        if !arms.iter().any(|arm| catches_all(&arm.pattern)) && !self.unify(&result, &Ty::None) {
+            result = Ty::Any;
+        }
+        result
+    }
+
+    fn infer_arm(&mut self, arm: &MatchArm, bindings: Vec<(String, Ty)>) -> Ty {
+        self.scopes.push(bindings.into_iter().map(|(name, ty)| (name, ty.into())).collect());
+
+        if let Some(guard) = &arm.pattern.guard {
+            let ty = self.infer(guard);
+            if !self.unify(&ty, &Ty::Bool) {
+                let ty = self.show(&ty);
+                self.diagnostics.push(Diagnostic::new(DiagnosticKind::Semantic, codes::MISMATCHED_TYPES, format!("Guard must be a boolean, found {}", ty))
+                    .with_label(guard.span, format!("this is {}", ty)));
+            }
+        }
+        let ty = self.infer_block(&arm.block);
+
+        self.scopes.pop();
+        ty
+    }
+
+    /// The type of the values the pattern is for, adding the type of each identifier it binds to `bindings`
+    fn pattern_type(&mut self, pattern: &PatternKind, bindings: &mut Vec<(String, Ty)>) -> Ty {
+        match pattern {
+            PatternKind::Empty | PatternKind::Wildcard => self.fresh(),
+            PatternKind::Binding { typ, identifier } => {
+                let ty = match typ {
+                    Some(typ) => Ty::of_type(typ),
+                    None => self.fresh(),
+                };
+                bindings.push((identifier.name.clone(), ty.clone()));
+                ty
+            }
+            PatternKind::Tuple(elements) => Ty::Tuple(elements.iter().map(|element| self.pattern_type(element, bindings)).collect()),
+            PatternKind::Literal(value) | PatternKind::Range { start: value, .. } => literal_type(value),
+            PatternKind::Or(alternatives) => {
+                // Every alternative binds the same identifiers. One that's bound to different types
+                // by different alternatives could be either, so it's left to be checked when running.
+                let mut ty = self.fresh();
+                let mut merged: Option<Vec<(String, Ty)>> = None;
+                for alternative in alternatives {
+                    let mut alternative_bindings = Vec::new();
+                    let alternative_ty = self.pattern_type(alternative, &mut alternative_bindings);
+                    if !self.unify(&ty, &alternative_ty) {
+                        ty = Ty::Any;
+                    }
+
+                    let Some(merged) = &mut merged else {
+                        merged = Some(alternative_bindings);
+                        continue;
+                    };
+                    for (name, binding_ty) in alternative_bindings {
+                        if let Some((_, merged_ty)) = merged.iter_mut().find(|(merged_name, _)| *merged_name == name) {
+                            if !self.unify(merged_ty, &binding_ty) {
+                                *merged_ty = Ty::Any;
+                            }
+                        }
+                    }
+                }
+                bindings.extend(merged.unwrap_or_default());
+                ty
+            }
+            PatternKind::Variant { typ, variant, payload } => {
+                let declared = match self.types.get(&typ.name) {
+                    Some(TypeBody::Enum(variants)) => variants.iter()
+                        .find(|(name, _)| *name == variant.name)
+                        .map(|(_, payload)| payload.clone()),
+                    _ => None,
+                };
+
+                for (idx, element) in payload.iter().enumerate() {
+                    let ty = self.pattern_type(element, bindings);
+                    if let Some(expected) = declared.as_ref().and_then(|declared| declared.get(idx)) {
+                        self.unify(&ty, &Ty::of_type(expected));
+                    }
+                }
+                Ty::Named(typ.name.clone())
+            }
+        }
+    }
+
+    fn fresh_var(&mut self) -> usize {
+        self.substitution.push(None);
+        self.substitution.len() - 1
+    }
+
+    fn fresh(&mut self) -> Ty {
+        Ty::Var(self.fresh_var())
+    }
+
+    fn bind(&mut self, name: &str, scheme: Scheme) {
+        self.scopes.last_mut().expect("There is always a scope").insert(name.to_string(), scheme);
+    }
+
+    fn lookup(&self, name: &str) -> Option<&Scheme> {
+        self.scopes.iter().rev().find_map(|scope| scope.get(name))
+    }
+
+    /// Lets a variable be given a value of a different type, after which its type isn't known until running
+    fn widen(&mut self, name: &str) {
+        if let Some(scope) = self.scopes.iter_mut().rev().find(|scope| scope.contains_key(name)) {
+            scope.insert(name.to_string(), Ty::Any.into());
+        }
+    }
+
+    /// Follows type variables until reaching a type which isn't one that's been worked out
+    fn resolve(&self, ty: &Ty) -> Ty {
+        let mut ty = ty.clone();
+        while let Ty::Var(var) = ty {
+            match &self.substitution[var] {
+                Some(bound) => ty = bound.clone(),
+                None => break,
+            }
+        }
+        ty
+    }
+
+    /// Replaces every type variable in the type which has been worked out
+    fn apply(&self, ty: &Ty) -> Ty {
+        self.resolve(ty).map(|inner| self.apply(inner))
+    }
+
+    fn show(&self, ty: &Ty) -> String {
+        self.apply(ty).to_string()
+    }
+
+    fn occurs(&self, var: usize, ty: &Ty) -> bool {
+        match self.resolve(ty) {
+            Ty::Var(other) => var == other,
+            ty => ty.children().into_iter().any(|inner| self.occurs(var, inner)),
+        }
+    }
+
+    fn free_vars(&self, ty: &Ty, vars: &mut HashSet<usize>) {
+        match self.resolve(ty) {
+            Ty::Var(var) => { vars.insert(var); }
+            ty => ty.children().into_iter().for_each(|inner| self.free_vars(inner, vars)),
+        }
+    }
+
+    /// Makes the type variables of a match's type which nothing around it depends on new for each use
+    fn generalize(&self, ty: &Ty) -> Scheme {
+        let mut in_scope = HashSet::new();
+        let schemes = self.scopes.iter().flat_map(HashMap::values)
+            .chain(self.statics.values().flat_map(HashMap::values));
+        for scheme in schemes {
+            let mut vars = HashSet::new();
+            self.free_vars(&scheme.ty, &mut vars);
+            in_scope.extend(vars.into_iter().filter(|var| !scheme.vars.contains(var)));
+        }
+
+        let mut vars = HashSet::new();
+        self.free_vars(ty, &mut vars);
+        Scheme {
+            vars: vars.difference(&in_scope).copied().collect(),
+            ty: self.apply(ty),
+        }
+    }
+
+    fn instantiate(&mut self, scheme: &Scheme) -> Ty {
+        let fresh: HashMap<usize, Ty> = scheme.vars.iter().map(|&var| (var, self.fresh())).collect();
+        self.replace(&scheme.ty, &fresh)
+    }
+
+    fn replace(&self, ty: &Ty, vars: &HashMap<usize, Ty>) -> Ty {
+        match self.resolve(ty) {
+            Ty::Var(var) => vars.get(&var).cloned().unwrap_or(Ty::Var(var)),
+            ty => ty.map(|inner| self.replace(inner, vars)),
+        }
+    }
+
+    /// Makes the two types the same by working out type variables, returning whether they can be.
+    /// If they can't, no type variables are worked out.
+    fn unify(&mut self, first: &Ty, second: &Ty) -> bool {
+        let mut bound = Vec::new();
+        let unified = self.unify_binding(first, second, &mut bound);
+        if !unified {
+            for var in bound {
+                self.substitution[var] = None;
+            }
+        }
+        unified
+    }
+
+    fn unify_binding(&mut self, first: &Ty, second: &Ty, bound: &mut Vec<usize>) -> bool {
+        match (self.resolve(first), self.resolve(second)) {
+            (Ty::Any, _) | (_, Ty::Any) => true,
+            (Ty::Var(first), Ty::Var(second)) if first == second => true,
+            (Ty::Var(var), ty) | (ty, Ty::Var(var)) => {
+                // A type can't contain itself, so a value like that is left to be checked when running
+                if !self.occurs(var, &ty) {
+                    self.substitution[var] = Some(ty);
+                    bound.push(var);
+                }
+                true
+            }
+            (Ty::Tuple(first), Ty::Tuple(second)) => {
+                first.len() == second.len() && first.iter().zip(&second).all(|(first, second)| self.unify_binding(first, second, bound))
+            }
+            (Ty::List(first), Ty::List(second)) | (Ty::Iter(first), Ty::Iter(second)) => self.unify_binding(&first, &second, bound),
+            (Ty::Dict(first_key, first_value), Ty::Dict(second_key, second_value)) |
+            (Ty::Function(first_key, first_value), Ty::Function(second_key, second_value)) => {
+                self.unify_binding(&first_key, &second_key, bound) && self.unify_binding(&first_value, &second_value, bound)
+            }
+            (first, second) => first == second,
+        }
+    }
+}
+
+fn literal_type(value: &Value) -> Ty {
+    match value {
+        Value::Function(Function::Constructor { typ, payload, .. }) => variant_type(typ, payload),
+        Value::Function(_) => Ty::function(Ty::Any, Ty::Any),
+        Value::IntRange(..) => Ty::Iter(Box::new(Ty::Int)),
+        value => Ty::of_type(&value.get_type()),
+    }
+}
+
+/// The type of a variant made through its type, like `Shape::Empty` or `Shape::Circle`. Variants
+/// with a payload are made by calling them with it.
+fn variant_type(typ: &str, payload: &[Type]) -> Ty {
+    let named = Ty::Named(typ.to_string());
+    match payload {
+        [] => named,
+        [single] => Ty::function(Ty::of_type(single), named),
+        payload => Ty::function(Ty::Tuple(payload.iter().map(Ty::of_type).collect()), named),
+    }
+}
+
+/// Whether the arm runs whatever the value, like `| n :` or `| _ :`
+fn catches_all(pattern: &Pattern) -> bool {
+    let unguarded = match &pattern.guard {
+        None => true,
+        Some(guard) => matches!(guard.kind, ExpressionKind::Literal(Value::Boolean(true))),
+    };
+    unguarded && matches!(pattern.kind, PatternKind::Empty | PatternKind::Wildcard | PatternKind::Binding { typ: None, .. })
+}
+
+/// Whether the operator works on two values of the type
+fn supports(operator: &BinaryOperator, ty: &Ty) -> bool {
+    match operator {
+        BinaryOperator::Plus => matches!(ty, Ty::Int | Ty::Float | Ty::String),
+        BinaryOperator::Minus | BinaryOperator::Mul | BinaryOperator::Div => matches!(ty, Ty::Int | Ty::Float),
+        BinaryOperator::Mod => matches!(ty, Ty::Int),
+        BinaryOperator::Equal | BinaryOperator::NotEqual => matches!(ty, Ty::Int | Ty::Float | Ty::String | Ty::Bool | Ty::Tuple(_)),
+        BinaryOperator::Greater | BinaryOperator::GreaterEqual |
+        BinaryOperator::Less | BinaryOperator::LessEqual => matches!(ty, Ty::Int | Ty::Float | Ty::String | Ty::Tuple(_)),
+        BinaryOperator::And | BinaryOperator::Or => matches!(ty, Ty::Bool),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn messages(source: &str) -> Vec<String> {
+        let mut diagnostics = Vec::new();
+        check(&super::super::parse(source), &mut diagnostics);
+        diagnostics.into_iter().map(|diagnostic| diagnostic.message).collect()
+    }
+
+    #[test]
+    fn let_bound_matches_are_polymorphic() {
+        assert!(messages("let id = | x : x\nlet n = (1 id) + 1\nlet s = (\"a\" id) + \"b\"\n").is_empty());
+        assert_eq!(messages("let id = | x : x\nlet n = (1 id) + 1\nlet s = (\"a\" id) - 1\n"), ["Cannot subtract string and int"]);
+    }
+
+    #[test]
+    fn recursive_matches_are_inferred() {
+        let fact = "let fact = | 0 : 1\n           | n : n * ((n - 1) fact)\n";
+        assert!(messages(&format!("{}let x = (5 fact) + 1\n", fact)).is_empty());
+        assert_eq!(messages(&format!("{}let x = (5 fact) + \"a\"\n", fact)), ["Cannot add int and string"]);
+    }
+
+    #[test]
+    fn reassigning_a_different_type_widens_the_variable() {
+        assert!(messages("let x = 1\nx = \"a\"\nlet y = x + 1\n").is_empty());
+        assert_eq!(messages("let x = 1\nx = 2\nlet y = x + \"a\"\n"), ["Cannot add int and string"]);
+    }
+
+    #[test]
+    fn or_pattern_bindings_of_different_types_are_unknown() {
+        assert!(messages("let f = | (int y, 0) | (0, string y) : y + \"a\"\n        | _ : \"b\"\n").is_empty());
+        assert_eq!(messages("let f = | (int y, 0) | (0, int y) : y + \"a\"\n        | _ : 1\n"), ["Cannot add int and string"]);
+    }
+
+    #[test]
+    fn operators() {
+        assert!(messages("let a = 1 + 2 * 3 - 4 / 5 % 6\nlet b = 1.5 * 2.0\nlet c = \"a\" + \"b\"\nlet d = 1 < 2 && !(3 == 4)\n").is_empty());
+        assert_eq!(messages("let a = 1 - 1.5\n"), ["Cannot subtract int and float"]);
+        assert_eq!(messages("let a = \"a\" < 1\n"), ["Cannot compare string and int"]);
+        assert_eq!(messages("let a = true + false\n"), ["Cannot add bool and bool"]);
+        assert_eq!(messages("let a = 1 && true\n"), ["Cannot AND int and bool"]);
+        assert_eq!(messages("let a = -\"a\"\n"), ["Cannot negate string"]);
+        assert_eq!(messages("let r = 1..\"a\"\n"), ["Cannot create range int and string"]);
+    }
+
+    #[test]
+    fn fields_and_statics() {
+        let person = "type Person:\n  name: string\n  age: int\nimpl Person:\n  count = 0\nlet bob = Person: name: \"Bob\", age: 31\n";
+        assert!(messages(&format!("{}let a = bob.age + 1\nlet c = Person::count + 1\n", person)).is_empty());
+        assert_eq!(messages(&format!("{}let a = bob.name + 1\n", person)), ["Cannot add string and int"]);
+        assert_eq!(messages(&format!("{}let a = bob.height\n", person)), ["Person has no field height"]);
+        assert_eq!(messages(&format!("{}let a = Person::total\n", person)), ["Person has no static member total"]);
+        assert_eq!(messages(&format!("{}let a = (Person: name: 1, age: 2)\n", person)), ["Field name of Person must be string, found int"]);
+        assert_eq!(messages("let a = 1\nlet b = a.name\n"), ["int has no field name"]);
+    }
+
+    #[test]
+    fn builtins() {
+        assert!(messages("let d = {\"a\": 1}\nlet d = (d, \"b\", 2) set\nlet has = (d, \"a\") contains && true\n").is_empty());
+        assert_eq!(messages("let d = {\"a\": 1}\nlet d = (d, 1) remove\n"), ["Mismatched types"]);
+        assert_eq!(messages("let d = {\"a\": 1}\nlet d = (d, \"b\", \"c\") set\n"), ["Mismatched types"]);
+        assert_eq!(messages("let a = 1 assert\n"), ["Mismatched types"]);
+    }
+
+    #[test]
+    fn get_can_give_none() {
+        // A missing key gives none, so the value isn't taken to be the dictionary's value type
+        assert!(messages("let d = {\"a\": 1}\nlet v = (d, \"b\") get\nlet s = v + \"x\"\n").is_empty());
+        assert_eq!(messages("let d = {\"a\": 1}\nlet v = (d, 1) get\n"), ["Mismatched types"]);
+    }
+}
@@ -1,9 +1,12 @@
+use std::collections::BTreeMap;
+
 use mlang::constructs::token::symbol::{builtin_symbols, Symbol};
-use mlang::constructs::variable::Value;
+use mlang::constructs::variable::Key;
 
 use super::environment::Env;
-use super::iter::{MLGIter, CharIter, RangeIter, ListIter, FilterIter, MapIter};
+use super::iter::{MLGIter, CharIter, RangeIter, ListIter, DictIter, FilterIter, MapIter};
 
+use super::Value;
 use crate::prelude::*;
 
 /// Builtin functions
@@ -11,7 +14,15 @@ use crate::prelude::*;
 pub enum Builtin {
     Print,
     Println,
-    Assert
+    Assert,
+    /// `(dict, key) get` is the value for the key, or None if there isn't one
+    Get,
+    /// `(dict, key, value) set` is the dictionary with the key set to the value
+    Set,
+    /// `(dict, key) remove` is the dictionary without the key
+    Remove,
+    /// `(dict, key) contains` is whether the dictionary has the key
+    Contains,
 }
 
 impl Builtin {
@@ -22,8 +33,16 @@ impl Builtin {
             Ok(Builtin::Println)
         } else if *symbol == *builtin_symbols::ASSERT {
             Ok(Builtin::Assert)
+        } else if *symbol == *builtin_symbols::GET {
+            Ok(Builtin::Get)
+        } else if *symbol == *builtin_symbols::SET {
+            Ok(Builtin::Set)
+        } else if *symbol == *builtin_symbols::REMOVE {
+            Ok(Builtin::Remove)
+        } else if *symbol == *builtin_symbols::CONTAINS {
+            Ok(Builtin::Contains)
         } else {
-            exec_err!("Symbol {} is not a builtin function!", symbol)
+            exec_err!(codes::NOT_CALLABLE, None, "Symbol {} is not a builtin function!", symbol)
         }
     }
 
@@ -41,15 +60,58 @@ impl Builtin {
                 match value {
                     Value::Boolean(b) if b => {}
                     _ => {
-                        return Err(ExecutionError("Assertion failed!".to_string()))
+                        return exec_err!(codes::ASSERTION_FAILED, None, "Assertion failed!")
                     }
                 }
                 Ok(Value::None)
             }
+            Self::Get => {
+                let (entries, key) = dict_and_key(&value, "get")?;
+                Ok(entries.get(&key).cloned().unwrap_or(Value::None))
+            }
+            Self::Set => {
+                let Value::Tuple(values) = &value else {
+                    return exec_err!(codes::INVALID_OPERANDS, None, "set expects a (dict, key, value) tuple. Got: {}", value);
+                };
+                let [Value::Dict(entries), key, new_value] = values.as_ref() else {
+                    return exec_err!(codes::INVALID_OPERANDS, None, "set expects a (dict, key, value) tuple. Got: {}", value);
+                };
+                let Some(key) = Key::from_value(key) else {
+                    return exec_err!(codes::INVALID_OPERANDS, None, "Cannot use {} as a dictionary key", key);
+                };
+
+                let mut entries = entries.clone();
+                entries.insert(key, new_value.clone());
+                Ok(Value::Dict(entries))
+            }
+            Self::Remove => {
+                let (entries, key) = dict_and_key(&value, "remove")?;
+                let mut entries = entries.clone();
+                entries.remove(&key);
+                Ok(Value::Dict(entries))
+            }
+            Self::Contains => {
+                let (entries, key) = dict_and_key(&value, "contains")?;
+                Ok(Value::Boolean(entries.contains_key(&key)))
+            }
         }
     }
 }
 
+/// Takes apart the `(dict, key)` argument of a dictionary builtin
+fn dict_and_key<'a>(value: &'a Value, name: &str) -> Result<(&'a BTreeMap<Key, Value>, Key)> {
+    if let Value::Tuple(values) = value {
+        if let [Value::Dict(entries), key] = values.as_ref() {
+            return match Key::from_value(key) {
+                Some(key) => Ok((entries, key)),
+                None => exec_err!(codes::INVALID_OPERANDS, None, "Cannot use {} as a dictionary key", key),
+            };
+        }
+    }
+
+    exec_err!(codes::INVALID_OPERANDS, None, "{} expects a (dict, key) tuple. Got: {}", name, value)
+}
+
 pub trait Iterable {
     fn iter<'a>(&'a self) -> Option<Box<dyn MLGIter + 'a>>;
 }
@@ -69,6 +131,9 @@ impl Iterable for Value {
                 index: 0,
                 list: l
             })),
+            Value::Dict(entries) => Some(Box::from(DictIter {
+                entries: entries.iter()
+            })),
             Value::Filter(val, mat) => {
                 val.iter().map(|iter| {
                     Box::from(FilterIter {
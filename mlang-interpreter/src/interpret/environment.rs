@@ -1,35 +1,151 @@
+use std::cell::RefCell;
 use std::collections::HashMap;
+use std::fmt::{Debug, Display};
+use std::io::Write;
+use std::rc::{Rc, Weak};
 
 use mlang::constructs::token::symbol::builtin_symbols;
-use mlang::constructs::variable::Value; 
-use mlang::constructs::ast::Function;
+use mlang::constructs::token::span::Span;
+use mlang::constructs::ast::MatchArm;
 
+use super::{Function, Value};
 use crate::prelude::*;
 
-pub struct Env<'a> {
-    ident_map: HashMap<String, Value>,
-    parent: Option<&'a Env<'a>>,
+/// The identifiers defined in a file or a call of a match arm. A match keeps the scope it was made in,
+/// so that it can use its identifiers whenever it's called.
+pub(super) struct Scope {
+    ident_map: RefCell<HashMap<String, Value>>,
+    parent: Option<Rc<Scope>>,
+}
+
+impl Scope {
+    fn child(parent: &Rc<Scope>) -> Rc<Scope> {
+        Rc::new(Scope {
+            ident_map: RefCell::new(HashMap::new()),
+            parent: Some(parent.clone()),
+        })
+    }
+
+    /// The innermost scope which defines `name`
+    fn defining(self: &Rc<Scope>, name: &str) -> Option<&Rc<Scope>> {
+        if self.ident_map.borrow().contains_key(name) {
+            Some(self)
+        } else {
+            self.parent.as_ref().and_then(|parent| parent.defining(name))
+        }
+    }
+
+    /// Puts the value in this scope. A closure made in this scope only holds on to it weakly from here on,
+    /// so the two don't keep each other alive.
+    fn insert(self: &Rc<Scope>, name: String, value: Value) {
+        let value = match value {
+            Value::Function(Function::Closure(closure)) => Value::Function(Function::Closure(closure.stored_in(self))),
+            value => value,
+        };
+        self.ident_map.borrow_mut().insert(name, value);
+    }
+
+    fn get(self: &Rc<Scope>, name: &str) -> Option<Value> {
+        match self.ident_map.borrow().get(name)? {
+            Value::Function(Function::Closure(closure)) => Some(Value::Function(Function::Closure(closure.taken_from(self)))),
+            value => Some(value.clone()),
+        }
+    }
+}
+
+/// A match made while the program runs. It keeps the scope it was made in, which its arms look identifiers up in
+/// whenever it's called.
+#[derive(Clone)]
+pub struct Closure {
+    pub(super) arms: Rc<Vec<MatchArm>>,
+    scope: Captured,
+}
+
+#[derive(Clone)]
+enum Captured {
+    Strong(Rc<Scope>),
+    /// The closure is stored in the scope it was made in, like a match which calls itself, which would be a cycle if
+    /// it held on strongly. It can only be got at through that scope, so the scope is still around whenever it's used.
+    Weak(Weak<Scope>),
+}
+
+impl Closure {
+    fn stored_in(self, scope: &Rc<Scope>) -> Closure {
+        match &self.scope {
+            Captured::Strong(captured) if Rc::ptr_eq(captured, scope) => Closure { scope: Captured::Weak(Rc::downgrade(scope)), ..self },
+            _ => self,
+        }
+    }
+
+    fn taken_from(&self, scope: &Rc<Scope>) -> Closure {
+        match &self.scope {
+            Captured::Weak(_) => Closure { arms: self.arms.clone(), scope: Captured::Strong(scope.clone()) },
+            Captured::Strong(_) => self.clone(),
+        }
+    }
+}
+
+impl Debug for Closure {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Closure").field("arms", &self.arms).finish_non_exhaustive()
+    }
+}
+
+impl Display for Closure {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "<Match Statement>")
+    }
+}
+
+/// Where an environment's printed text goes
+pub enum Output {
+    /// Kept until it's taken with [`Env::take_output`]
+    Buffer(String),
+    /// Written to stdout straight away, so a long-running program shows its output as it goes
+    Stdout,
+}
+
+pub struct Env {
+    scope: Rc<Scope>,
     pub(super) break_flag: bool,
-    output: Option<String>
+    output: Rc<RefCell<Output>>,
 }
 
-impl<'a> Default for Env<'a> {
-    fn default() -> Env<'a> {
+impl Default for Env {
+    fn default() -> Env {
         let mut ident_map = HashMap::new();
         // Special values
         ident_map.insert("print".to_string(), Value::Function(Function::Builtin(*builtin_symbols::PRINT)));
         ident_map.insert("println".to_string(), Value::Function(Function::Builtin(*builtin_symbols::PRINTLN)));
         ident_map.insert("assert".to_string(), Value::Function(Function::Builtin(*builtin_symbols::ASSERT)));
+        ident_map.insert("get".to_string(), Value::Function(Function::Builtin(*builtin_symbols::GET)));
+        ident_map.insert("set".to_string(), Value::Function(Function::Builtin(*builtin_symbols::SET)));
+        ident_map.insert("remove".to_string(), Value::Function(Function::Builtin(*builtin_symbols::REMOVE)));
+        ident_map.insert("contains".to_string(), Value::Function(Function::Builtin(*builtin_symbols::CONTAINS)));
         Env {
-            ident_map,
-            parent: None,
+            scope: Rc::new(Scope {
+                ident_map: RefCell::new(ident_map),
+                parent: None,
+            }),
             break_flag: false,
-            output: Some(String::new())
+            output: Rc::new(RefCell::new(Output::Buffer(String::new()))),
         }
     }
 }
 
-impl<'a> Env<'a> {
+impl Env {
+    /// Creates a root environment with the script's command-line arguments bound to `args`.
+    pub fn with_args(args: Vec<String>) -> Env {
+        let mut env = Env::default();
+        env.create_ident("args".to_string(), Value::List(args.into_iter().map(Value::String).collect()));
+        env
+    }
+
+    pub fn with_output(self, output: Output) -> Env {
+        *self.output.borrow_mut() = output;
+        self
+    }
+
     pub(super) fn set_break(&mut self) {
         self.break_flag = true;
     }
@@ -38,69 +154,152 @@ impl<'a> Env<'a> {
         self.break_flag = false;
     }
 
-    pub(super) fn new_child(&'a self) -> Env<'a> {
-        Env {
-            ident_map: HashMap::new(),
-            parent: Some(self),
+    /// Creates an environment for running one of a closure's arms, inside the scope the closure was made in.
+    /// A match which isn't a closure runs inside this environment's scope. Printing still goes to this environment's output.
+    pub(super) fn new_child(&self, closure: Option<&Closure>) -> Result<Env> {
+        let parent = match closure.map(|closure| &closure.scope) {
+            Some(Captured::Strong(scope)) => scope.clone(),
+            Some(Captured::Weak(scope)) => match scope.upgrade() {
+                Some(scope) => scope,
+                None => return exec_err!(codes::INTERNAL, None, "A match outlived the scope it was made in"),
+            },
+            None => self.scope.clone(),
+        };
+
+        Ok(Env {
+            scope: Scope::child(&parent),
             break_flag: false,
-            output: None
+            output: self.output.clone(),
+        })
+    }
+
+    /// Makes the match's arms into a closure over this environment's scope
+    pub(super) fn close(&self, arms: &[MatchArm]) -> Closure {
+        Closure {
+            arms: Rc::new(arms.to_vec()),
+            scope: Captured::Strong(self.scope.clone()),
         }
     }
 
-    pub(super) fn get_ident(&self, name: String) -> Result<&Value> {
-        match self.ident_map.get(&name) {
+    pub(super) fn get_ident(&self, name: &str, span: Span) -> Result<Value> {
+        match self.scope.defining(name).and_then(|scope| scope.get(name)) {
             Some(value) => Ok(value),
-            None => match self.parent {
-                Some(parent) => parent.get_ident(name),
-                None => exec_err!("Identifier {} not found", name)
-            }
+            None => Err(Diagnostic::new(DiagnosticKind::Runtime, codes::UNDEFINED_IDENTIFIER, format!("Identifier {} not found", name))
+                .with_label(span, "not found in this scope")),
         }
     }
 
     pub(super) fn create_ident(&mut self, name: String, value: Value) {
-        self.ident_map.insert(name, value);
+        self.scope.insert(name, value);
     }
 
+    /// Gives the identifier a new value in the scope that defines it, or defines it here if none do
     pub(super) fn set_ident(&mut self, name: String, value: Value) {
-        if let Some(parent) = self.parent {
-            if parent.has_ident(&name) {
-                // You have my permission to cry about this
-                unsafe {
-                    let ptr = parent as *const Env as *mut Env;
-                    (*ptr).set_ident(name, value);
-                }
-                return;
-            }
-        }
-        self.ident_map.insert(name, value);
+        let scope = self.scope.defining(&name).unwrap_or(&self.scope);
+        scope.insert(name, value);
     }
 
     pub fn print(&mut self, text: String) -> Result<()> {
-        if let Some(output) = &mut self.output {
-            output.push_str(&text);
-        } else if let Some(parent) = self.parent {
-            unsafe {
-                let ptr = parent as *const Env as *mut Env;
-                (*ptr).print(text)?;
+        match &mut *self.output.borrow_mut() {
+            Output::Buffer(buffer) => buffer.push_str(&text),
+            Output::Stdout => {
+                // Flush even without a line break, so that `print` shows up before the program waits or fails
+                let mut stdout = std::io::stdout().lock();
+                if let Err(error) = stdout.write_all(text.as_bytes()).and_then(|_| stdout.flush()) {
+                    return exec_err!(codes::OUTPUT_FAILED, None, "Could not print: {}", error);
+                }
             }
-        } else {
-            return exec_err!("No output stream found");
         }
         Ok(())
     }
 
-    pub fn write_to_string(&self, output: &mut String) {
-        if let Some(output_str) = &self.output {
-            output.push_str(output_str);
-        } else if let Some(parent) = self.parent {
-            parent.write_to_string(output);
+    /// Removes and returns everything printed to this environment's output buffer so far.
+    /// Output written straight to stdout can't be taken back, so gives nothing.
+    pub fn take_output(&mut self) -> String {
+        match &mut *self.output.borrow_mut() {
+            Output::Buffer(buffer) => std::mem::take(buffer),
+            Output::Stdout => String::new(),
         }
     }
 
-    pub(super) fn has_ident(&self, name: &str) -> bool {
-        self.ident_map.contains_key(name) || match self.parent {
-            Some(parent) => parent.has_ident(name),
-            None => false,
+    /// The names of every identifier defined in this environment and its parents
+    pub fn identifiers(&self) -> Vec<String> {
+        let mut identifiers = Vec::new();
+        let mut scope = Some(&self.scope);
+        while let Some(current) = scope {
+            identifiers.extend(current.ident_map.borrow().keys().cloned());
+            scope = current.parent.as_ref();
         }
+        identifiers
+    }
+
+    pub(super) fn has_ident(&self, name: &str) -> bool {
+        self.scope.defining(name).is_some()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::program::Program;
+
+    /// Runs the program in a fresh environment, returning what it printed
+    fn run(source: &str, env: &mut Env) -> String {
+        let program = Program::new(source.to_string()).unwrap()
+            .tokenize().unwrap()
+            .parse().unwrap()
+            .verify().unwrap();
+        program.run_in(env).unwrap();
+        env.take_output()
+    }
+
+    #[test]
+    fn matches_keep_their_scope() {
+        let source = "\
+let adder = | n : | x : x + n
+let add_two = 2 adder
+let n = 100
+5 add_two println";
+        assert_eq!(run(source, &mut Env::default()), "7\n");
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn matches_change_identifiers_in_their_scope() {
+        let source = "\
+let make_counter = | _ :
+    let count = 0
+    | _ :
+        count = count + 1
+        count
+let counter = () make_counter
+() counter
+() counter println
+let other = () make_counter
+() other println";
+        assert_eq!(run(source, &mut Env::default()), "2\n1\n");
+    }
+
+    #[test]
+    fn recursion() {
+        let source = "\
+let factorial = | 0 : 1
+                | n : n * (n - 1) factorial
+5 factorial println
+let make = | _ :
+    let countdown = | 0 : \"done\"
+                    | n : (n - 1) countdown
+    countdown
+3 (() make) println";
+        assert_eq!(run(source, &mut Env::default()), "120\ndone\n");
+    }
+
+    #[test]
+    fn recursive_matches_do_not_keep_their_scope_alive() {
+        let mut env = Env::default();
+        run("let factorial = | 0 : 1\n                | n : n * (n - 1) factorial", &mut env);
+
+        let scope = Rc::downgrade(&env.scope);
+        drop(env);
+        assert!(scope.upgrade().is_none(), "The scope and the match stored in it keep each other alive");
+    }
+}
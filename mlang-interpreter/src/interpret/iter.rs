@@ -1,10 +1,11 @@
-use mlang::constructs::variable::Value;
-use mlang::constructs::ast::Function;
+use std::collections::btree_map;
+
+use mlang::constructs::variable::Key;
 
 use crate::prelude::*;
 
 use super::environment::Env;
-use super::call_function;
+use super::{call_function, Function, Value};
 
 pub trait MLGIter {
     fn next(&mut self, env: &mut Env) -> Result<Option<Value>>;
@@ -60,6 +61,19 @@ impl MLGIter for ListIter<'_> {
     }
 }
 
+/// Yields a `(key, value)` tuple for each entry of a dictionary
+pub struct DictIter<'a> {
+    pub(crate) entries: btree_map::Iter<'a, Key, Value>
+}
+
+impl MLGIter for DictIter<'_> {
+    fn next(&mut self, _env: &mut Env) -> Result<Option<Value>> {
+        Ok(self.entries.next().map(|(key, value)| {
+            Value::Tuple(Box::new([Value::from(key.clone()), value.clone()]))
+        }))
+    }
+}
+
 pub struct FilterIter<'a> {
     pub(crate) iter: Box<dyn MLGIter + 'a>,
     pub(crate) func: Function
@@ -75,7 +89,7 @@ impl MLGIter for FilterIter<'_> {
                     continue;
                 }
             } else {
-                return exec_err!("Filter match must return a boolean");
+                return exec_err!(codes::EXPECTED_BOOLEAN, None, "Filter match must return a boolean");
             }
         }
 
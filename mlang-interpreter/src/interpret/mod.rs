@@ -1,11 +1,20 @@
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
+
 use crate::prelude::*;
-use mlang::constructs::variable::Value;
-use mlang::constructs::ast::*;
+use mlang::constructs::variable::{self, Key, Type, TypeBody};
+use mlang::constructs::ast::{self, *};
+use mlang::constructs::token::span::Span;
 
 pub mod iter;
 pub mod builtin;
 pub mod environment;
-use environment::Env;
+use environment::{Closure, Env};
+
+/// A value while the program runs, whose matches are closures over the scope they were made in
+pub type Value = variable::Value<Closure>;
+pub type Function = ast::Function<Closure>;
+pub type TypeDefinition = variable::TypeDefinition<Closure>;
 
 use builtin::{Iterable, Builtin};
 
@@ -15,253 +24,528 @@ pub trait Executable {
 
 impl Executable for Expression {
     fn execute(&self, env: &mut Env) -> Result<Value> {
-        match self {
-            Expression::Literal(literal) => {
-                if let Value::Function(Function::Match { arms }) = literal {
-                    if arms.iter().all(|arm| {
-                        arm.pattern.identifier.is_none()
-                    }) {
-                        for arm in arms {
-                            if matches(&Value::None, arm, env)? {
-                                return arm.block.execute(env);
-                            }
+        // Calls, builtins and comparisons don't know which expression they're for, so their errors point here
+        evaluate(self, env).map_err(|error| error.or_span(self.span))
+    }
+}
+
+/// Works out the value of an expression, for [`Executable::execute`]
+fn evaluate(expr: &Expression, env: &mut Env) -> Result<Value> {
+    match &expr.kind {
+        ExpressionKind::Literal(literal) => {
+            if let variable::Value::Function(ast::Function::Match { arms }) = literal {
+                if arms.iter().all(|arm| {
+                    matches!(arm.pattern.kind, PatternKind::Empty)
+                }) {
+                    for arm in arms {
+                        if matches(&Value::None, arm, env)? {
+                            return arm.block.execute(env);
                         }
-        
-                        return Ok(Value::None);
                     }
+    
+                    return Ok(Value::None);
                 }
-                Ok(literal.clone())
-            },
-            Expression::Identifier(identifier) => {
-                env.get_ident(identifier.name.clone()).map(|v| v.clone())
+
+                return Ok(Value::Function(Function::Closure(env.close(arms))));
             }
-            Expression::Binary(left, operator, right) => {
-                let left = left.execute(env)?;
-                let right = right.execute(env)?;
-
-                match operator {
-                    BinaryOperator::Plus => {
-                        match (&left, &right) {
-                            (Value::Int(left), Value::Int(right)) => Ok(Value::Int(left + right)),
-                            (Value::Float(left), Value::Float(right)) => Ok(Value::Float(left + right)),
-                            (Value::String(left), Value::String(right)) => Ok(Value::String(left.to_owned() + right)),
-                            _ => exec_err!("Cannot add {} and {}", left, right),
-                        }
+            Ok(literal.with_closures())
+        },
+        ExpressionKind::Identifier(identifier) => {
+            env.get_ident(&identifier.name, identifier.span)
+        }
+        ExpressionKind::Interpolated(parts) => {
+            let mut string = String::new();
+            for part in parts {
+                match part {
+                    InterpolatedPart::Text(text) => string.push_str(text),
+                    InterpolatedPart::Expression(expression) => string.push_str(&expression.execute(env)?.to_string()),
+                }
+            }
+
+            Ok(Value::String(string))
+        }
+        ExpressionKind::Tuple(elements) => {
+            let values = elements.iter()
+                .map(|element| element.execute(env))
+                .collect::<Result<_>>()?;
+
+            Ok(Value::Tuple(values))
+        }
+        ExpressionKind::List(elements) => {
+            let values = elements.iter()
+                .map(|element| element.execute(env))
+                .collect::<Result<_>>()?;
+
+            Ok(Value::List(values))
+        }
+        ExpressionKind::Dict(entries) => {
+            let mut dict = BTreeMap::new();
+            for (key_expression, value) in entries {
+                let key = key_expression.execute(env)?;
+                let Some(key) = Key::from_value(&key) else {
+                    return exec_err!(codes::INVALID_OPERANDS, Some(key_expression.span), "Cannot use {} as a dictionary key", key);
+                };
+                dict.insert(key, value.execute(env)?);
+            }
+
+            Ok(Value::Dict(dict))
+        }
+        ExpressionKind::Index(expression, index) => {
+            let value = expression.execute(env)?;
+            let index_value = index.expression.execute(env)?;
+
+            get_index(&value, &index_value, index.span)
+        }
+        ExpressionKind::Construct(name, fields) => {
+            let definition = get_type_definition(name, env)?;
+            let TypeBody::Struct(declared_fields) = &definition.body else {
+                return exec_err!(codes::INVALID_OPERANDS, Some(name.span), "{} has variants instead of fields, so it's made with one of them, like {}::Variant", definition.name, definition.name);
+            };
+
+            let mut values = Vec::new();
+            for (field, expression) in fields {
+                let Some((_, typ)) = declared_fields.iter().find(|(declared, _)| *declared == field.name) else {
+                    return exec_err!(codes::UNKNOWN_FIELD, Some(field.span), "{} has no field {}", definition.name, field.name);
+                };
+                let value = expression.execute(env)?;
+                if !has_type(&value, typ) {
+                    return exec_err!(codes::INVALID_OPERANDS, Some(expression.span), "Field {} of {} must be {}, got {}", field.name, definition.name, typ, value);
+                }
+                values.push((field.name.clone(), value));
+            }
+
+            // Keep the fields in the order they were declared in, whatever order they were given in
+            let mut ordered = Vec::new();
+            for (field, _) in declared_fields {
+                let Some(position) = values.iter().position(|(given, _)| given == field) else {
+                    return exec_err!(codes::MISSING_FIELD, Some(expr.span), "Missing field {} of {}", field, definition.name);
+                };
+                ordered.push(values.swap_remove(position));
+            }
+
+            Ok(Value::Struct(definition.name, ordered))
+        }
+        ExpressionKind::Field(expression, field) => {
+            let value = expression.execute(env)?;
+            match &value {
+                Value::Struct(_, fields) => match fields.iter().find(|(name, _)| *name == field.name) {
+                    Some((_, value)) => Ok(value.clone()),
+                    None => exec_err!(codes::UNKNOWN_FIELD, Some(field.span), "{} has no field {}", value, field.name),
+                },
+                _ => exec_err!(codes::UNKNOWN_FIELD, Some(field.span), "{} has no field {}", value, field.name),
+            }
+        }
+        ExpressionKind::Static(typ, member) => {
+            let definition = get_type_definition(typ, env)?;
+            match definition.statics.get(&member.name) {
+                Some(value) => Ok(value.clone()),
+                None => exec_err!(codes::UNKNOWN_FIELD, Some(member.span), "{} has no static member {}", definition.name, member.name),
+            }
+        }
+        ExpressionKind::Binary(left, operator, right) => {
+            let right_span = right.span;
+            let left = left.execute(env)?;
+            let right = right.execute(env)?;
+
+            match operator {
+                BinaryOperator::Plus => {
+                    match (&left, &right) {
+                        (Value::Int(left), Value::Int(right)) => Ok(Value::Int(left + right)),
+                        (Value::Float(left), Value::Float(right)) => Ok(Value::Float(left + right)),
+                        (Value::String(left), Value::String(right)) => Ok(Value::String(left.to_owned() + right)),
+                        _ => exec_err!(codes::INVALID_OPERANDS, Some(expr.span), "Cannot add {} and {}", left, right),
                     }
-                    BinaryOperator::Minus => {
-                        match (&left, &right) {
-                            (Value::Int(left), Value::Int(right)) => Ok(Value::Int(left - right)),
-                            (Value::Float(left), Value::Float(right)) => Ok(Value::Float(left - right)),
-                            _ => exec_err!("Cannot subtract {} and {}", left, right),
-                        }
+                }
+                BinaryOperator::Minus => {
+                    match (&left, &right) {
+                        (Value::Int(left), Value::Int(right)) => Ok(Value::Int(left - right)),
+                        (Value::Float(left), Value::Float(right)) => Ok(Value::Float(left - right)),
+                        _ => exec_err!(codes::INVALID_OPERANDS, Some(expr.span), "Cannot subtract {} and {}", left, right),
                     }
-                    BinaryOperator::Mul => {
-                        match (&left, &right) {
-                            (Value::Int(left), Value::Int(right)) => Ok(Value::Int(left * right)),
-                            (Value::Float(left), Value::Float(right)) => Ok(Value::Float(left * right)),
-                            _ => exec_err!("Cannot multiply {} and {}", left, right),
-                        }
+                }
+                BinaryOperator::Mul => {
+                    match (&left, &right) {
+                        (Value::Int(left), Value::Int(right)) => Ok(Value::Int(left * right)),
+                        (Value::Float(left), Value::Float(right)) => Ok(Value::Float(left * right)),
+                        _ => exec_err!(codes::INVALID_OPERANDS, Some(expr.span), "Cannot multiply {} and {}", left, right),
                     }
-                    BinaryOperator::Div => {
-                        match (&left, &right) {
-                            (Value::Int(left), Value::Int(right)) => Ok(Value::Int(left / right)),
-                            (Value::Float(left), Value::Float(right)) => Ok(Value::Float(left / right)),
-                            _ => exec_err!("Cannot divide {} and {}", left, right),
-                        }
+                }
+                BinaryOperator::Div => {
+                    match (&left, &right) {
+                        (Value::Int(left), Value::Int(right)) => Ok(Value::Int(left / right)),
+                        (Value::Float(left), Value::Float(right)) => Ok(Value::Float(left / right)),
+                        _ => exec_err!(codes::INVALID_OPERANDS, Some(expr.span), "Cannot divide {} and {}", left, right),
                     }
-                    BinaryOperator::Range => {
-                        match (&left, &right) {
-                            (Value::Int(left), Value::Int(right)) => Ok(Value::IntRange(*left, *right)),
-                            _ => exec_err!("Cannot create range {} and {}", left, right),
-                        }
+                }
+                BinaryOperator::Range => {
+                    match (&left, &right) {
+                        (Value::Int(left), Value::Int(right)) => Ok(Value::IntRange(*left, *right)),
+                        _ => exec_err!(codes::INVALID_OPERANDS, Some(expr.span), "Cannot create range {} and {}", left, right),
                     }
-                    BinaryOperator::ForEach => {
-                        if let func @ Value::Function(_) = right {
-                            if let Some(mut iter) = left.iter() {
-                                while let Some(val) = iter.next(env)? {
-                                    Expression::Call(
-                                        Box::from(Expression::Literal(val)), 
-                                        Box::from(Expression::Literal(func.clone()))
-                                    ).execute(env)?;
-
-                                    if env.break_flag {
-                                        env.reset_break();
-                                        break;
-                                    }
+                }
+                BinaryOperator::ForEach => {
+                    if let Value::Function(func) = right {
+                        if let Some(mut iter) = left.iter() {
+                            while let Some(val) = iter.next(env)? {
+                                call_function(&val, &func, env)?;
+
+                                if env.break_flag {
+                                    env.reset_break();
+                                    break;
                                 }
                             }
-
-                            Ok(Value::None)
-                        } else {
-                            exec_err!("Cannot iterate over {} with {}", left, right)
                         }
+
+                        Ok(Value::None)
+                    } else {
+                        exec_err!(codes::INVALID_OPERANDS, Some(expr.span), "Cannot iterate over {} with {}", left, right)
                     }
-                    
-                    BinaryOperator::Map => {
-                        if let Value::Function(mat) = right {
-                            Ok(Value::Map(Box::from(left), mat))
-                        } else {
-                            exec_err!("Cannot iterate over {} with {}", left, right)
-                        }
+                }
+                
+                BinaryOperator::Map => {
+                    if let Value::Function(mat) = right {
+                        Ok(Value::Map(Box::from(left), mat))
+                    } else {
+                        exec_err!(codes::INVALID_OPERANDS, Some(expr.span), "Cannot iterate over {} with {}", left, right)
                     }
-                    BinaryOperator::Filter => {
-                        if let Value::Function(mat) = right {
-                            Ok(Value::Filter(Box::from(left), mat))
-                        } else {
-                            exec_err!("Cannot filter over {} with {}", left, right)
-                        }
+                }
+                BinaryOperator::Filter => {
+                    if let Value::Function(mat) = right {
+                        Ok(Value::Filter(Box::from(left), mat))
+                    } else {
+                        exec_err!(codes::INVALID_OPERANDS, Some(expr.span), "Cannot filter over {} with {}", left, right)
                     }
-                    BinaryOperator::Mod => {
-                        match (&left, &right) {
-                            (Value::Int(left), Value::Int(right)) => Ok(Value::Int(left % right)),
-                            _ => exec_err!("Cannot modulo {} and {}", left, right),
-                        }
+                }
+                BinaryOperator::Mod => {
+                    match (&left, &right) {
+                        (Value::Int(left), Value::Int(right)) => Ok(Value::Int(left % right)),
+                        _ => exec_err!(codes::INVALID_OPERANDS, Some(expr.span), "Cannot modulo {} and {}", left, right),
                     }
-                    BinaryOperator::NotEqual => {
-                        match (&left, &right) {
-                            (Value::Int(left), Value::Int(right)) => Ok(Value::Boolean(left != right)),
-                            (Value::Float(left), Value::Float(right)) => Ok(Value::Boolean(left != right)),
-                            (Value::String(left), Value::String(right)) => Ok(Value::Boolean(left != right)),
-                            (Value::Boolean(left), Value::Boolean(right)) => Ok(Value::Boolean(left != right)),
-                            _ => exec_err!("Cannot compare {} and {}", left, right),
-                        }
+                }
+                BinaryOperator::NotEqual => {
+                    match (&left, &right) {
+                        (Value::Int(left), Value::Int(right)) => Ok(Value::Boolean(left != right)),
+                        (Value::Float(left), Value::Float(right)) => Ok(Value::Boolean(left != right)),
+                        (Value::String(left), Value::String(right)) => Ok(Value::Boolean(left != right)),
+                        (Value::Boolean(left), Value::Boolean(right)) => Ok(Value::Boolean(left != right)),
+                        (Value::Tuple(_), Value::Tuple(_)) => Ok(Value::Boolean(compare(&left, &right)? != Some(Ordering::Equal))),
+                        _ => exec_err!(codes::INVALID_OPERANDS, Some(expr.span), "Cannot compare {} and {}", left, right),
                     }
-                    BinaryOperator::Equal => {
-                        match (&left, &right) {
-                            (Value::Int(left), Value::Int(right)) => Ok(Value::Boolean(left == right)),
-                            (Value::Float(left), Value::Float(right)) => Ok(Value::Boolean(left == right)),
-                            (Value::String(left), Value::String(right)) => Ok(Value::Boolean(left == right)),
-                            (Value::Boolean(left), Value::Boolean(right)) => Ok(Value::Boolean(left == right)),
-                            _ => exec_err!("Cannot compare {} and {}", left, right),
-                        }
+                }
+                BinaryOperator::Equal => {
+                    match (&left, &right) {
+                        (Value::Int(left), Value::Int(right)) => Ok(Value::Boolean(left == right)),
+                        (Value::Float(left), Value::Float(right)) => Ok(Value::Boolean(left == right)),
+                        (Value::String(left), Value::String(right)) => Ok(Value::Boolean(left == right)),
+                        (Value::Boolean(left), Value::Boolean(right)) => Ok(Value::Boolean(left == right)),
+                        (Value::Tuple(_), Value::Tuple(_)) => Ok(Value::Boolean(compare(&left, &right)? == Some(Ordering::Equal))),
+                        _ => exec_err!(codes::INVALID_OPERANDS, Some(expr.span), "Cannot compare {} and {}", left, right),
                     }
-                    BinaryOperator::Greater => {
-                        match (&left, &right) {
-                            (Value::Int(left), Value::Int(right)) => Ok(Value::Boolean(left > right)),
-                            (Value::Float(left), Value::Float(right)) => Ok(Value::Boolean(left > right)),
-                            (Value::String(left), Value::String(right)) => Ok(Value::Boolean(left > right)),
-                            _ => exec_err!("Cannot compare {} and {}", left, right),
-                        }
+                }
+                BinaryOperator::Greater => {
+                    match (&left, &right) {
+                        (Value::Int(left), Value::Int(right)) => Ok(Value::Boolean(left > right)),
+                        (Value::Float(left), Value::Float(right)) => Ok(Value::Boolean(left > right)),
+                        (Value::String(left), Value::String(right)) => Ok(Value::Boolean(left > right)),
+                        (Value::Tuple(_), Value::Tuple(_)) => Ok(Value::Boolean(compare(&left, &right)? == Some(Ordering::Greater))),
+                        _ => exec_err!(codes::INVALID_OPERANDS, Some(expr.span), "Cannot compare {} and {}", left, right),
                     }
-                    BinaryOperator::GreaterEqual => {
-                        match (&left, &right) {
-                            (Value::Int(left), Value::Int(right)) => Ok(Value::Boolean(left >= right)),
-                            (Value::Float(left), Value::Float(right)) => Ok(Value::Boolean(left >= right)),
-                            (Value::String(left), Value::String(right)) => Ok(Value::Boolean(left >= right)),
-                            _ => exec_err!("Cannot compare {} and {}", left, right),
-                        }
+                }
+                BinaryOperator::GreaterEqual => {
+                    match (&left, &right) {
+                        (Value::Int(left), Value::Int(right)) => Ok(Value::Boolean(left >= right)),
+                        (Value::Float(left), Value::Float(right)) => Ok(Value::Boolean(left >= right)),
+                        (Value::String(left), Value::String(right)) => Ok(Value::Boolean(left >= right)),
+                        (Value::Tuple(_), Value::Tuple(_)) => Ok(Value::Boolean(matches!(compare(&left, &right)?, Some(Ordering::Greater | Ordering::Equal)))),
+                        _ => exec_err!(codes::INVALID_OPERANDS, Some(expr.span), "Cannot compare {} and {}", left, right),
                     }
-                    BinaryOperator::Less => {
-                        match (&left, &right) {
-                            (Value::Int(left), Value::Int(right)) => Ok(Value::Boolean(left < right)),
-                            (Value::Float(left), Value::Float(right)) => Ok(Value::Boolean(left < right)),
-                            (Value::String(left), Value::String(right)) => Ok(Value::Boolean(left < right)),
-                            _ => exec_err!("Cannot compare {} and {}", left, right),
-                        }
+                }
+                BinaryOperator::Less => {
+                    match (&left, &right) {
+                        (Value::Int(left), Value::Int(right)) => Ok(Value::Boolean(left < right)),
+                        (Value::Float(left), Value::Float(right)) => Ok(Value::Boolean(left < right)),
+                        (Value::String(left), Value::String(right)) => Ok(Value::Boolean(left < right)),
+                        (Value::Tuple(_), Value::Tuple(_)) => Ok(Value::Boolean(compare(&left, &right)? == Some(Ordering::Less))),
+                        _ => exec_err!(codes::INVALID_OPERANDS, Some(expr.span), "Cannot compare {} and {}", left, right),
                     }
-                    BinaryOperator::LessEqual => {
-                        match (&left, &right) {
-                            (Value::Int(left), Value::Int(right)) => Ok(Value::Boolean(left <= right)),
-                            (Value::Float(left), Value::Float(right)) => Ok(Value::Boolean(left <= right)),
-                            (Value::String(left), Value::String(right)) => Ok(Value::Boolean(left <= right)),
-                            _ => exec_err!("Cannot compare {} and {}", left, right),
-                        }
+                }
+                BinaryOperator::LessEqual => {
+                    match (&left, &right) {
+                        (Value::Int(left), Value::Int(right)) => Ok(Value::Boolean(left <= right)),
+                        (Value::Float(left), Value::Float(right)) => Ok(Value::Boolean(left <= right)),
+                        (Value::String(left), Value::String(right)) => Ok(Value::Boolean(left <= right)),
+                        (Value::Tuple(_), Value::Tuple(_)) => Ok(Value::Boolean(matches!(compare(&left, &right)?, Some(Ordering::Less | Ordering::Equal)))),
+                        _ => exec_err!(codes::INVALID_OPERANDS, Some(expr.span), "Cannot compare {} and {}", left, right),
                     }
-                    BinaryOperator::And => {
-                        match (&left, &right) {
-                            (Value::Boolean(left), Value::Boolean(right)) => Ok(Value::Boolean(*left && *right)),
-                            _ => exec_err!("Cannot AND {} and {}", left, right),
-                        }
+                }
+                BinaryOperator::And => {
+                    match (&left, &right) {
+                        (Value::Boolean(left), Value::Boolean(right)) => Ok(Value::Boolean(*left && *right)),
+                        _ => exec_err!(codes::INVALID_OPERANDS, Some(expr.span), "Cannot AND {} and {}", left, right),
                     }
-                    BinaryOperator::Or => {
-                        match (&left, &right) {
-                            (Value::Boolean(left), Value::Boolean(right)) => Ok(Value::Boolean(*left || *right)),
-                            _ => exec_err!("Cannot OR {} and {}", left, right),
-                        }
+                }
+                BinaryOperator::Or => {
+                    match (&left, &right) {
+                        (Value::Boolean(left), Value::Boolean(right)) => Ok(Value::Boolean(*left || *right)),
+                        _ => exec_err!(codes::INVALID_OPERANDS, Some(expr.span), "Cannot OR {} and {}", left, right),
                     }
-                    BinaryOperator::All => {
-                        if let Some(mut iter) = left.iter() {
-                            while let Some(val) = iter.next(env)? {
-                                if let Value::Boolean(bl) = Expression::Call(
-                                    Box::from(Expression::Literal(val.clone())), 
-                                    Box::from(Expression::Literal(right.clone()))
-                                ).execute(env)? {
-                                    if !bl {
-                                        return Ok(Value::Boolean(false));
-                                    }
-                                } else {
-                                    return exec_err!("Filter must return a boolean!");
+                }
+                BinaryOperator::All => {
+                    if let Some(mut iter) = left.iter() {
+                        while let Some(val) = iter.next(env)? {
+                            if let Value::Boolean(bl) = call_value(&val, &right, env)? {
+                                if !bl {
+                                    return Ok(Value::Boolean(false));
                                 }
+                            } else {
+                                return exec_err!(codes::EXPECTED_BOOLEAN, Some(right_span), "Filter must return a boolean!");
                             }
-                            Ok(Value::Boolean(true))
-                        } else {
-                            exec_err!("Cannot &&& over {} with {}", left, right)
                         }
+                        Ok(Value::Boolean(true))
+                    } else {
+                        exec_err!(codes::INVALID_OPERANDS, Some(expr.span), "Cannot &&& over {} with {}", left, right)
                     }
-                    BinaryOperator::Any => {
-                        if let Some(mut iter) = left.iter() {
-                            while let Some(val) = iter.next(env)? {
-                                if let Value::Boolean(bl) = Expression::Call(
-                                    Box::from(Expression::Literal(val.clone())), 
-                                    Box::from(Expression::Literal(right.clone()))
-                                ).execute(env)? {
-                                    if bl {
-                                        return Ok(Value::Boolean(true));
-                                    }
-                                } else {
-                                    return exec_err!("Filter must return a boolean!");
+                }
+                BinaryOperator::Any => {
+                    if let Some(mut iter) = left.iter() {
+                        while let Some(val) = iter.next(env)? {
+                            if let Value::Boolean(bl) = call_value(&val, &right, env)? {
+                                if bl {
+                                    return Ok(Value::Boolean(true));
                                 }
+                            } else {
+                                return exec_err!(codes::EXPECTED_BOOLEAN, Some(right_span), "Filter must return a boolean!");
                             }
-                            Ok(Value::Boolean(false))
-                        } else {
-                            exec_err!("Cannot ||| over {} with {}", left, right)
                         }
+                        Ok(Value::Boolean(false))
+                    } else {
+                        exec_err!(codes::INVALID_OPERANDS, Some(expr.span), "Cannot ||| over {} with {}", left, right)
                     }
                 }
             }
-            Expression::Unary(operator, expression) => {
-                let expression = expression.execute(env)?;
-
-                match operator {
-                    UnaryOperator::Minus => {
-                        match expression {
-                            Value::Int(value) => Ok(Value::Int(-value)),
-                            Value::Float(value) => Ok(Value::Float(-value)),
-                            _ => exec_err!("Cannot negate {}", expression),
-                        }
+        }
+        ExpressionKind::Unary(operator, expression) => {
+            let expression = expression.execute(env)?;
+
+            match operator {
+                UnaryOperator::Minus => {
+                    match expression {
+                        Value::Int(value) => Ok(Value::Int(-value)),
+                        Value::Float(value) => Ok(Value::Float(-value)),
+                        _ => exec_err!(codes::INVALID_OPERANDS, Some(expr.span), "Cannot negate {}", expression),
                     }
-                    UnaryOperator::Not => {
-                        match expression {
-                            Value::Boolean(value) => Ok(Value::Boolean(!value)),
-                            _ => exec_err!("Cannot negate {}", expression),
-                        }
+                }
+                UnaryOperator::Not => {
+                    match expression {
+                        Value::Boolean(value) => Ok(Value::Boolean(!value)),
+                        _ => exec_err!(codes::INVALID_OPERANDS, Some(expr.span), "Cannot negate {}", expression),
                     }
                 }
             }
-            Expression::Call(expression, callee) => {
-                match callee.execute(env)? {
-                    Value::Function(func) => call_function(&expression.execute(env)?, &func, env),
-                    unknown => {
-                        exec_err!("Cannot call {:?}", unknown)
-                    }
+        }
+        ExpressionKind::Call(expression, callee) => {
+            match callee.execute(env)? {
+                Value::Function(func) => call_function(&expression.execute(env)?, &func, env),
+                unknown => {
+                    exec_err!(codes::NOT_CALLABLE, Some(callee.span), "Cannot call {:?}", unknown)
                 }
             }
         }
     }
 }
 
+/// Orders two values of the same type. Tuples are compared element by element, from left to right.
+/// Returns `None` if the values are unordered, like a NaN float.
+fn compare(left: &Value, right: &Value) -> Result<Option<Ordering>> {
+    match (left, right) {
+        (Value::Int(left), Value::Int(right)) => Ok(left.partial_cmp(right)),
+        (Value::Float(left), Value::Float(right)) => Ok(left.partial_cmp(right)),
+        (Value::String(left), Value::String(right)) => Ok(left.partial_cmp(right)),
+        (Value::Boolean(left), Value::Boolean(right)) => Ok(left.partial_cmp(right)),
+        (Value::Tuple(left_values), Value::Tuple(right_values)) => {
+            if left_values.len() != right_values.len() {
+                return exec_err!(codes::INVALID_OPERANDS, None, "Cannot compare {} and {}, since they have different lengths", left, right);
+            }
+
+            for (left, right) in left_values.iter().zip(right_values.iter()) {
+                match compare(left, right)? {
+                    Some(Ordering::Equal) => {}
+                    ordering => return Ok(ordering),
+                }
+            }
+
+            Ok(Some(Ordering::Equal))
+        }
+        _ => exec_err!(codes::INVALID_OPERANDS, None, "Cannot compare {} and {}", left, right),
+    }
+}
+
+fn get_index(value: &Value, index: &Value, span: Span) -> Result<Value> {
+    match (value, index) {
+        (Value::List(values), Value::Int(index)) => {
+            Ok(values[resolve_index(*index, values.len(), span)?].clone())
+        }
+        (Value::List(values), Value::IntRange(start, end)) => {
+            Ok(Value::List(values[resolve_slice(*start, *end, values.len(), span)?].to_vec()))
+        }
+        (Value::String(string), Value::Int(index)) => {
+            let chars: Vec<char> = string.chars().collect();
+            Ok(Value::String(chars[resolve_index(*index, chars.len(), span)?].to_string()))
+        }
+        (Value::String(string), Value::IntRange(start, end)) => {
+            let chars: Vec<char> = string.chars().collect();
+            Ok(Value::String(chars[resolve_slice(*start, *end, chars.len(), span)?].iter().collect()))
+        }
+        (Value::Dict(entries), key) => {
+            match Key::from_value(key).and_then(|key| entries.get(&key)) {
+                Some(value) => Ok(value.clone()),
+                None => Err(Diagnostic::new(DiagnosticKind::Runtime, codes::KEY_NOT_FOUND, format!("Key {} is not in the dictionary", key))
+                    .with_label(span, "not found")
+                    .with_help("Use (dict, key) get to get None for a missing key")),
+            }
+        }
+        _ => Err(Diagnostic::new(DiagnosticKind::Runtime, codes::INVALID_OPERANDS, format!("Cannot index {} with {}", value, index))
+            .with_label(span, "index used here")),
+    }
+}
+
+/// One step into the value being assigned to, with its index worked out
+enum Step {
+    Index(Value, Span),
+    Field(String, Span),
+    Static(String, Span),
+}
+
+/// Sets the part of `target` found by following every step in turn
+fn set_place(target: &mut Value, steps: &[Step], value: Value, env: &Env) -> Result<()> {
+    let Some((step, rest)) = steps.split_first() else {
+        *target = value;
+        return Ok(());
+    };
+
+    match (target, step) {
+        (Value::List(values), Step::Index(Value::Int(index), span)) => {
+            let index = resolve_index(*index, values.len(), *span)?;
+            set_place(&mut values[index], rest, value, env)
+        }
+        (Value::Dict(entries), Step::Index(index, span)) => {
+            let Some(key) = Key::from_value(index) else {
+                return Err(Diagnostic::new(DiagnosticKind::Runtime, codes::INVALID_OPERANDS, format!("Cannot use {} as a dictionary key", index))
+                    .with_label(*span, "index used here"));
+            };
+            if rest.is_empty() {
+                entries.insert(key, value);
+                return Ok(());
+            }
+
+            match entries.get_mut(&key) {
+                Some(entry) => set_place(entry, rest, value, env),
+                None => Err(Diagnostic::new(DiagnosticKind::Runtime, codes::KEY_NOT_FOUND, format!("Key {} is not in the dictionary", index))
+                    .with_label(*span, "not found")),
+            }
+        }
+        (Value::Struct(name, fields), Step::Field(field, span)) => match fields.iter_mut().find(|(declared, _)| declared == field) {
+            Some((_, entry)) => {
+                // A field keeps the type it was declared with, as when the struct was constructed
+                if rest.is_empty() {
+                    if let Ok(Value::Type(definition)) = env.get_ident(name, *span) {
+                        if let TypeBody::Struct(declared_fields) = &definition.body {
+                            if let Some((_, typ)) = declared_fields.iter().find(|(declared, _)| declared == field) {
+                                if !has_type(&value, typ) {
+                                    return Err(Diagnostic::new(DiagnosticKind::Runtime, codes::INVALID_OPERANDS, format!("Field {} of {} must be {}, got {}", field, name, typ, value))
+                                        .with_label(*span, "assigned to here"));
+                                }
+                            }
+                        }
+                    }
+                }
+                set_place(entry, rest, value, env)
+            }
+            None => Err(Diagnostic::new(DiagnosticKind::Runtime, codes::UNKNOWN_FIELD, format!("{} has no field {}", name, field))
+                .with_label(*span, "no such field")),
+        },
+        (Value::Type(definition), Step::Static(member, span)) => match definition.statics.get_mut(member) {
+            Some(entry) => set_place(entry, rest, value, env),
+            None => Err(Diagnostic::new(DiagnosticKind::Runtime, codes::UNKNOWN_FIELD, format!("{} has no static member {}", definition.name, member))
+                .with_label(*span, "no such static member")),
+        },
+        (target, Step::Index(index, span)) => Err(Diagnostic::new(DiagnosticKind::Runtime, codes::INVALID_OPERANDS, format!("Cannot assign to index {} of {}", index, target))
+            .with_label(*span, "index used here")),
+        (target, Step::Field(field, span)) => Err(Diagnostic::new(DiagnosticKind::Runtime, codes::UNKNOWN_FIELD, format!("{} has no field {}", target, field))
+            .with_label(*span, "no such field")),
+        (target, Step::Static(member, span)) => Err(Diagnostic::new(DiagnosticKind::Runtime, codes::INVALID_OPERANDS, format!("Cannot assign to static member {} of {}, since it isn't a type", member, target))
+            .with_label(*span, "assigned to here")),
+    }
+}
+
+/// Looks up a type declared with `type` by its name
+fn get_type_definition(name: &Identifier, env: &Env) -> Result<TypeDefinition> {
+    match env.get_ident(&name.name, name.span)? {
+        Value::Type(definition) => Ok(*definition),
+        value => exec_err!(codes::INVALID_OPERANDS, Some(name.span), "{} is not a type", value),
+    }
+}
+
+/// Converts an index which may count back from the end, like `-1`, to a position in a sequence of length `len`
+fn resolve_index(index: isize, len: usize, span: Span) -> Result<usize> {
+    let position = if index < 0 { index + len as isize } else { index };
+
+    if (0..len as isize).contains(&position) {
+        Ok(position as usize)
+    } else {
+        Err(Diagnostic::new(DiagnosticKind::Runtime, codes::INDEX_OUT_OF_BOUNDS, format!("Index {} is out of bounds for a length of {}", index, len))
+            .with_label(span, "out of bounds"))
+    }
+}
+
+/// Like [`resolve_index`], but for the start and exclusive end of a slice
+fn resolve_slice(start: isize, end: isize, len: usize, span: Span) -> Result<std::ops::Range<usize>> {
+    let resolve = |index: isize| if index < 0 { index + len as isize } else { index };
+    let (from, to) = (resolve(start), resolve(end));
+
+    if 0 <= from && from <= to && to <= len as isize {
+        Ok(from as usize..to as usize)
+    } else {
+        Err(Diagnostic::new(DiagnosticKind::Runtime, codes::INDEX_OUT_OF_BOUNDS, format!("Slice {}..{} is out of bounds for a length of {}", start, end, len))
+            .with_label(span, "out of bounds"))
+    }
+}
+
+/// Calls `callee` with `value`, if it can be called
+fn call_value(value: &Value, callee: &Value, env: &mut Env) -> Result<Value> {
+    match callee {
+        Value::Function(func) => call_function(value, func, env),
+        unknown => exec_err!(codes::NOT_CALLABLE, None, "Cannot call {:?}", unknown),
+    }
+}
+
 pub fn call_function(value: &Value, function: &Function, env: &mut Env) -> Result<Value> {
     match function {
         Function::Builtin(b) => Builtin::from(b)?.execute(value.clone(), env),
-        Function::Match { arms } => {
-            get_result_from_match(value, arms, env)
+        Function::Match { arms } => get_result_from_match(value, arms, None, env),
+        Function::Closure(closure) => get_result_from_match(value, &closure.arms, Some(closure), env),
+        Function::Constructor { typ, variant, payload } => {
+            // A payload of several values is passed as a tuple, like any other arguments
+            let values = match value {
+                Value::Tuple(values) if payload.len() > 1 => values.clone(),
+                value => Box::new([value.clone()]) as Box<[Value]>,
+            };
+
+            if values.len() != payload.len() || !values.iter().zip(payload).all(|(value, typ)| has_type(value, typ)) {
+                let expected = match payload.as_slice() {
+                    [typ] => typ.to_string(),
+                    types => format!("({})", types.iter().map(Type::to_string).collect::<Vec<_>>().join(", ")),
+                };
+                return exec_err!(codes::INVALID_OPERANDS, None, "{}::{} takes {}, got {}", typ, variant, expected, value);
+            }
+            Ok(Value::Variant(typ.clone(), variant.clone(), values))
         }
     }
 }
 
-fn get_result_from_match(value: &Value, arms: &Vec<MatchArm>, env: &mut Env) -> Result<Value> {
+fn get_result_from_match(value: &Value, arms: &[MatchArm], closure: Option<&Closure>, env: &mut Env) -> Result<Value> {
     for arm in arms {
-        let mut inner_env = env.new_child();
+        let mut inner_env = env.new_child(closure)?;
 
         if matches(value, arm, &mut inner_env)? {
             let result = arm.block.execute(&mut inner_env);
@@ -278,27 +562,76 @@ fn get_result_from_match(value: &Value, arms: &Vec<MatchArm>, env: &mut Env) ->
 
 fn matches(value: &Value, arm: &MatchArm, inner_env: &mut Env) -> Result<bool> {
     // Match pattern
-    if let Some(ident) = &arm.pattern.identifier {
-        if let Some(typ) = &arm.pattern.typ {
-            if *typ != value.get_type() {
-                return Ok(false);
-            }
-        }
-
-        inner_env.create_ident(ident.name.clone(), value.clone());
+    if !bind_pattern(value, &arm.pattern.kind, inner_env) {
+        return Ok(false);
     }
 
     // Match guard
     if let Some(guard) = &arm.pattern.guard {
         match guard.execute(inner_env)? {
             Value::Boolean(b) => return Ok(b),
-            o => return exec_err!("Guard must return a boolean. Got: {}", o),
+            o => return exec_err!(codes::EXPECTED_BOOLEAN, Some(guard.span), "Guard must return a boolean. Got: {}", o),
         }
     }
 
     Ok(true)
 }
 
+/// Checks the value against the pattern, creating an identifier for each binding in it
+fn bind_pattern(value: &Value, pattern: &PatternKind, inner_env: &mut Env) -> bool {
+    match pattern {
+        PatternKind::Empty | PatternKind::Wildcard => true,
+        // Values of different types are never equal, rather than being an error
+        PatternKind::Literal(literal) => matches!(compare(value, &literal.with_closures()), Ok(Some(Ordering::Equal))),
+        PatternKind::Range { start, end, inclusive } => {
+            let after_start = matches!(compare(value, &start.with_closures()), Ok(Some(Ordering::Greater | Ordering::Equal)));
+            let before_end = match compare(value, &end.with_closures()) {
+                Ok(Some(Ordering::Less)) => true,
+                Ok(Some(Ordering::Equal)) => *inclusive,
+                _ => false,
+            };
+            after_start && before_end
+        }
+        PatternKind::Or(alternatives) => alternatives.iter().any(|alternative| bind_pattern(value, alternative, inner_env)),
+        PatternKind::Binding { typ, identifier } => {
+            if let Some(typ) = typ {
+                if !has_type(value, typ) {
+                    return false;
+                }
+            }
+
+            inner_env.create_ident(identifier.name.clone(), value.clone());
+            true
+        }
+        PatternKind::Tuple(elements) => match value {
+            Value::Tuple(values) if values.len() == elements.len() => {
+                values.iter().zip(elements).all(|(value, element)| bind_pattern(value, element, inner_env))
+            }
+            _ => false,
+        },
+        PatternKind::Variant { typ, variant, payload } => match value {
+            Value::Variant(value_type, value_variant, values) => {
+                *value_type == typ.name && *value_variant == variant.name && values.len() == payload.len()
+                    && values.iter().zip(payload).all(|(value, element)| bind_pattern(value, element, inner_env))
+            }
+            _ => false,
+        },
+    }
+}
+
+fn has_type(value: &Value, typ: &Type) -> bool {
+    match (value, typ) {
+        // `tuple` matches tuples of any length
+        (Value::Tuple(_), Type::Tuple(types)) if types.is_empty() => true,
+        (Value::Tuple(values), Type::Tuple(types)) => values.len() == types.len() && values.iter().zip(types).all(|(value, typ)| has_type(value, typ)),
+        (Value::List(values), Type::List(element)) => values.iter().all(|value| has_type(value, element)),
+        (Value::Dict(entries), Type::Dict(key_type, value_type)) => entries.iter().all(|(key, value)| {
+            has_type(&Value::from(key.clone()), key_type) && has_type(value, value_type)
+        }),
+        _ => *typ == value.get_type(),
+    }
+}
+
 impl Executable for Block {
     fn execute(&self, env: &mut Env) -> Result<Value> {
         for (idx, statement) in self.statements.iter().enumerate() {
@@ -327,7 +660,8 @@ impl Executable for Block {
             }
         }
 
-        unreachable!()
+        // Only an empty program has no statements
+        Ok(Value::None)
     }
 }
 
@@ -337,7 +671,7 @@ impl Executable for Statement {
             Statement::Expression(expression) => expression.execute(env),
             Statement::Let(identifier, expression) => {
                 if env.has_ident(&identifier.name) {
-                    exec_err!("Identifier {} already exists", identifier.name)
+                    exec_err!(codes::DUPLICATE_IDENTIFIER, Some(identifier.span), "Identifier {} already exists", identifier.name)
                 } else {
                     let value = expression.execute(env)?;
                     
@@ -348,7 +682,7 @@ impl Executable for Statement {
             Statement::Return(expression) => expression.execute(env),
             Statement::Set(identifier, expression) => {
                 if !env.has_ident(&identifier.name) {
-                    exec_err!("Identifier {} does not exist", identifier.name)
+                    exec_err!(codes::UNDEFINED_IDENTIFIER, Some(identifier.span), "Identifier {} does not exist", identifier.name)
                 } else {
                     let value = expression.execute(env)?;
                     
@@ -356,7 +690,247 @@ impl Executable for Statement {
                     Ok(Value::None)
                 }
             }
+            Statement::SetPlace(identifier, accessors, expression) => {
+                let mut target = env.get_ident(&identifier.name, identifier.span)?;
+                let steps = accessors.iter()
+                    .map(|accessor| Ok(match accessor {
+                        Accessor::Index(index) => Step::Index(index.expression.execute(env)?, index.span),
+                        Accessor::Field(field) => Step::Field(field.name.clone(), field.span),
+                        Accessor::Static(member) => Step::Static(member.name.clone(), member.span),
+                    }))
+                    .collect::<Result<Vec<_>>>()?;
+                let value = expression.execute(env)?;
+
+                set_place(&mut target, &steps, value, env)?;
+                env.set_ident(identifier.name.clone(), target);
+                Ok(Value::None)
+            }
+            Statement::Type(declaration) => {
+                if env.has_ident(&declaration.name.name) {
+                    return exec_err!(codes::DUPLICATE_IDENTIFIER, Some(declaration.name.span), "Identifier {} already exists", declaration.name.name);
+                }
+
+                let name = declaration.name.name.clone();
+                let mut statics = BTreeMap::new();
+                if let TypeBody::Enum(variants) = &declaration.body {
+                    // Variants are made through the type, like `Shape::Empty` or `2.0 Shape::Circle`
+                    for (variant, payload) in variants {
+                        let value = if payload.is_empty() {
+                            Value::Variant(name.clone(), variant.clone(), Box::new([]))
+                        } else {
+                            Value::Function(Function::Constructor { typ: name.clone(), variant: variant.clone(), payload: payload.clone() })
+                        };
+                        statics.insert(variant.clone(), value);
+                    }
+                }
+
+                let definition = TypeDefinition {
+                    name,
+                    body: declaration.body.clone(),
+                    statics,
+                };
+                env.set_ident(definition.name.clone(), Value::Type(Box::new(definition)));
+                Ok(Value::None)
+            }
+            Statement::Impl(typ, members) => {
+                for (member, expression) in members {
+                    let value = expression.execute(env)?;
+
+                    // Looked up again for each member, in case an earlier one changed the type
+                    let mut definition = get_type_definition(typ, env)?;
+                    if definition.statics.contains_key(&member.name) {
+                        return exec_err!(codes::DUPLICATE_IDENTIFIER, Some(member.span), "{} already has a static member {}", definition.name, member.name);
+                    }
+                    definition.statics.insert(member.name.clone(), value);
+                    env.set_ident(typ.name.clone(), Value::Type(Box::new(definition)));
+                }
+                Ok(Value::None)
+            }
+            Statement::Error(err) => Err(err.clone()),
             _ => unreachable!("Breaks and continues should be handled in the block execute function"),
         }
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::program::Program;
+
+    /// Runs the program, returning what it printed and how it ended
+    fn run(source: &str) -> (String, Result<Value>) {
+        let program = Program::new(source.to_string()).unwrap()
+            .parse_and_verify()
+            .unwrap_or_else(|error| panic!("The test's source should verify: {}", error));
+        let mut env = Env::default();
+        let result = program.ast().0.execute(&mut env);
+        (env.take_output(), result)
+    }
+
+    fn output(source: &str) -> String {
+        let (output, result) = run(source);
+        if let Err(error) = result {
+            panic!("The test's source failed with {:?}", error);
+        }
+        output
+    }
+
+    #[test]
+    fn tuple_patterns() {
+        let source = "\
+let f = | (int a, (b, string c)) : \"{a} {b} {c}\"
+        | tuple t : \"another tuple\"
+        | _ : \"not a tuple\"
+(1, (2.5, \"x\")) f println
+(1, (2.5, 3)) f println
+(1, 2, 3) f println
+5 f println";
+        assert_eq!(output(source), "1 2.5 x\nanother tuple\nanother tuple\nnot a tuple\n");
+    }
+
+    #[test]
+    fn fields_keep_their_declared_type() {
+        let source = "\
+type Person: name: string, age: int
+let p = Person: name: \"Ann\", age: 30
+p.age = 31
+p.age println
+p.age = \"x\"";
+        let (output, result) = run(source);
+        assert_eq!(output, "31\n");
+        let error = result.unwrap_err();
+        assert_eq!(error.code, codes::INVALID_OPERANDS);
+        assert_eq!(error.message, "Field age of Person must be int, got x");
+    }
+
+    #[test]
+    fn variant_payloads() {
+        let source = "\
+type Shape: Circle(float) | Rect(float, float)
+2.0 Shape::Circle println
+let size = (1.0, 2.0)
+size Shape::Rect println
+size = 5
+size Shape::Rect";
+        let (output, result) = run(source);
+        assert_eq!(output, "Shape::Circle(2)\nShape::Rect(1, 2)\n");
+        assert_eq!(result.unwrap_err().message, "Shape::Rect takes (float, float), got 5");
+    }
+
+    #[test]
+    fn tuple_display() {
+        let source = "\
+(1, \"a\", 2.5) println
+(1,) println
+((1, 2), (3,)) println
+() println
+(1) println";
+        assert_eq!(output(source), "(1, a, 2.5)\n(1,)\n((1, 2), (3,))\nNone\n1\n");
+    }
+
+    #[test]
+    fn tuple_comparison() {
+        let source = "\
+((1, 2) < (1, 3)) println
+((1, 9) < (2, 0)) println
+((2, \"b\") > (2, \"a\")) println
+((1, 2) == (1, 2)) println
+((1, (2, 3)) < (1, (2, 4))) println";
+        assert_eq!(output(source), "true\ntrue\ntrue\ntrue\ntrue\n");
+
+        // Tuples of different lengths can't be compared, which is caught when running if it can't be before
+        let source = "\
+let t = (1, 2)
+t = (1, 2, 3)
+(1, 2) < t";
+        let error = run(source).1.unwrap_err();
+        assert_eq!(error.message, "Cannot compare (1, 2) and (1, 2, 3), since they have different lengths");
+    }
+
+    /// Runs a program which should fail, returning its error's message and the source its span points at
+    fn error(source: &str) -> (String, String) {
+        let error = run(source).1.unwrap_err();
+        let span = error.span().expect("The error should point at the source");
+        (error.message.clone(), source[span.beginning()..span.beginning() + span.length()].to_string())
+    }
+
+    #[test]
+    fn list_indexing() {
+        let source = "\
+let xs = [1, 2, 3, 4]
+xs[0] println
+xs[-1] println
+xs[-4] println
+xs[1..3] println
+xs[0..0] println
+xs[-3..-1] println
+\"héllo\"[1] println
+\"héllo\"[1..3] println";
+        assert_eq!(output(source), "1\n4\n1\n[2, 3]\n[]\n[2, 3]\né\nél\n");
+
+        assert_eq!(error("let xs = [1, 2]\nxs[2]"), ("Index 2 is out of bounds for a length of 2".to_string(), "2".to_string()));
+        assert_eq!(error("let xs = [1, 2]\nxs[-3]"), ("Index -3 is out of bounds for a length of 2".to_string(), "-3".to_string()));
+        assert_eq!(error("let xs = [1, 2]\nxs[1..3]"), ("Slice 1..3 is out of bounds for a length of 2".to_string(), "1..3".to_string()));
+        assert_eq!(error("let xs = [1, 2]\nxs[2..1]"), ("Slice 2..1 is out of bounds for a length of 2".to_string(), "2..1".to_string()));
+    }
+
+    #[test]
+    fn list_assignment() {
+        let source = "\
+let xs = [1, 2, 3]
+xs[0] = 10
+xs[-1] = 30
+xs println
+let grid = [[1, 2], [3, 4]]
+grid[1][0] = 5
+grid println";
+        assert_eq!(output(source), "[10, 2, 30]\n[[1, 2], [5, 4]]\n");
+
+        assert_eq!(error("let xs = [1, 2]\nxs[2] = 3"), ("Index 2 is out of bounds for a length of 2".to_string(), "2".to_string()));
+        assert_eq!(error("let xs = [1, 2]\nxs[-3] = 3"), ("Index -3 is out of bounds for a length of 2".to_string(), "-3".to_string()));
+    }
+
+    #[test]
+    fn dict_literals_and_iteration() {
+        let source = "\
+let ages = {\"bob\": 31, \"alice\": 27}
+ages println
+ages[\"bob\"] println
+ages[\"carol\"] = 45
+ages[\"bob\"] = 32
+ages $ | (name, age) : \"{name} is {age}\" println
+{} println
+{(1, true): \"a\", (0, false): \"b\"} println";
+        // Keys are kept in order, whatever order they were added in
+        assert_eq!(output(source), "{alice: 27, bob: 31}\n31\nalice is 27\nbob is 32\ncarol is 45\n{}\n{(0, false): b, (1, true): a}\n");
+    }
+
+    #[test]
+    fn dict_builtins() {
+        let source = "\
+let ages = {\"bob\": 31}
+(ages, \"bob\") get println
+(ages, \"dave\") get println
+(ages, \"bob\") contains println
+(ages, \"dave\") contains println
+let more = (ages, \"dave\", 40) set
+more println
+ages println
+(more, \"bob\") remove println
+(more, \"carol\") remove println";
+        // set and remove give back a copy, leaving the dictionary they're given alone
+        assert_eq!(output(source), "31\nNone\ntrue\nfalse\n{bob: 31, dave: 40}\n{bob: 31}\n{dave: 40}\n{bob: 31, dave: 40}\n");
+
+        assert_eq!(error("let ages = {\"bob\": 31}\nages[\"carol\"]"), ("Key carol is not in the dictionary".to_string(), "\"carol\"".to_string()));
+        assert_eq!(error("let ages = {\"bob\": 31}\nlet key = \"bob\"\nkey = [1]\nages[key] = 2"), ("Cannot use [1] as a dictionary key".to_string(), "key".to_string()));
+    }
+
+    #[test]
+    fn runtime_errors_point_at_their_expression() {
+        assert_eq!(error("let x = 1\nx = \"a\"\nx + 1"), ("Cannot add a and 1".to_string(), "x + 1".to_string()));
+        assert_eq!(error("let x = 1\nx = true\n-x"), ("Cannot negate true".to_string(), "-x".to_string()));
+        assert_eq!(error("(1 == 2) assert"), ("Assertion failed!".to_string(), "(1 == 2) assert".to_string()));
+        // Errors from inside a match point inside it, rather than at the call
+        assert_eq!(error("let f = | n : n * 2\nlet s = 1\ns = \"a\"\ns f"), ("Cannot multiply a and 2".to_string(), "n * 2".to_string()));
+        assert_eq!(error("let b = true\nb = 1\nlet f = | n ~ b : n\n1 f"), ("Guard must return a boolean. Got: 1".to_string(), "b".to_string()));
+    }
+}
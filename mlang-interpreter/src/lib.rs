@@ -1,21 +1,20 @@
+// Diagnostics carry labels, notes and help, so they are large. They are only built on the error path.
+#![allow(clippy::result_large_err)]
+
 pub mod interpret;
 pub mod program;
 
 pub mod prelude {
-    use std::fmt::Display;
+    pub use mlang::prelude::{Diagnostic, DiagnosticKind, codes};
 
     pub type Result<T> = std::result::Result<T, ExecutionError>;
 
-    pub struct ExecutionError(pub String);
-
-    impl Display for ExecutionError {
-        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-            write!(f, "{}", self.0)
-        }
-    }
+    /// Runtime errors are diagnostics of kind [`DiagnosticKind::Runtime`]
+    pub type ExecutionError = Diagnostic;
 
+    /// A runtime error pointing at a span, if there is one. Errors without one point at the expression being worked out.
     macro_rules! exec_err {
-        ($($arg:tt)*) => (Err(ExecutionError(format!($($arg)*))))
+        ($code:expr, $span:expr, $($arg:tt)*) => (Err(Diagnostic::new(DiagnosticKind::Runtime, $code, format!($($arg)*)).with_span($span)))
     }
 
     pub(crate) use exec_err;
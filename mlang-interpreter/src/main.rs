@@ -1,15 +1,193 @@
-use mlang_interpreter::program::Program;
+mod repl;
 
-fn main() {
-    // print!("\x1Bc"); // Clear terminal
+use std::io::{IsTerminal, Read};
+use std::process::ExitCode;
 
-    let source = std::fs::read_to_string("./test_files/test2.mlg").unwrap();
+use mlang::tokenize::Indentation;
+use mlang_interpreter::program::{ErrorFormat, Program, ProgramError, Ready};
 
-    match Program::new(source).unwrap().parse_and_run() {
-        Ok(output) => println!("{}", output),
+const USAGE: &str = "\
+Usage:
+    mlang run [options] <file> [args...]    Run a script, passing any remaining arguments to it
+    mlang check [options] <file>            Tokenize, parse and verify a script without running it
+    mlang repl [options]                    Start an interactive session
+
+Use - as the file to read the script from stdin.
+
+Options:
+    --color=<auto|always|never>    Whether to colour error messages. Defaults to auto
+    --error-format=<human|json>    How to print error messages. json prints one object per line.
+                                   Defaults to human
+    --indentation=<spaces|consistent|tab-width:N>
+                                   How lines may be indented. spaces forbids tabs, consistent allows
+                                   tabs or spaces but not both, and tab-width:N allows tabs which
+                                   move to the next multiple of N columns. Defaults to spaces
+    --strict                       Make matches which don't cover every value errors rather than warnings";
+
+// Exit codes
+const EXIT_SUCCESS: u8 = 0;
+const EXIT_RUNTIME_ERR: u8 = 1;
+const EXIT_USAGE_ERR: u8 = 2;
+const EXIT_SYNTAX_ERR: u8 = 3;
+const EXIT_SEMANTIC_ERR: u8 = 4;
+const EXIT_COMPILER_ERR: u8 = 5;
+const EXIT_IO_ERR: u8 = 6;
+
+enum Command {
+    Run { file: String, args: Vec<String> },
+    Check { file: String },
+    Repl,
+}
+
+#[derive(Default)]
+pub struct Options {
+    pub color: Option<bool>,
+    pub error_format: Option<ErrorFormat>,
+    pub indentation: Indentation,
+    pub strict: bool,
+}
+
+impl Options {
+    /// Whether error messages written to stderr should be coloured
+    pub fn use_color(&self) -> bool {
+        self.color.unwrap_or_else(|| {
+            std::io::stderr().is_terminal() && std::env::var_os("NO_COLOR").is_none()
+        })
+    }
+
+    pub fn error_format(&self) -> ErrorFormat {
+        self.error_format.unwrap_or(ErrorFormat::Human)
+    }
+}
+
+fn parse_args(args: impl Iterator<Item = String>) -> Result<(Command, Options), String> {
+    let mut args = args.peekable();
+    let command = args.next().ok_or("No command given")?;
+
+    let mut options = Options::default();
+    while let Some(option) = args.next_if(|arg| arg.starts_with("--")) {
+        match option.split_once('=') {
+            None if option == "--strict" => options.strict = true,
+            Some(("--color", "auto")) => options.color = None,
+            Some(("--color", "always")) => options.color = Some(true),
+            Some(("--color", "never")) => options.color = Some(false),
+            Some(("--error-format", "human")) => options.error_format = Some(ErrorFormat::Human),
+            Some(("--error-format", "json")) => options.error_format = Some(ErrorFormat::Json),
+            Some(("--indentation", "spaces")) => options.indentation = Indentation::Spaces,
+            Some(("--indentation", "consistent")) => options.indentation = Indentation::Consistent,
+            Some(("--indentation", policy)) if policy.starts_with("tab-width:") => {
+                let width = policy["tab-width:".len()..].parse()
+                    .ok()
+                    .filter(|&width| width > 0)
+                    .ok_or_else(|| format!("Invalid tab width: {}", option))?;
+                options.indentation = Indentation::TabWidth(width);
+            }
+            _ => return Err(format!("Unknown option: {}", option)),
+        }
+    }
+
+    let command = match command.as_str() {
+        "run" => {
+            let file = args.next().ok_or("No file given to run")?;
+            Ok(Command::Run { file, args: args.collect() })
+        }
+        "check" => {
+            let file = args.next().ok_or("No file given to check")?;
+            if let Some(extra) = args.next() {
+                return Err(format!("Unexpected argument: {}", extra));
+            }
+            Ok(Command::Check { file })
+        }
+        "repl" => {
+            if let Some(extra) = args.next() {
+                return Err(format!("Unexpected argument: {}", extra));
+            }
+            Ok(Command::Repl)
+        }
+        other => Err(format!("Unknown command: {}", other)),
+    }?;
+
+    Ok((command, options))
+}
+
+fn read_source(file: &str) -> std::io::Result<String> {
+    if file == "-" {
+        let mut source = String::new();
+        std::io::stdin().read_to_string(&mut source)?;
+        Ok(source)
+    } else {
+        std::fs::read_to_string(file)
+    }
+}
+
+/// Prints the warnings found while verifying the program to stderr
+fn report_warnings(program: &Program<Ready>) {
+    if !program.warnings().is_empty() {
+        eprintln!("{}", program.render_diagnostics(program.warnings()));
+    }
+}
+
+fn exit_code_of(error: &ProgramError) -> u8 {
+    match error {
+        ProgramError::Syntax(_) => EXIT_SYNTAX_ERR,
+        ProgramError::Semantic(_) => EXIT_SEMANTIC_ERR,
+        ProgramError::Compiler(_) => EXIT_COMPILER_ERR,
+        ProgramError::Runtime(_) => EXIT_RUNTIME_ERR,
+    }
+}
+
+fn main() -> ExitCode {
+    let (command, options) = match parse_args(std::env::args().skip(1)) {
+        Ok(parsed) => parsed,
+        Err(message) => {
+            eprintln!("{}\n\n{}", message, USAGE);
+            return ExitCode::from(EXIT_USAGE_ERR);
+        }
+    };
+
+    let file = match &command {
+        Command::Run { file, .. } | Command::Check { file } => file,
+        Command::Repl => {
+            repl::run(&options);
+            return ExitCode::from(EXIT_SUCCESS);
+        }
+    };
+
+    let source = match read_source(file) {
+        Ok(source) => source,
+        Err(error) => {
+            eprintln!("Could not read {}: {}", file, error);
+            return ExitCode::from(EXIT_IO_ERR);
+        }
+    };
+
+    let name = if file == "-" { "<stdin>" } else { file };
+    let program = match Program::new(source) {
+        Ok(program) => program
+            .with_name(name)
+            .with_color(options.use_color())
+            .with_error_format(options.error_format())
+            .with_indentation(options.indentation)
+            .with_strict(options.strict),
+        Err(error) => {
+            eprintln!("{}", error);
+            return ExitCode::from(EXIT_IO_ERR);
+        }
+    };
+
+    let result = match command {
+        Command::Run { args, .. } => program.parse_and_verify()
+            .inspect(report_warnings)
+            .and_then(|program| program.run_to_stdout(args)),
+        Command::Check { .. } => program.parse_and_verify().map(|program| report_warnings(&program)),
+        Command::Repl => unreachable!("The repl does not read a file"),
+    };
+
+    match result {
+        Ok(()) => ExitCode::from(EXIT_SUCCESS),
         Err(error) => {
-            println!("{}", error);
-            std::process::exit(1)
-        },
+            eprintln!("{}", error);
+            ExitCode::from(exit_code_of(&error))
+        }
     }
-}
\ No newline at end of file
+}
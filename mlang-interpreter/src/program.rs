@@ -1,157 +1,292 @@
-use crate::interpret::Executable;
-use crate::interpret::environment::Env;
+use crate::interpret::{Executable, Value};
+use crate::interpret::environment::{Env, Output};
 
+use std::fmt::Display;
+
+use mlang::prelude::{Diagnostic, DiagnosticKind};
 use mlang::constructs::ast::AST;
 use mlang::constructs::token::{Tokens, Token};
+use mlang::constructs::token::span::{FileId, SourceFile, SourceMap};
+use mlang::tokenize::{Indentation, TokenizeOptions};
+use mlang::verify::VerifyOptions;
+
+use self::error_handling::render_diagnostic;
+use self::json::diagnostic_to_json;
+
+mod error_handling;
+mod json;
+
+/// An error raised while taking a program from source to output, 
+/// already rendered against the program's source.
+#[derive(Debug, Clone)]
+pub enum ProgramError {
+    Syntax(String),
+    Semantic(String),
+    Compiler(String),
+    Runtime(String),
+}
 
-use self::error_handling::display_error;
+impl ProgramError {
+    /// Categorises every diagnostic by the first one.
+    fn from_diagnostics(errors: &[Diagnostic], rendered: String) -> Self {
+        match errors.first().map(|err| err.kind) {
+            Some(DiagnosticKind::Semantic) => ProgramError::Semantic(rendered),
+            Some(DiagnosticKind::Compiler) => ProgramError::Compiler(rendered),
+            Some(DiagnosticKind::Runtime) => ProgramError::Runtime(rendered),
+            Some(DiagnosticKind::Syntax) | None => ProgramError::Syntax(rendered),
+        }
+    }
+}
+
+impl Display for ProgramError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ProgramError::Syntax(s) |
+            ProgramError::Semantic(s) |
+            ProgramError::Compiler(s) |
+            ProgramError::Runtime(s) => write!(f, "{}", s),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ErrorFormat {
+    /// Rendered with snippets of the source, for people to read
+    Human,
+    /// One JSON object per diagnostic, each on its own line, for tools to read
+    Json,
+}
+
+/// How a program's diagnostics are turned into text
+#[derive(Debug, Clone)]
+struct DiagnosticSettings {
+    /// Whether to colour human-readable error messages with ANSI escape codes
+    color: bool,
+    format: ErrorFormat,
+}
+
+impl DiagnosticSettings {
+    /// Renders the diagnostics against the files in `sources`. Labels that don't say which file they're in are in `file`.
+    fn render(&self, sources: &SourceMap, file: FileId, errors: &[Diagnostic]) -> String {
+        match self.format {
+            ErrorFormat::Human => errors.iter()
+                .map(|err| render_diagnostic(sources, file, err, self.color))
+                .collect::<Vec<_>>()
+                .join("\n\n"),
+            ErrorFormat::Json => errors.iter()
+                .map(|err| diagnostic_to_json(sources, file, err))
+                .collect::<Vec<_>>()
+                .join("\n"),
+        }
+    }
+}
 
 pub trait ProgramStatus { type ProgramData; }
 pub struct Unparsed;
 pub struct Tokenized;
 pub struct Parsed;
 pub struct Ready;
-impl ProgramStatus for Unparsed { type ProgramData = (); }
+impl ProgramStatus for Unparsed { type ProgramData = TokenizeOptions; }
 impl ProgramStatus for Tokenized { type ProgramData = Vec<Token>; }
 impl ProgramStatus for Parsed { type ProgramData = AST; }
 impl ProgramStatus for Ready { type ProgramData = AST; }
 
 pub struct Program<S: ProgramStatus> {
-    source: String,
+    sources: SourceMap,
+    /// The program's own file in `sources`
+    file: FileId,
+    settings: DiagnosticSettings,
+    verify_options: VerifyOptions,
+    /// Diagnostics which don't stop the program from running, like a match that doesn't cover every value
+    warnings: Vec<Diagnostic>,
     data: S::ProgramData,
 }
 
+impl<S: ProgramStatus> Program<S> {
+    fn with_data<T: ProgramStatus>(self, data: T::ProgramData) -> Program<T> {
+        Program {
+            sources: self.sources,
+            file: self.file,
+            settings: self.settings,
+            verify_options: self.verify_options,
+            warnings: self.warnings,
+            data
+        }
+    }
+
+    pub fn warnings(&self) -> &[Diagnostic] {
+        &self.warnings
+    }
+
+    /// Renders the diagnostics in the program's error format
+    pub fn render_diagnostics(&self, errors: &[Diagnostic]) -> String {
+        self.settings.render(&self.sources, self.file, errors)
+    }
+
+    /// Converts each diagnostic to a JSON object, one per line. Each object has the file,
+    /// code, severity and message of the diagnostic, and the byte range and line/column of its spans.
+    pub fn diagnostics_to_json(&self, errors: &[Diagnostic]) -> String {
+        errors.iter()
+            .map(|err| diagnostic_to_json(&self.sources, self.file, err))
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    fn source(&self) -> &SourceFile {
+        self.sources.get(self.file).expect("The program's file is in its source map")
+    }
+
+    /// Notes that the diagnostics are about this program's file
+    fn in_file(&self, diagnostics: impl IntoIterator<Item = Diagnostic>) -> Vec<Diagnostic> {
+        diagnostics.into_iter().map(|diagnostic| diagnostic.in_file(self.file)).collect()
+    }
+
+    fn to_error(&self, errors: impl IntoIterator<Item = Diagnostic>) -> ProgramError {
+        let errors = self.in_file(errors);
+        ProgramError::from_diagnostics(&errors, self.render_diagnostics(&errors))
+    }
+}
+
 impl Program<Unparsed> {
     pub fn new(source: String) -> Result<Self, std::io::Error> {
+        let mut sources = SourceMap::default();
+        let file = sources.add_file("<input>", source);
         Ok(Program { 
-            source,
-            data: () 
+            sources,
+            file,
+            settings: DiagnosticSettings {
+                color: false,
+                format: ErrorFormat::Human,
+            },
+            verify_options: VerifyOptions::default(),
+            warnings: Vec::new(),
+            data: TokenizeOptions::default()
         })
     }
 
-    pub fn tokenize(self) -> Result<Program<Tokenized>, String> {
-        let tokens = match mlang::tokenize::parse_tokens(&self.source) {
+    pub fn with_name(mut self, name: impl Into<String>) -> Self {
+        if let Some(file) = self.sources.get_mut(self.file) {
+            file.set_name(name);
+        }
+        self
+    }
+
+    pub fn with_color(mut self, color: bool) -> Self {
+        self.settings.color = color;
+        self
+    }
+
+    pub fn with_error_format(mut self, format: ErrorFormat) -> Self {
+        self.settings.format = format;
+        self
+    }
+
+    pub fn with_indentation(mut self, indentation: Indentation) -> Self {
+        self.data.indentation = indentation;
+        self
+    }
+
+    /// Makes matches which don't cover every value errors rather than warnings
+    pub fn with_strict(mut self, strict: bool) -> Self {
+        self.verify_options.strict = strict;
+        self
+    }
+
+    /// Lets the program use identifiers defined before it runs, like those from earlier REPL inputs
+    pub fn with_defined(mut self, identifiers: Vec<String>) -> Self {
+        self.verify_options.defined = identifiers;
+        self
+    }
+
+    pub fn tokenize(self) -> Result<Program<Tokenized>, ProgramError> {
+        let tokens = match mlang::tokenize::parse_tokens_with_options(self.source().source(), &self.data) {
             Ok(tokens) => tokens,
-            Err(e) => return Err(display_error(&self.source, e))
+            Err(e) => return Err(self.to_error([e]))
         };
 
-        Ok(Program { 
-            source: self.source, 
-            data: tokens 
-        })
+        Ok(self.with_data(tokens))
     }
     
     // Shortcuts
-    pub fn parse_and_run(self) -> Result<String, String> {
+    /// Runs the program, returning whatever it printed along with how it went. A program that
+    /// doesn't get as far as running prints nothing.
+    pub fn parse_and_run(self) -> (String, Result<(), ProgramError>) {
+        match self.parse_and_verify() {
+            Ok(program) => program.run(),
+            Err(error) => (String::new(), Err(error)),
+        }
+    }
+
+    pub fn parse_and_verify(self) -> Result<Program<Ready>, ProgramError> {
         self.tokenize()?
             .parse()?
-            .verify()?
-            .run()
+            .verify()
     }
 }
 
 impl Program<Tokenized> {
-    pub fn parse(self) -> Result<Program<Parsed>, String> {
+    pub fn tokens(&self) -> &Vec<Token> {
+        &self.data
+    }
+
+    pub fn parse(self) -> Result<Program<Parsed>, ProgramError> {
         let mut tokens = Tokens::new(&self.data);
-        let ast = match mlang::parse::parse(&mut tokens) {
-            Ok(ast) => ast,
-            Err(e) => return Err(display_error(&self.source, e))
-        };
+        let (ast, errors) = mlang::parse::parse(&mut tokens);
+        if !errors.is_empty() {
+            return Err(self.to_error(errors));
+        }
 
-        Ok(Program { source: self.source, data: ast })
+        Ok(self.with_data(ast))
     }
 }
 impl Program<Parsed> {
-    pub fn verify(self) -> Result<Program<Ready>, String> {
-        let ast = match mlang::verify::verify(self.data) {
-            Ok(ast) => ast,
-            Err(e) => return Err(display_error(&self.source, e))
-        };
-
-        Ok(Program { source: self.source, data: ast })
+    pub fn ast(&self) -> &AST {
+        &self.data
     }
-}
-
-impl Program<Ready> {
-    pub fn run(&self) -> Result<String, String> {
-        let mut output = String::new();
-        let mut env = Env::default();
-        let result = self.data.0.execute(&mut env);
 
-        env.write_to_string(&mut output);
+    /// Checks the program before it runs. Warnings are kept on the program, unless there are errors,
+    /// in which case the errors are returned along with the warnings.
+    pub fn verify(self) -> Result<Program<Ready>, ProgramError> {
+        let Program { sources, file, settings, verify_options, mut warnings, data } = self;
 
-        match result {
-            Ok(_) => Ok(output),
-            Err(e) => Err(e.to_string())
+        let (ast, diagnostics) = mlang::verify::verify_with_options(data, &verify_options);
+        let diagnostics: Vec<Diagnostic> = diagnostics.into_iter().map(|diagnostic| diagnostic.in_file(file)).collect();
+        if diagnostics.iter().any(Diagnostic::is_error) {
+            return Err(ProgramError::from_diagnostics(&diagnostics, settings.render(&sources, file, &diagnostics)));
         }
+
+        warnings.extend(diagnostics);
+        Ok(Program { sources, file, settings, verify_options, warnings, data: ast })
     }
 }
 
-// Error prettifying code. (Warning: This sucks)
-mod error_handling {
-    use mlang::prelude::*;
-    use mlang::constructs::token::span::Span;
-
-    pub fn display_error(input_string: &String, err: MLGError) -> String {
-        match err {
-            ref err @ MLGError::SyntaxErr(Some(span), ..) |
-            ref err @ MLGError::SemanticErr(Some(span), ..) => {
-                let snippet = get_snippet(input_string, span);
-                format!("An error was found in the following code:\n{}\n       {}\n{}", 
-                    snippet, 
-                    get_error_highlight(input_string, span),
-                    err
-                )
-            }
-            _ => err.to_string()
-        }
+impl Program<Ready> {
+    pub fn ast(&self) -> &AST {
+        &self.data
     }
 
-    fn line_number(input: &str, index: usize) -> usize {
-        // Count the newlines before the given index
-        1 + input.chars()
-            .take(index)
-            .filter(|&chr| chr == '\n')
-            .count()
+    pub fn run(&self) -> (String, Result<(), ProgramError>) {
+        self.run_with_args(Vec::new())
     }
 
-    fn get_snippet(input: &str, span: Span) -> String {
-        let line_span = {
-            let beginning_line = line_number(input, span.beginning());
-            Span {
-                index: beginning_line as u32,
-                len: 1 + (line_number(input, span.ending()) - beginning_line) as u16
-            }
-        };
+    /// Runs the program with `args` bound to the `args` identifier as a list of strings.
+    /// The output is returned even if the program fails, since it may have printed before failing.
+    pub fn run_with_args(&self, args: Vec<String>) -> (String, Result<(), ProgramError>) {
+        let mut env = Env::with_args(args);
+        let result = self.run_in(&mut env);
 
-        const NUM_PREV_LINES: usize = 3;
+        (env.take_output(), result.map(|_| ()))
+    }
 
-        input.lines()
-            .enumerate()
-            .map(|(index, line)| (index + 1, line))
-            .skip(line_span.beginning().saturating_sub(NUM_PREV_LINES + 1))
-            .take(line_span.length() + NUM_PREV_LINES)
-            .fold(String::new(), |prev, (line_number, line)| {
-                format!("{prev}\n{line_number:5}: {line}")
-            })
+    /// Runs the program like [`Program::run_with_args`], but prints to stdout as it goes rather than returning the output
+    pub fn run_to_stdout(&self, args: Vec<String>) -> Result<(), ProgramError> {
+        let mut env = Env::with_args(args).with_output(Output::Stdout);
+        self.run_in(&mut env).map(|_| ())
     }
-    
-    fn get_error_highlight(input: &String, span: Span) -> String {
-        let line_start_index;
-
-        if let Some((index, _)) = input.as_bytes().iter()
-            .enumerate()
-            .take(span.beginning())
-            .rev()
-            .find(|(_, &chr)| chr as char == '\n') 
-        {
-            line_start_index = index + 1; // Line starts AFTER the newline
-        } else {
-            line_start_index = 0;
-        }
-        
-        let blank_space_length = span.beginning() - line_start_index;
-    
-        " ".repeat(blank_space_length) + &"^".repeat(span.length())
+
+    /// Runs the program inside an existing environment, keeping any identifiers it defines.
+    /// Printed output is left in the environment rather than returned.
+    pub fn run_in(&self, env: &mut Env) -> Result<Value, ProgramError> {
+        self.data.0.execute(env).map_err(|e| self.to_error([e]))
     }
-}
\ No newline at end of file
+}
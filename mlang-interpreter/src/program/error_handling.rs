@@ -0,0 +1,224 @@
+use mlang::prelude::*;
+use mlang::constructs::token::span::{ColumnUnit, FileId, LineColumn, SourceFile, SourceMap};
+
+// ANSI colour codes
+const RED: &str = "1;31";
+const YELLOW: &str = "1;33";
+const CYAN: &str = "1;36";
+const BLUE: &str = "1;34";
+const BOLD: &str = "1";
+
+struct Painter {
+    color: bool,
+}
+
+impl Painter {
+    fn paint(&self, style: &str, text: &str) -> String {
+        if self.color {
+            format!("\x1b[{style}m{text}\x1b[0m")
+        } else {
+            text.to_string()
+        }
+    }
+
+    fn severity_style(severity: Severity) -> &'static str {
+        match severity {
+            Severity::Error => RED,
+            Severity::Warning => YELLOW,
+            Severity::Note => CYAN,
+        }
+    }
+}
+
+struct PositionedLabel<'a> {
+    start: LineColumn,
+    /// The position just after the last character of the label. Columns count characters.
+    end: LineColumn,
+    message: Option<&'a str>,
+    is_primary: bool,
+}
+
+impl<'a> PositionedLabel<'a> {
+    fn new(file: &SourceFile, label: &'a Label, is_primary: bool) -> Self {
+        let start = file.line_column(label.span.beginning(), ColumnUnit::Char);
+        let last = file.line_column(label.span.ending().max(label.span.beginning()), ColumnUnit::Char);
+
+        PositionedLabel {
+            start,
+            end: LineColumn { line: last.line, column: last.column + 1 },
+            message: label.message.as_deref(),
+            is_primary,
+        }
+    }
+}
+
+/// Renders a diagnostic in the style of:
+/// ```text
+/// error[E0201]: Unexpected token
+///  --> main.mlg:2:9
+///   |
+/// 2 | let b = )
+///   |         ^ label message
+///   |
+///   = note: a note
+/// ```
+/// Each label is shown in the file it's in, looked up in `sources`, or in `file` if it doesn't say.
+/// Labels in other files than the primary label's are shown after it, under their file's name.
+pub fn render_diagnostic(sources: &SourceMap, file: FileId, diagnostic: &Diagnostic, color: bool) -> String {
+    let painter = Painter { color };
+    let severity_style = Painter::severity_style(diagnostic.severity);
+
+    let labels = diagnostic.primary.iter()
+        .map(|label| (label, true))
+        .chain(diagnostic.secondary.iter().map(|label| (label, false)));
+
+    let mut files: Vec<(&SourceFile, Vec<PositionedLabel>)> = Vec::new();
+    for (label, is_primary) in labels {
+        let Some(source) = sources.get(label.file.unwrap_or(file)) else {
+            continue;
+        };
+
+        let positioned = PositionedLabel::new(source, label, is_primary);
+        match files.iter_mut().find(|(shown, _)| shown.id() == source.id()) {
+            Some((_, labels)) => labels.push(positioned),
+            None => files.push((source, vec![positioned])),
+        }
+    }
+
+    let shown_lines: Vec<Vec<usize>> = files.iter()
+        .map(|(_, labels)| {
+            let mut lines: Vec<usize> = labels.iter()
+                .flat_map(|label| label.start.line..=label.end.line)
+                .collect();
+            lines.sort();
+            lines.dedup();
+            lines
+        })
+        .collect();
+
+    let gutter_width = shown_lines.iter()
+        .filter_map(|lines| lines.last())
+        .max()
+        .map_or(1, |line| (line + 1).to_string().len());
+    let gutter = painter.paint(BLUE, &format!("{} |", " ".repeat(gutter_width)));
+
+    let mut output = format!("{}{}",
+        painter.paint(severity_style, &format!("{}[{}]", diagnostic.severity, diagnostic.code)),
+        painter.paint(BOLD, &format!(": {}", diagnostic.message)),
+    );
+
+    if files.is_empty() {
+        let name = sources.get(file).map_or("", SourceFile::name);
+        output += &format!("\n{}{} {}", " ".repeat(gutter_width), painter.paint(BLUE, "-->"), name);
+    }
+
+    for (idx, ((source, labels), lines)) in files.iter().zip(&shown_lines).enumerate() {
+        let arrow = if idx == 0 { "-->" } else { ":::" };
+        if idx > 0 {
+            output += &format!("\n{}", gutter);
+        }
+        let first = &labels[0];
+        output += &format!("\n{}{} {}:{}:{}", " ".repeat(gutter_width), painter.paint(BLUE, arrow), source.name(), first.start.line + 1, first.start.column + 1);
+        output += &format!("\n{}", gutter);
+
+        for (idx, &line) in lines.iter().enumerate() {
+            if idx > 0 && lines[idx - 1] + 1 != line {
+                output += &format!("\n{}", painter.paint(BLUE, "..."));
+            }
+
+            let text = source.line(line).unwrap_or_default();
+            output += &format!("\n{} {}",
+                painter.paint(BLUE, &format!("{:>gutter_width$} |", line + 1)),
+                text
+            );
+
+            for label in labels.iter().filter(|label| (label.start.line..=label.end.line).contains(&line)) {
+                output += &format!("\n{} {}", gutter, underline(text, line, label, &painter, severity_style));
+            }
+        }
+    }
+
+    if !files.is_empty() && (!diagnostic.notes.is_empty() || diagnostic.help.is_some()) {
+        output += &format!("\n{}", gutter);
+    }
+
+    let annotations = diagnostic.notes.iter()
+        .map(|note| ("note", note))
+        .chain(diagnostic.help.iter().map(|help| ("help", help)));
+
+    for (kind, text) in annotations {
+        output += &format!("\n{} {} {}",
+            " ".repeat(gutter_width),
+            painter.paint(BLUE, "="),
+            painter.paint(BOLD, &format!("{}:", kind)) + " " + text
+        );
+    }
+
+    output
+}
+
+/// The marker line underneath one line of a label
+fn underline(text: &str, line: usize, label: &PositionedLabel, painter: &Painter, severity_style: &str) -> String {
+    let line_length = text.chars().count();
+
+    let from = if line == label.start.line {
+        label.start.column
+    } else {
+        text.chars().take_while(|chr| chr.is_whitespace()).count()
+    };
+    let to = if line == label.end.line {
+        label.end.column
+    } else {
+        line_length
+    };
+    let to = to.max(from + 1);
+
+    // Keep tabs so the markers line up with the source above them
+    let padding: String = text.chars()
+        .chain(std::iter::repeat(' '))
+        .take(from)
+        .map(|chr| if chr == '\t' { '\t' } else { ' ' })
+        .collect();
+
+    let (marker, style) = if label.is_primary { ('^', severity_style) } else { ('-', BLUE) };
+    let mut markers = marker.to_string().repeat(to - from);
+
+    if line == label.end.line {
+        if let Some(message) = label.message {
+            markers = format!("{} {}", markers, message);
+        }
+    }
+
+    padding + &painter.paint(style, &markers)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mlang::constructs::token::span::Span;
+
+    #[test]
+    fn labels_are_shown_in_their_own_files() {
+        let mut sources = SourceMap::default();
+        let main = sources.add_file("main.mlg", "let a = b\n");
+        let other = sources.add_file("other.mlg", "let b = 1\n");
+
+        let mut diagnostic = Diagnostic::new(DiagnosticKind::Semantic, codes::UNRESOLVED_IDENTIFIER, "Identifier b is used before it's defined".to_string())
+            .with_label(Span { index: 8, len: 1 }, "used here")
+            .with_secondary_label(Span { index: 4, len: 1 }, "defined here");
+        diagnostic.secondary[0].file = Some(other);
+
+        let rendered = render_diagnostic(&sources, main, &diagnostic.in_file(main), false);
+        assert_eq!(rendered, "\
+error[E0306]: Identifier b is used before it's defined
+ --> main.mlg:1:9
+  |
+1 | let a = b
+  |         ^ used here
+  |
+ ::: other.mlg:1:5
+  |
+1 | let b = 1
+  |     - defined here");
+    }
+}
@@ -0,0 +1,108 @@
+use mlang::prelude::*;
+use mlang::constructs::token::span::{ColumnUnit, FileId, SourceFile, SourceMap};
+
+/// Converts a diagnostic to a single line of JSON, e.g.
+/// ```text
+/// {"file":"main.mlg","kind":"syntax","severity":"error","code":"E0201","message":"Unexpected token",
+///  "span":{"start":8,"end":9,"line":2,"column":9,"end_line":2,"end_column":10},
+///  "labels":[{"primary":true,"message":null,"span":{...}}],"notes":[],"help":null}
+/// ```
+/// Byte ranges are half-open. Lines and columns start at 1, and columns count characters.
+/// `span` is the span of the primary label, or null if the diagnostic has no location.
+/// Each label is in the file it says it's in, looked up in `sources`, or in `file` if it doesn't say.
+/// `file` at the top is the primary label's file, and each label also has its own.
+pub fn diagnostic_to_json(sources: &SourceMap, file: FileId, diagnostic: &Diagnostic) -> String {
+    let source_of = |label: &Label| sources.get(label.file.unwrap_or(file));
+
+    let kind = match diagnostic.kind {
+        DiagnosticKind::Syntax => "syntax",
+        DiagnosticKind::Semantic => "semantic",
+        DiagnosticKind::Runtime => "runtime",
+        DiagnosticKind::Compiler => "compiler",
+    };
+
+    let primary = diagnostic.primary.as_ref().and_then(|label| Some((label, source_of(label)?)));
+    let name = match primary {
+        Some((_, source)) => source.name(),
+        None => sources.get(file).map_or("", SourceFile::name),
+    };
+    let span = primary.map_or("null".to_string(), |(label, source)| span_to_json(source, label));
+
+    // Labels in files that aren't in `sources` can't be given a position, so they're left out
+    let labels = diagnostic.primary.iter()
+        .map(|label| (label, true))
+        .chain(diagnostic.secondary.iter().map(|label| (label, false)))
+        .filter_map(|(label, is_primary)| Some(label_to_json(source_of(label)?, label, is_primary)))
+        .collect::<Vec<_>>()
+        .join(",");
+
+    let notes = diagnostic.notes.iter()
+        .map(|note| string(note))
+        .collect::<Vec<_>>()
+        .join(",");
+
+    format!(
+        r#"{{"file":{},"kind":"{}","severity":"{}","code":"{}","message":{},"span":{},"labels":[{}],"notes":[{}],"help":{}}}"#,
+        string(name),
+        kind,
+        diagnostic.severity,
+        diagnostic.code,
+        string(&diagnostic.message),
+        span,
+        labels,
+        notes,
+        optional_string(diagnostic.help.as_deref()),
+    )
+}
+
+fn label_to_json(file: &SourceFile, label: &Label, is_primary: bool) -> String {
+    format!(
+        r#"{{"file":{},"primary":{},"message":{},"span":{}}}"#,
+        string(file.name()),
+        is_primary,
+        optional_string(label.message.as_deref()),
+        span_to_json(file, label),
+    )
+}
+
+fn span_to_json(file: &SourceFile, label: &Label) -> String {
+    let start = label.span.beginning();
+    let end = start + label.span.length();
+    let start_position = file.line_column(start, ColumnUnit::Char);
+    let end_position = file.line_column(end, ColumnUnit::Char);
+
+    format!(
+        r#"{{"start":{},"end":{},"line":{},"column":{},"end_line":{},"end_column":{}}}"#,
+        start,
+        end,
+        start_position.line + 1,
+        start_position.column + 1,
+        end_position.line + 1,
+        end_position.column + 1,
+    )
+}
+
+fn optional_string(text: Option<&str>) -> String {
+    text.map_or("null".to_string(), string)
+}
+
+/// Quotes and escapes a string as a JSON string literal
+fn string(text: &str) -> String {
+    let mut quoted = String::with_capacity(text.len() + 2);
+    quoted.push('"');
+
+    for chr in text.chars() {
+        match chr {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            chr if (chr as u32) < 0x20 => quoted.push_str(&format!("\\u{:04x}", chr as u32)),
+            chr => quoted.push(chr),
+        }
+    }
+
+    quoted.push('"');
+    quoted
+}
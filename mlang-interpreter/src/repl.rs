@@ -0,0 +1,326 @@
+use std::io::{BufRead, Write};
+
+use mlang::constructs::token::{Token, TokenKind};
+use mlang::prelude::codes;
+use mlang::tokenize::TokenizeOptions;
+use mlang_interpreter::interpret::environment::{Env, Output};
+use mlang_interpreter::interpret::Value;
+use mlang_interpreter::program::{Program, ProgramError, Ready, Tokenized};
+
+use crate::Options;
+
+const HELP: &str = "\
+Enter mlang code to run it. Identifiers defined with let stay around for later inputs.
+Lines ending in a colon, an equals sign, a binary operator, an open bracket or an unfinished string,
+and lines with a match on them, start a multi-line input. It carries on through lines which are
+indented further than its first line or start with a bar, and is finished by entering an empty line.
+A blank line inside an unfinished string is part of the string.
+
+Commands:
+    :tokens [code]    Print the tokens of the code, or of the previous input
+    :ast [code]       Print the syntax tree of the code, or of the previous input
+    :reset            Forget every identifier defined so far
+    :help             Show this message
+    :quit             Exit the repl, even in the middle of a multi-line input";
+
+pub fn run(options: &Options) {
+    let stdin = std::io::stdin();
+    let mut lines = stdin.lock().lines();
+    let tokenize_options = TokenizeOptions { indentation: options.indentation };
+
+    let mut session = Session::default();
+    let mut input = Input::default();
+
+    println!("mlang repl. Type :help for help.");
+
+    loop {
+        print!("{}", if input.is_empty() { "> " } else { "... " });
+        let _ = std::io::stdout().flush();
+
+        let line = match lines.next() {
+            Some(Ok(line)) => line,
+            Some(Err(error)) => {
+                eprintln!("Could not read input: {}", error);
+                break;
+            }
+            None => {
+                println!();
+                if let Some(code) = input.finish() {
+                    session.evaluate(&code, options);
+                }
+                break;
+            }
+        };
+
+        for entry in input.push(&line, &tokenize_options) {
+            match entry {
+                Entry::Command(name, code) => {
+                    if !session.command(&name, &code, options) {
+                        return;
+                    }
+                }
+                Entry::Code(code) => session.evaluate(&code, options),
+            }
+        }
+    }
+}
+
+/// What the repl remembers between inputs
+struct Session {
+    env: Env,
+    previous_input: String,
+}
+
+impl Default for Session {
+    fn default() -> Session {
+        Session { env: Env::default().with_output(Output::Stdout), previous_input: String::new() }
+    }
+}
+
+impl Session {
+    /// Runs a command, returning whether the repl should keep going
+    fn command(&mut self, name: &str, code: &str, options: &Options) -> bool {
+        let code = if code.trim().is_empty() { self.previous_input.as_str() } else { code };
+
+        match name {
+            "tokens" => match tokenize(code, options, Vec::new()) {
+                Ok(program) => for Token(kind, span) in program.tokens() {
+                    println!("{:?} @ {}..{}", kind, span.beginning(), span.beginning() + span.length());
+                },
+                Err(error) => eprintln!("{}", error),
+            },
+            "ast" => match prepare(code, options, &self.env) {
+                Ok(program) => println!("{:#?}", program.ast().0),
+                Err(error) => eprintln!("{}", error),
+            },
+            "reset" => {
+                self.env = Env::default().with_output(Output::Stdout);
+                println!("Environment reset.");
+            }
+            "help" => println!("{}", HELP),
+            "quit" | "q" => return false,
+            other => eprintln!("Unknown command :{}. Type :help for help.", other),
+        }
+        true
+    }
+
+    fn evaluate(&mut self, code: &str, options: &Options) {
+        match prepare(code, options, &self.env).and_then(|program| program.run_in(&mut self.env)) {
+            Ok(value) => {
+                if !matches!(value, Value::None) {
+                    println!("{}", value);
+                }
+            }
+            Err(error) => eprintln!("{}", error),
+        }
+
+        self.previous_input = code.to_string();
+    }
+}
+
+/// Something the user finished entering
+#[derive(Debug, PartialEq)]
+enum Entry {
+    /// A command like `:ast 1 + 2`, split into its name and the code after it
+    Command(String, String),
+    Code(String),
+}
+
+/// Gathers lines of code until they make up a whole input
+#[derive(Default)]
+struct Input {
+    buffer: String,
+    /// How far the first line of the buffer is indented
+    indent: usize,
+}
+
+impl Input {
+    fn is_empty(&self) -> bool {
+        self.buffer.is_empty()
+    }
+
+    /// Takes the next line, returning whatever it finishes. Commands are picked out before anything else,
+    /// so they work in the middle of a multi-line input, which carries on afterwards. A line which doesn't
+    /// continue the input before it finishes that input, and then starts the next one.
+    fn push(&mut self, line: &str, options: &TokenizeOptions) -> Vec<Entry> {
+        if let Some(command) = line.trim_start().strip_prefix(':') {
+            let (name, code) = command.split_once(' ').unwrap_or((command, ""));
+            return vec![Entry::Command(name.to_string(), code.to_string())];
+        }
+
+        let mut entries = Vec::new();
+        if !self.buffer.is_empty() {
+            let ending = Ending::of(&self.buffer, options);
+            let is_blank = line.trim().is_empty();
+
+            if ending.in_string || (!is_blank && (ending.is_unfinished || self.continues(line))) {
+                self.buffer.push('\n');
+                self.buffer.push_str(line);
+                return entries;
+            }
+
+            entries.push(Entry::Code(std::mem::take(&mut self.buffer)));
+            if is_blank {
+                return entries;
+            }
+        }
+
+        if line.trim().is_empty() {
+            return entries;
+        }
+
+        self.buffer.push_str(line);
+        self.indent = indent_of(line);
+
+        let ending = Ending::of(&self.buffer, options);
+        if !(ending.in_string || ending.is_unfinished || ending.has_match) {
+            entries.push(Entry::Code(std::mem::take(&mut self.buffer)));
+        }
+        entries
+    }
+
+    /// Whether the line carries on the multi-line input, by being an arm of a match or indented inside it
+    fn continues(&self, line: &str) -> bool {
+        line.trim_start().starts_with('|') || indent_of(line) > self.indent
+    }
+
+    /// Gives up on waiting for more lines, returning the code entered so far
+    fn finish(&mut self) -> Option<String> {
+        (!self.buffer.is_empty()).then(|| std::mem::take(&mut self.buffer))
+    }
+}
+
+fn indent_of(line: &str) -> usize {
+    line.len() - line.trim_start().len()
+}
+
+/// How the code entered so far ends
+#[derive(Default)]
+struct Ending {
+    /// In a string which hasn't been closed, where blank lines are part of the string
+    in_string: bool,
+    /// With a colon, an equals sign, a binary operator or inside brackets, so the next line must carry it on
+    is_unfinished: bool,
+    /// The code has a match outside of any brackets, whose other arms may be on the next lines
+    has_match: bool,
+}
+
+impl Ending {
+    fn of(code: &str, options: &TokenizeOptions) -> Ending {
+        // Apart from an unfinished string, code that fails to tokenize will not be fixed by more lines. Let it error.
+        let tokens = match mlang::tokenize::parse_tokens_with_options(code, options) {
+            Ok(tokens) => tokens,
+            Err(error) => return Ending { in_string: error.code == codes::UNTERMINATED_STRING, ..Ending::default() },
+        };
+
+        let mut depth = 0;
+        let mut has_match = false;
+        for Token(kind, ..) in &tokens {
+            depth += kind.nesting();
+            has_match |= depth == 0 && matches!(kind, TokenKind::Bar(_));
+        }
+
+        let is_unfinished = match tokens.last() {
+            _ if depth > 0 => true,
+            Some(Token(TokenKind::Colon | TokenKind::Equal, ..)) => true,
+            Some(Token(kind, span)) => kind.as_binary_operator(Some(*span)).is_ok(),
+            None => false,
+        };
+
+        Ending { in_string: false, is_unfinished, has_match }
+    }
+}
+
+fn tokenize(code: &str, options: &Options, defined: Vec<String>) -> Result<Program<Tokenized>, ProgramError> {
+    Program::new(code.to_string())
+        .map_err(|error| ProgramError::Compiler(error.to_string()))?
+        .with_name("<repl>")
+        .with_color(options.use_color())
+        .with_error_format(options.error_format())
+        .with_indentation(options.indentation)
+        .with_strict(options.strict)
+        .with_defined(defined)
+        .tokenize()
+}
+
+/// Gets the code ready to run in `env`, whose identifiers it can use
+fn prepare(code: &str, options: &Options, env: &Env) -> Result<Program<Ready>, ProgramError> {
+    let program = tokenize(code, options, env.identifiers())?
+        .parse()?
+        .verify()?;
+
+    if !program.warnings().is_empty() {
+        eprintln!("{}", program.render_diagnostics(program.warnings()));
+    }
+    Ok(program)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Feeds the lines to a fresh input, returning everything they finish and what's left unfinished
+    fn feed(lines: &[&str]) -> (Vec<Entry>, Option<String>) {
+        let mut input = Input::default();
+        let options = TokenizeOptions::default();
+        let entries = lines.iter().flat_map(|line| input.push(line, &options)).collect();
+        (entries, input.finish())
+    }
+
+    fn code(code: &str) -> Entry {
+        Entry::Code(code.to_string())
+    }
+
+    #[test]
+    fn single_lines_run_straight_away() {
+        assert_eq!(feed(&["let x = 1", "", "x println"]), (vec![code("let x = 1"), code("x println")], None));
+    }
+
+    #[test]
+    fn match_with_an_arm_per_line() {
+        let lines = ["let f =", "    | 0 : \"zero\"", "    | n : \"many\"", "0 f println"];
+        assert_eq!(feed(&lines), (vec![code("let f =\n    | 0 : \"zero\"\n    | n : \"many\""), code("0 f println")], None));
+
+        let lines = ["let f = | 0 : \"zero\"", "| n : \"many\"", ""];
+        assert_eq!(feed(&lines), (vec![code("let f = | 0 : \"zero\"\n| n : \"many\"")], None));
+    }
+
+    #[test]
+    fn arms_with_blocks() {
+        let lines = ["let f =", "    | 0 :", "        let y = 1", "        y", "    | n : n", ""];
+        assert_eq!(feed(&lines), (vec![code("let f =\n    | 0 :\n        let y = 1\n        y\n    | n : n")], None));
+    }
+
+    #[test]
+    fn open_brackets_carry_on() {
+        assert_eq!(feed(&["(1,", "2) println", ""]), (vec![code("(1,\n2) println")], None));
+        assert_eq!(feed(&["let x = 1 +", "2", ""]), (vec![code("let x = 1 +\n2")], None));
+    }
+
+    #[test]
+    fn blank_lines_inside_strings() {
+        let lines = ["let s = \"\"\"", "    first", "", "    last", "    \"\"\"", ""];
+        assert_eq!(feed(&lines), (vec![code("let s = \"\"\"\n    first\n\n    last\n    \"\"\"")], None));
+
+        let (entries, rest) = feed(&["let s = \"\"\"", "", ""]);
+        assert!(entries.is_empty());
+        assert_eq!(rest.as_deref(), Some("let s = \"\"\"\n\n"));
+    }
+
+    #[test]
+    fn commands_in_the_middle_of_input() {
+        let (entries, rest) = feed(&["let f =", "    | 0 : 1", ":quit"]);
+        assert_eq!(entries, vec![Entry::Command("quit".to_string(), String::new())]);
+        assert_eq!(rest.as_deref(), Some("let f =\n    | 0 : 1"));
+
+        let (entries, _) = feed(&["let s = \"\"\"", ":ast 1 + 2"]);
+        assert_eq!(entries, vec![Entry::Command("ast".to_string(), "1 + 2".to_string())]);
+    }
+
+    #[test]
+    fn quit_stops_the_session() {
+        let mut session = Session::default();
+        assert!(!session.command("quit", "", &Options::default()));
+        assert!(session.command("help", "", &Options::default()));
+    }
+}
@@ -11,14 +11,15 @@ fn run_program(
     error_occurred: UseStateHandle<bool>,
 ) {
     if let Ok(program) = Program::new((*code).clone()) {
-        output.set(match program.parse_and_run() {
-            Ok(out) => {
+        let (out, result) = program.parse_and_run();
+        output.set(match result {
+            Ok(()) => {
                 error_occurred.set(false);
                 out
             }
-            Err(out) => {
+            Err(error) => {
                 error_occurred.set(true);
-                out
+                format!("{}{}", out, error)
             }
         })
     } else {